@@ -0,0 +1,220 @@
+/*
+
+This file runs the benchmark suite used by the bench command.
+
+Each registered algorithm that can run the rule (see
+life_algorithms/mod.rs) runs each pattern of a fixed corpus for a
+fixed number of generations, from a fresh life object every time. Runs are
+repeated, and the time per generation is summarised by its mean,
+median and standard deviation, so that results can be compared across
+machines and commits without depending on the GUI's frame rate.
+
+Peak memory is the most resident memory the process used during a
+run. It is read from /proc/self/status, after resetting the peak
+through /proc/self/clear_refs, so it is only reported on Linux.
+
+Results can be written as JSON, or as CSV. CSV results are appended
+to an existing file, so one file can collect the results of many
+commits.
+
+*/
+
+use std::fs::{File,OpenOptions};
+use std::io::prelude::*;
+use std::path::{Path,PathBuf};
+use std::process;
+
+use num::BigUint;
+use time;
+
+use commands::advance_to;
+use common::LifeAlgorithm;
+use error::Error;
+use patterns::read_seed_from_file;
+
+// The patterns in assets/game_seeds that are benchmarked by default, with how many generations
+// each is run for
+pub const CORPUS: [(&'static str, u64); 3] = [
+    ("r_pentomino", 1000),
+    ("primer", 500),
+    ("spider", 500),
+];
+
+/// One pattern to benchmark.
+pub struct Case {
+    pub name: String,
+    pub path: PathBuf,
+    pub generations: u64,
+}
+
+/// The measurements from a single run.
+pub struct Sample {
+    /// Seconds taken for the whole run
+    pub seconds: f64,
+    /// The peak resident memory in kilobytes, where it could be measured
+    pub peak_memory: Option<u64>,
+    pub population: usize,
+}
+
+/// The summary of all the runs of one algorithm on one case.
+pub struct Report {
+    pub algorithm: String,
+    pub pattern: String,
+    pub generations: u64,
+    pub repeats: usize,
+    /// Mean, median and standard deviation of the seconds per generation
+    pub mean: f64,
+    pub median: f64,
+    pub stddev: f64,
+    pub peak_memory: Option<u64>,
+    pub population: usize,
+}
+
+impl Report {
+    /// Summarises the samples from repeated runs. If there are no samples, then the thread panics.
+    pub fn new(algorithm: &str, case: &Case, samples: &[Sample]) -> Report {
+        assert!(!samples.is_empty());
+        let generations = case.generations.max(1) as f64;
+        let mut per_generation: Vec<f64> = samples.iter().map(|s| s.seconds / generations).collect();
+        per_generation.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let n = per_generation.len();
+        let mean = per_generation.iter().sum::<f64>() / n as f64;
+        let median = if n % 2 == 1 {
+            per_generation[n / 2]
+        } else {
+            (per_generation[n / 2 - 1] + per_generation[n / 2]) / 2.0
+        };
+        // Sample standard deviation, which is 0 for a single run
+        let stddev = if n > 1 {
+            (per_generation.iter().map(|t| (t - mean) * (t - mean)).sum::<f64>() / (n - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+
+        Report {
+            algorithm: algorithm.to_string(),
+            pattern: case.name.clone(),
+            generations: case.generations,
+            repeats: n,
+            mean: mean,
+            median: median,
+            stddev: stddev,
+            peak_memory: samples.iter().filter_map(|s| s.peak_memory).max(),
+            population: samples[n - 1].population,
+        }
+    }
+}
+
+/// Loads a case into a fresh life object and times how long it takes to run.
+pub fn time_run<L: LifeAlgorithm>(mut life_obj: L, case: &Case, step: &BigUint) -> Result<Sample, Error> {
+    try!(read_seed_from_file(&mut life_obj, case.path.clone()));
+    reset_peak_memory();
+    let start_time = time::precise_time_ns();
+    advance_to(&mut life_obj, &BigUint::from(case.generations), step);
+    let seconds = (time::precise_time_ns() - start_time) as f64 / 1000000000.0;
+    Ok(Sample { seconds: seconds, peak_memory: peak_memory(), population: life_obj.population() })
+}
+
+// Resets the peak resident memory reported by peak_memory. Only works on Linux.
+fn reset_peak_memory() {
+    if let Ok(mut f) = OpenOptions::new().write(true).open("/proc/self/clear_refs") {
+        let _ = f.write_all(b"5");
+    }
+}
+
+// Reads the peak resident memory of the process in kilobytes. Only works on Linux.
+fn peak_memory() -> Option<u64> {
+    let mut status = String::new();
+    if File::open("/proc/self/status").and_then(|mut f| f.read_to_string(&mut status)).is_err() {
+        return None
+    }
+    status.lines()
+          .find(|line| line.starts_with("VmHWM:"))
+          .and_then(|line| line.split_whitespace().nth(1))
+          .and_then(|kb| kb.parse().ok())
+}
+
+/// Prints reports as a table.
+pub fn print_reports(reports: &[Report]) {
+    println!("{:<12}{:<16}{:>8}{:>8}{:>14}{:>14}{:>14}{:>12}{:>12}",
+             "algorithm", "pattern", "gens", "runs", "mean ms/gen", "median ms/gen", "stddev ms/gen", "peak KB", "population");
+    for r in reports {
+        let peak = r.peak_memory.map_or("-".to_string(), |kb| kb.to_string());
+        println!("{:<12}{:<16}{:>8}{:>8}{:>14.4}{:>14.4}{:>14.4}{:>12}{:>12}",
+                 r.algorithm, r.pattern, r.generations, r.repeats,
+                 r.mean * 1000.0, r.median * 1000.0, r.stddev * 1000.0, peak, r.population);
+    }
+}
+
+// Describes the run being reported: when it happened and which commit was built, if known
+fn run_details() -> (String, Option<String>) {
+    let timestamp = format!("{}", time::now_utc().rfc3339());
+    let commit = process::Command::new("git").args(&["rev-parse", "--short", "HEAD"]).output().ok()
+        .and_then(|out| if out.status.success() { String::from_utf8(out.stdout).ok() } else { None })
+        .map(|s| s.trim().to_string());
+    (timestamp, commit)
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"'  => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains(',') || s.contains('"') || s.contains('\n') {
+        format!("\"{}\"", s.replace("\"", "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Writes reports to a file as JSON, along with the time and commit they were made at.
+pub fn write_json(reports: &[Report], rule: &str, path: &Path) -> Result<(), Error> {
+    let (timestamp, commit) = run_details();
+    let mut out = String::from("{\n");
+    out.push_str(&format!("  \"timestamp\": {},\n", json_string(&timestamp)));
+    out.push_str(&format!("  \"commit\": {},\n", commit.map_or("null".to_string(), |c| json_string(&c))));
+    out.push_str(&format!("  \"rule\": {},\n", json_string(rule)));
+    out.push_str("  \"results\": [\n");
+    for (i, r) in reports.iter().enumerate() {
+        out.push_str(&format!("    {{\"algorithm\": {}, \"pattern\": {}, \"generations\": {}, \"repeats\": {}, \
+                               \"mean_seconds_per_generation\": {:e}, \"median_seconds_per_generation\": {:e}, \
+                               \"stddev_seconds_per_generation\": {:e}, \"peak_memory_kb\": {}, \"population\": {}}}{}\n",
+                              json_string(&r.algorithm), json_string(&r.pattern), r.generations, r.repeats,
+                              r.mean, r.median, r.stddev, r.peak_memory.map_or("null".to_string(), |kb| kb.to_string()),
+                              r.population, if i + 1 < reports.len() { "," } else { "" }));
+    }
+    out.push_str("  ]\n}\n");
+    File::create(path).and_then(|mut f| f.write_all(out.as_bytes())).map_err(|e| Error::Io(path.to_path_buf(), e))
+}
+
+/// Appends reports to a CSV file, writing the header first if the file is new.
+pub fn write_csv(reports: &[Report], rule: &str, path: &Path) -> Result<(), Error> {
+    let (timestamp, commit) = run_details();
+    let mut out = String::new();
+    if !path.exists() {
+        out.push_str("timestamp,commit,rule,algorithm,pattern,generations,repeats,mean_seconds_per_generation,\
+                      median_seconds_per_generation,stddev_seconds_per_generation,peak_memory_kb,population\n");
+    }
+    for r in reports {
+        out.push_str(&format!("{},{},{},{},{},{},{},{:e},{:e},{:e},{},{}\n",
+                              timestamp, commit.clone().unwrap_or(String::new()), csv_field(rule),
+                              csv_field(&r.algorithm), csv_field(&r.pattern), r.generations, r.repeats,
+                              r.mean, r.median, r.stddev, r.peak_memory.map_or(String::new(), |kb| kb.to_string()),
+                              r.population));
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+        .and_then(|mut f| f.write_all(out.as_bytes()))
+        .map_err(|e| Error::Io(path.to_path_buf(), e))
+}
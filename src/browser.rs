@@ -0,0 +1,120 @@
+/*
+
+This file keeps the state of the in-GUI file browser, which lists
+the pattern files in a directory so that one can be opened into the
+running engine.
+
+Directories are listed first, followed by files with one of the
+pattern extensions that patterns.rs understands. The browser itself
+doesn't draw anything, it only hands back the lines to show.
+
+*/
+
+use std::fs;
+use std::io;
+use std::path::{Path,PathBuf};
+
+// Extensions of the files worth listing. The format is still worked out from the contents.
+const PATTERN_EXTENSIONS: [&'static str; 5] = ["cells", "rle", "lif", "life", "mc"];
+
+// How many entries are shown at once
+const VISIBLE_ENTRIES: usize = 15;
+
+pub struct FileBrowser {
+    dir: PathBuf,
+    entries: Vec<PathBuf>,
+    selected: usize,
+}
+
+impl FileBrowser {
+    /// Creates a browser listing the given directory.
+    pub fn new(dir: &Path) -> io::Result<FileBrowser> {
+        let mut browser = FileBrowser { dir: dir.to_path_buf(), entries: vec![], selected: 0 };
+        try!(browser.refresh());
+        Ok(browser)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Reads the contents of the current directory again.
+    pub fn refresh(&mut self) -> io::Result<()> {
+        let mut dirs = vec![];
+        let mut files = vec![];
+        for entry in try!(fs::read_dir(&self.dir)) {
+            let path = try!(entry).path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().and_then(|e| e.to_str()).map_or(false, |e| PATTERN_EXTENSIONS.contains(&e)) {
+                files.push(path);
+            }
+        }
+        dirs.sort();
+        files.sort();
+        dirs.extend(files);
+        self.entries = dirs;
+        self.selected = 0;
+        Ok(())
+    }
+
+    /// Moves the selection up (negative) or down (positive), stopping at either end of the list.
+    pub fn move_selection(&mut self, delta: isize) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let last = self.entries.len() as isize - 1;
+        self.selected = (self.selected as isize + delta).max(0).min(last) as usize;
+    }
+
+    /// Moves to the parent of the current directory.
+    pub fn parent(&mut self) -> io::Result<()> {
+        let parent = self.dir.parent().map(|p| p.to_path_buf());
+        if let Some(parent) = parent {
+            let child = self.dir.clone();
+            self.dir = parent;
+            try!(self.refresh());
+            // Keep the directory we came from selected
+            if let Some(i) = self.entries.iter().position(|e| *e == child) {
+                self.selected = i;
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens the selected entry. Directories are entered, and files are returned to be loaded.
+    pub fn activate(&mut self) -> io::Result<Option<PathBuf>> {
+        let path = match self.entries.get(self.selected) {
+            Some(path) => path.clone(),
+            None => return Ok(None),
+        };
+        if path.is_dir() {
+            self.dir = path;
+            try!(self.refresh());
+            Ok(None)
+        } else {
+            Ok(Some(path))
+        }
+    }
+
+    /// Returns the lines to display: the directory, then a window of entries around the
+    /// selection, with the selected one marked.
+    pub fn lines(&self) -> Vec<String> {
+        let mut out = vec![format!("Open file in {} (arrows to choose, Enter to open, Backspace for parent, O to close)", self.dir.display())];
+        if self.entries.is_empty() {
+            out.push("  (no pattern files)".to_string());
+            return out;
+        }
+        let first = if self.selected < VISIBLE_ENTRIES / 2 { 0 } else { self.selected - VISIBLE_ENTRIES / 2 };
+        let first = first.min(self.entries.len().saturating_sub(VISIBLE_ENTRIES));
+        for (i, path) in self.entries.iter().enumerate().skip(first).take(VISIBLE_ENTRIES) {
+            let marker = if i == self.selected { ">" } else { " " };
+            let mut name = path.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+            if path.is_dir() {
+                name.push('/');
+            }
+            out.push(format!("{} {}", marker, name));
+        }
+        out
+    }
+}
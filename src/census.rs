@@ -0,0 +1,161 @@
+/*
+
+This file takes a census of a pattern: it splits the live cells into
+objects and counts how many of each there are.
+
+An object is a group of live cells connected through their Moore
+neighbourhoods, so objects that touch are counted as one. Objects
+are compared up to rotation, reflection and translation. Under
+Conway's rule the common still lifes, oscillators and spaceships are
+recognised in any phase and given their usual names. Anything else
+is described by its plaintext picture.
+
+*/
+
+use std::collections::{HashMap,HashSet};
+
+// Common objects under B3/S23, in one phase each, with their periods
+const KNOWN_OBJECTS: [(&'static str, &'static str, usize); 16] = [
+    ("block", "OO/OO", 1),
+    ("beehive", ".OO./O..O/.OO.", 1),
+    ("loaf", ".OO./O..O/.O.O/..O.", 1),
+    ("boat", "OO./O.O/.O.", 1),
+    ("ship", "OO./O.O/.OO", 1),
+    ("tub", ".O./O.O/.O.", 1),
+    ("pond", ".OO./O..O/O..O/.OO.", 1),
+    ("long boat", "OO../O.O./.O.O/..O.", 1),
+    ("barge", ".O../O.O./.O.O/..O.", 1),
+    ("snake", "OO.O/O.OO", 1),
+    ("blinker", "OOO", 2),
+    ("toad", ".OOO/OOO.", 2),
+    ("beacon", "OO../O.../...O/..OO", 2),
+    ("glider", ".O./..O/OOO", 4),
+    ("lightweight spaceship", ".O..O/O..../O...O/OOOO.", 4),
+    ("middleweight spaceship", "...O../.O...O/O...../O....O/OOOOO.", 4),
+];
+
+pub struct Census {
+    names: HashMap<Vec<(isize, isize)>, &'static str>,
+}
+
+impl Census {
+    /// Creates a census. If conway is true, then the objects in KNOWN_OBJECTS are named.
+    pub fn new(conway: bool) -> Census {
+        let mut names = HashMap::new();
+        if conway {
+            for &(name, picture, period) in KNOWN_OBJECTS.iter() {
+                let mut cells = from_picture(picture);
+                for _ in 0..period {
+                    names.insert(canonical(&cells), name);
+                    cells = conway_step(&cells);
+                }
+            }
+        }
+        Census { names: names }
+    }
+
+    /// Counts the objects made up by the given live cells. Returns each kind of object with its
+    /// count, most common first.
+    pub fn take<I: Iterator<Item=(isize, isize)>>(&self, cells: I) -> Vec<(String, usize)> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for object in split_objects(cells.collect()) {
+            let shape = canonical(&object);
+            let name = match self.names.get(&shape) {
+                Some(name) => name.to_string(),
+                None => format!("{} cells: {}", object.len(), to_picture(&shape)),
+            };
+            *counts.entry(name).or_insert(0) += 1;
+        }
+        let mut out: Vec<(String, usize)> = counts.into_iter().collect();
+        out.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        out
+    }
+
+    /// Returns the name of an object, if it's one of the known ones.
+    pub fn name(&self, cells: &[(isize, isize)]) -> Option<&'static str> {
+        self.names.get(&canonical(cells)).cloned()
+    }
+}
+
+/// Splits a set of live cells into groups connected through their Moore neighbourhoods.
+pub fn split_objects(mut cells: HashSet<(isize, isize)>) -> Vec<Vec<(isize, isize)>> {
+    let mut objects = vec![];
+    while let Some(&start) = cells.iter().next() {
+        cells.remove(&start);
+        let mut object = vec![start];
+        let mut i = 0;
+        while i < object.len() {
+            let (x, y) = object[i];
+            for dx in -1..2 {
+                for dy in -1..2 {
+                    if cells.remove(&(x+dx, y+dy)) {
+                        object.push((x+dx, y+dy));
+                    }
+                }
+            }
+            i += 1;
+        }
+        objects.push(object);
+    }
+    objects
+}
+
+/// Returns the cells of an object in a form that is the same for all of its rotations,
+/// reflections and translations: the smallest sorted list of cells over the eight symmetries,
+/// moved so that its top left corner is at (0,0).
+pub fn canonical(cells: &[(isize, isize)]) -> Vec<(isize, isize)> {
+    let transforms: [fn((isize, isize)) -> (isize, isize); 8] = [
+        |(x, y)| ( x,  y), |(x, y)| (-x,  y), |(x, y)| ( x, -y), |(x, y)| (-x, -y),
+        |(x, y)| ( y,  x), |(x, y)| (-y,  x), |(x, y)| ( y, -x), |(x, y)| (-y, -x),
+    ];
+    transforms.iter().map(|t| {
+        let moved: Vec<(isize, isize)> = cells.iter().map(|&c| t(c)).collect();
+        let x_min = moved.iter().map(|c| c.0).min().unwrap_or(0);
+        let y_min = moved.iter().map(|c| c.1).min().unwrap_or(0);
+        let mut out: Vec<(isize, isize)> = moved.into_iter().map(|(x, y)| (x - x_min, y - y_min)).collect();
+        out.sort();
+        out
+    }).min().unwrap()
+}
+
+/// Reads a plaintext picture with rows separated by '/'.
+fn from_picture(picture: &str) -> Vec<(isize, isize)> {
+    let mut cells = vec![];
+    for (y, row) in picture.split('/').enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            if c == 'O' {
+                cells.push((x as isize, y as isize));
+            }
+        }
+    }
+    cells
+}
+
+/// Draws cells whose top left corner is at (0,0) as a plaintext picture with rows separated by '/'.
+pub fn to_picture(cells: &[(isize, isize)]) -> String {
+    let width = cells.iter().map(|c| c.0).max().unwrap_or(0) + 1;
+    let height = cells.iter().map(|c| c.1).max().unwrap_or(0) + 1;
+    let rows: Vec<String> = (0..height).map(|y| {
+        (0..width).map(|x| if cells.contains(&(x, y)) { 'O' } else { '.' }).collect()
+    }).collect();
+    rows.join("/")
+}
+
+/// Advances a small object one generation under B3/S23.
+fn conway_step(cells: &[(isize, isize)]) -> Vec<(isize, isize)> {
+    let alive: HashSet<(isize, isize)> = cells.iter().cloned().collect();
+    let mut counts: HashMap<(isize, isize), u8> = HashMap::new();
+    for &(x, y) in cells {
+        for dx in -1..2 {
+            for dy in -1..2 {
+                if dx != 0 || dy != 0 {
+                    *counts.entry((x+dx, y+dy)).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+    counts.into_iter()
+          .filter(|&(c, n)| n == 3 || (n == 2 && alive.contains(&c)))
+          .map(|(c, _)| c)
+          .collect()
+}
@@ -0,0 +1,485 @@
+/*
+
+This file parses the command line. The first argument picks a
+subcommand:
+
+* gui: open a window and run the simulation (the default).
+* run: run a pattern without a window and print or save the result.
+* bench: time the algorithms on a corpus of patterns (see bench.rs).
+* convert: read a pattern file and write it out in another format.
+* census: run a pattern and count the objects left at the end.
+* replay: play back an event log recorded in the GUI without a
+  window (see recording.rs).
+* script: run a Rhai script on a grid without a window (see
+  scripting.rs).
+* collide: crash objects into each other in every way within a range
+  and report the interesting results (see collide.rs).
+* search: find every still life or oscillator that fits (see
+  search.rs).
+* predecessor: find a pattern that evolves into the given one, or
+  show that there is none (see predecessor.rs).
+
+Everything else is given with named flags. For compatibility with
+older versions, the pattern, algorithm and rule can also be given as
+positional arguments in that order, so `game_of_life r_pentomino
+hashlife` still works.
+
+*/
+
+use std::path::PathBuf;
+
+use num::{self,BigUint,One,Zero};
+
+use error::Error;
+use life_algorithms;
+use rules::Rule;
+use tracking::{self,ColorScheme,COLOR_SCHEMES};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Command {
+    Gui,
+    Run,
+    Bench,
+    Convert,
+    Census,
+    Replay,
+    Script,
+    Collide,
+    Search,
+    Predecessor,
+    Help,
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Command> {
+        match name {
+            "gui"     => Some(Command::Gui),
+            "run"     => Some(Command::Run),
+            "bench"   => Some(Command::Bench),
+            "convert" => Some(Command::Convert),
+            "census"  => Some(Command::Census),
+            "replay"  => Some(Command::Replay),
+            "script"  => Some(Command::Script),
+            "collide" => Some(Command::Collide),
+            "search"  => Some(Command::Search),
+            "predecessor" => Some(Command::Predecessor),
+            "help"    => Some(Command::Help),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub command: Command,
+    /// A path to a pattern file, or the name of one in assets/game_seeds
+    pub pattern: Option<String>,
+    pub algorithm: Option<String>,
+    /// A rule in B/S notation, or the name of (or path to) a rule table
+    pub rule: String,
+    /// Generations can go past a u64 with the hashlife algorithm, so they're kept as BigUints
+    pub generations: Option<BigUint>,
+    /// How many generations to advance at a time
+    pub step: BigUint,
+    /// The number of threads for the parallel algorithm. Its default is twice the number of cores
+    pub threads: Option<usize>,
+    pub width: u32,
+    pub height: u32,
+    pub output: Option<PathBuf>,
+    /// The format to write patterns in (life105, life106 or rle) or benchmark results in (json or csv)
+    pub format: Option<String>,
+    /// How many times bench repeats each run
+    pub repeat: u64,
+    /// A CSV file to log the population to after every step of run and census
+    pub population_log: Option<PathBuf>,
+    /// A session file (see session.rs) to carry on from instead of the pattern
+    pub resume: Option<PathBuf>,
+    /// The most generations between the checkpoints the GUI steps backwards from (see history.rs)
+    pub checkpoint_interval: BigUint,
+    /// How the GUI colors cells to begin with (see tracking.rs)
+    pub colors: ColorScheme,
+    /// How many of the latest generations the heat color scheme counts changes over
+    pub heat_window: u32,
+    /// A theme file (see theme.rs), or the name of one in assets/themes
+    pub theme: Option<String>,
+    /// The percentage of cells the GUI's random fill makes alive
+    pub density: u64,
+    /// The event log the GUI records to, or that replay plays back (see recording.rs)
+    pub event_log: Option<PathBuf>,
+    /// A script file (see scripting.rs), or the name of one in assets/scripts, for the script
+    /// command to run or the GUI to run with X
+    pub script: Option<String>,
+    /// The objects collide crashes together, as apgcodes, library patterns or pattern files, each
+    /// optionally followed by the direction to send it in (see collide.rs)
+    pub objects: Vec<String>,
+    /// How far from the first object collide places the others, in cells
+    pub range: u64,
+    /// The period search looks for, 1 for still lifes
+    pub period: u64,
+    /// The most live cells search allows
+    pub cells: Option<u64>,
+    /// The width and height of the box search looks in
+    pub search_box: Option<(usize, usize)>,
+}
+
+impl Options {
+    pub fn new() -> Options {
+        Options {
+            command: Command::Gui,
+            pattern: None,
+            algorithm: None,
+            rule: "B3/S23".to_string(),
+            generations: None,
+            step: BigUint::one(),
+            threads: None,
+            width: 600,
+            height: 400,
+            output: None,
+            format: None,
+            repeat: 5,
+            population_log: None,
+            resume: None,
+            checkpoint_interval: BigUint::from(100u64),
+            colors: ColorScheme::States,
+            heat_window: 16,
+            theme: None,
+            density: 50,
+            event_log: None,
+            script: None,
+            objects: vec![],
+            range: 8,
+            period: 1,
+            cells: None,
+            search_box: None,
+        }
+    }
+
+    pub fn pattern(&self) -> &str {
+        self.pattern.as_ref().map_or("r_pentomino", |p| p)
+    }
+
+    /// The algorithm given on the command line, or else the first registered one that can run the
+    /// rule. Rules that aren't in B/S notation are rule tables
+    pub fn algorithm(&self) -> &str {
+        match self.algorithm {
+            Some(ref algorithm) => algorithm,
+            None => {
+                let table = Rule::parse(&self.rule).is_err();
+                life_algorithms::ALGORITHMS.iter()
+                    .find(|a| if table { a.capabilities.rule_tables } else { a.capabilities.custom_rules })
+                    .map_or("sequential", |a| a.name)
+            },
+        }
+    }
+
+    pub fn generations(&self) -> BigUint {
+        self.generations.clone().unwrap_or(BigUint::from(100u64))
+    }
+
+    /// Returns the file the GUI saves sessions to and loads them from: the one resumed from, if any
+    pub fn session_path(&self) -> PathBuf {
+        self.resume.clone().unwrap_or(PathBuf::from("game_of_life.session"))
+    }
+
+    /// Returns the format to write output in. Benchmark results default to the extension of the
+    /// output file, and patterns to Life 1.06.
+    pub fn format(&self) -> &str {
+        if let Some(ref format) = self.format {
+            return format
+        }
+        if self.command == Command::Bench {
+            let extension = self.output.as_ref().and_then(|o| o.extension()).and_then(|e| e.to_str());
+            return if extension == Some("csv") { "csv" } else { "json" }
+        }
+        "life106"
+    }
+}
+
+/// Parses the command line arguments, not including the program name.
+pub fn parse(args: &[String]) -> Result<Options, Error> {
+    let mut opts = Options::new();
+    let mut args = args.iter().peekable();
+
+    if let Some(command) = args.peek().and_then(|a| Command::from_name(a)) {
+        opts.command = command;
+        args.next();
+    }
+
+    let mut positional: Vec<String> = vec![];
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            positional.push(arg.clone());
+            continue;
+        }
+        if arg == "-h" || arg == "--help" {
+            opts.command = Command::Help;
+            continue;
+        }
+
+        // Values can be given either as --flag value or --flag=value
+        let (flag, inline_value) = match arg.find('=') {
+            Some(i) => (&arg[..i], Some(arg[i+1..].to_string())),
+            None    => (&arg[..], None),
+        };
+        let value = match inline_value.or_else(|| args.next().cloned()) {
+            Some(value) => value,
+            None => return Err(Error::Argument(format!("{} needs a value", flag))),
+        };
+        match flag {
+            "-p" | "--pattern"     => opts.pattern = Some(value),
+            "-a" | "--algorithm"   => opts.algorithm = Some(value),
+            "-r" | "--rule"        => opts.rule = value,
+            "-g" | "--generations" => opts.generations = Some(try!(parse_big(flag, &value))),
+            "-s" | "--step"        => opts.step = try!(parse_big(flag, &value).and_then(|step| positive(flag, step))),
+            "-t" | "--threads"     => opts.threads = Some(try!(parse_positive(flag, &value)) as usize),
+            "--width"              => opts.width = try!(parse_positive(flag, &value)) as u32,
+            "--height"             => opts.height = try!(parse_positive(flag, &value)) as u32,
+            "-o" | "--output"      => opts.output = Some(PathBuf::from(value)),
+            "-f" | "--format"      => opts.format = Some(value),
+            "-n" | "--repeat"      => opts.repeat = try!(parse_positive(flag, &value)),
+            "--population-log"     => opts.population_log = Some(PathBuf::from(value)),
+            "--resume"             => opts.resume = Some(PathBuf::from(value)),
+            "--checkpoint-interval" => opts.checkpoint_interval = try!(parse_big(flag, &value).and_then(|n| positive(flag, n))),
+            "--colors"             => opts.colors = try!(parse_color_scheme(&value)),
+            "--heat-window"        => opts.heat_window = try!(parse_positive(flag, &value)) as u32,
+            "--theme"              => opts.theme = Some(value),
+            "--density"            => opts.density = try!(parse_number(flag, &value)),
+            "--record"             => opts.event_log = Some(PathBuf::from(value)),
+            "--script"             => opts.script = Some(value),
+            "--range"              => opts.range = try!(parse_number(flag, &value)),
+            "--period"             => opts.period = try!(parse_positive(flag, &value)),
+            "--cells"              => opts.cells = Some(try!(parse_positive(flag, &value))),
+            "--box"                => opts.search_box = Some(try!(parse_size(flag, &value))),
+            _ => return Err(Error::Argument(format!("Unknown option {}. Run with --help for a list of options.", flag))),
+        }
+    }
+
+    if opts.command == Command::Convert {
+        if positional.len() != 2 {
+            return Err(Error::Argument("convert needs an input and an output file".to_string()));
+        }
+        opts.pattern = Some(positional[0].clone());
+        opts.output = Some(PathBuf::from(positional[1].clone()));
+    } else if opts.command == Command::Replay {
+        if opts.event_log.is_some() {
+            return Err(Error::Argument("--record only works with gui".to_string()));
+        }
+        if positional.len() != 1 {
+            return Err(Error::Argument("replay needs an event log".to_string()));
+        }
+        opts.event_log = Some(PathBuf::from(positional[0].clone()));
+    } else if opts.command == Command::Script {
+        if positional.len() != 1 {
+            return Err(Error::Argument("script needs a script to run".to_string()));
+        }
+        opts.script = Some(positional[0].clone());
+    } else if opts.command == Command::Collide {
+        if positional.len() < 2 {
+            return Err(Error::Argument("collide needs at least two objects to crash together".to_string()));
+        }
+        opts.objects = positional;
+    } else if opts.command == Command::Search {
+        if let Some(arg) = positional.first() {
+            return Err(Error::Argument(format!("Unexpected argument {}", arg)));
+        }
+    } else {
+        if positional.len() > 3 {
+            return Err(Error::Argument(format!("Unexpected argument {}", positional[3])));
+        }
+        let mut positional = positional.into_iter();
+        if let Some(pattern) = positional.next() { opts.pattern = Some(pattern); }
+        if let Some(algorithm) = positional.next() { opts.algorithm = Some(algorithm); }
+        if let Some(rule) = positional.next() { opts.rule = rule; }
+    }
+
+    if opts.command != Command::Help {
+        try!(life_algorithms::find(opts.algorithm()));
+    }
+    if opts.resume.is_some() && (opts.command == Command::Bench || opts.command == Command::Convert ||
+                                 opts.command == Command::Replay || opts.command == Command::Script ||
+                                 opts.command == Command::Collide || opts.command == Command::Search ||
+                                 opts.command == Command::Predecessor) {
+        return Err(Error::Argument("--resume only works with gui, run and census".to_string()));
+    }
+    if opts.event_log.is_some() && opts.command != Command::Gui && opts.command != Command::Replay {
+        return Err(Error::Argument("--record only works with gui".to_string()));
+    }
+    if opts.script.is_some() && opts.command != Command::Gui && opts.command != Command::Script {
+        return Err(Error::Argument("--script only works with gui".to_string()));
+    }
+    if opts.heat_window > tracking::MAX_HEAT_GENERATIONS {
+        return Err(Error::Argument(format!("--heat-window can be at most {}", tracking::MAX_HEAT_GENERATIONS)));
+    }
+    if opts.density > 100 {
+        return Err(Error::Argument("--density is a percentage, so it can be at most 100".to_string()));
+    }
+    let formats: &[&str] = if opts.command == Command::Bench { &["json", "csv"] } else { &["life105", "life106", "rle"] };
+    if !formats.contains(&opts.format()) {
+        return Err(Error::Argument(format!("Unknown format {}, expected one of {}", opts.format(), formats.join(", "))));
+    }
+    Ok(opts)
+}
+
+fn parse_number(flag: &str, value: &str) -> Result<u64, Error> {
+    value.parse().map_err(|_| Error::Argument(format!("{} expects a number, not {}", flag, value)))
+}
+
+fn parse_positive(flag: &str, value: &str) -> Result<u64, Error> {
+    match try!(parse_number(flag, value)) {
+        0 => Err(Error::Argument(format!("{} must be at least 1", flag))),
+        n => Ok(n),
+    }
+}
+
+// Parses a number of generations, which may be too large for a u64. Powers can be written as
+// BASE^EXPONENT, such as 2^100.
+fn parse_big(flag: &str, value: &str) -> Result<BigUint, Error> {
+    let error = || Error::Argument(format!("{} expects a number or a power such as 2^100, not {}", flag, value));
+    match value.find('^') {
+        Some(i) => {
+            let base: BigUint = try!(value[..i].parse().map_err(|_| error()));
+            let exponent: usize = try!(value[i+1..].parse().map_err(|_| error()));
+            Ok(num::pow(base, exponent))
+        },
+        None => value.parse().map_err(|_| error()),
+    }
+}
+
+// Parses a size given as WIDTHxHEIGHT, such as 6x6
+fn parse_size(flag: &str, value: &str) -> Result<(usize, usize), Error> {
+    let error = || Error::Argument(format!("{} expects a size such as 6x6, not {}", flag, value));
+    let i = try!(value.find('x').ok_or_else(&error));
+    match (value[..i].parse(), value[i+1..].parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(error()),
+    }
+}
+
+fn parse_color_scheme(value: &str) -> Result<ColorScheme, Error> {
+    ColorScheme::from_name(value).ok_or_else(|| {
+        let names: Vec<&str> = COLOR_SCHEMES.iter().map(|s| s.name()).collect();
+        Error::Argument(format!("Unknown color scheme {}, expected one of {}", value, names.join(", ")))
+    })
+}
+
+fn positive(flag: &str, n: BigUint) -> Result<BigUint, Error> {
+    if n.is_zero() {
+        Err(Error::Argument(format!("{} must be at least 1", flag)))
+    } else {
+        Ok(n)
+    }
+}
+
+/// Returns the help text, listing the subcommands, options and algorithms.
+pub fn usage() -> String {
+    let mut out = String::from(
+"Rusty Game of Life
+
+Usage:
+    game_of_life [gui] [PATTERN [ALGORITHM [RULE]]] [OPTIONS]
+    game_of_life run [PATTERN] [OPTIONS]
+    game_of_life bench [OPTIONS]
+    game_of_life census [PATTERN] [OPTIONS]
+    game_of_life convert INPUT OUTPUT [--format FORMAT]
+    game_of_life replay LOG [--algorithm NAME] [--output FILE]
+    game_of_life script SCRIPT [--pattern PATTERN] [OPTIONS]
+    game_of_life collide OBJECT[:DIRECTION] OBJECT[:DIRECTION]... [--range N] [OPTIONS]
+    game_of_life search [--period P] [--cells N] [--box WxH] [OPTIONS]
+    game_of_life predecessor [PATTERN] [--generations N] [OPTIONS]
+    game_of_life [gui|run|census] --resume SESSION [OPTIONS]
+
+Commands:
+    gui        Open a window and run the simulation (the default)
+    run        Run the pattern without a window and print the final population
+    bench      Time the algorithms on a corpus of patterns, or on one pattern with --pattern
+    census     Run the pattern and count the objects that are left
+    convert    Read a pattern file and write it out as Life 1.05, Life 1.06 or RLE
+    replay     Play back an event log recorded with --record, checking the grid matches
+    script     Run a Rhai script on an empty grid, or on the pattern if one is given
+    collide    Crash objects, given as apgcodes, library patterns or files, into the first in
+               every distinct way and report the ones that leave something interesting
+    search     Find every still life with up to --cells cells, or every oscillator of a --period
+               that fits in a --box
+    predecessor
+               Find a pattern that evolves into the pattern in --generations, or show that it's a
+               Garden of Eden
+
+Options:
+    -p, --pattern PATTERN    A pattern file, or the name of one in assets/game_seeds [default: r_pentomino]
+    -a, --algorithm NAME     The algorithm to simulate with [default: the first listed below that can run the rule,
+                             all that can run it for bench, or the fastest for collide]
+    -r, --rule RULE          A rule in B/S notation, or a rule table in assets/rules [default: B3/S23]
+    -g, --generations N      Generations to run for run, bench and census, after objects meet for collide, or
+                             to go back for predecessor [default: 100, fixed for each pattern in the bench
+                             corpus, 500 for collide or 1 for predecessor].
+                             Can be a power such as 2^100
+    -s, --step N             Generations to advance at a time [default: 1]. Can be a power such as 2^40
+    -t, --threads N          Threads for the parallel algorithm [default: twice the number of cores]
+        --width W            Window width [default: 600]
+        --height H           Window height [default: 400]
+    -o, --output FILE        Write the final pattern of run, replay or script, the results of bench or
+                             collide, or the patterns search or predecessor finds, to a file
+    -f, --format FORMAT      life105, life106 or rle for patterns [default: life106], json or csv for
+                             bench results [default: from the extension of the output file]
+    -n, --repeat N           How many times bench repeats each run [default: 5]
+        --range N            How many cells from the first object collide places the others [default: 8]
+        --period P           The period search looks for, 1 for still lifes [default: 1]
+        --cells N            The most live cells search allows [default: 8 for still lifes]
+        --box WxH            The box search looks in [default: --cells wide and tall for still lifes,
+                             6x6 for oscillators]
+        --population-log FILE
+                             Write the population after every step of run or census to a CSV file
+        --resume FILE        Carry on from a saved session, with its algorithm and rule, instead of
+                             the pattern. The GUI saves to this file with S [default: game_of_life.session]
+        --checkpoint-interval N
+                             Generations between the checkpoints the GUI steps backwards from
+                             [default: 100]
+        --colors SCHEME      Color cells by states, age, heat or history in the GUI [default: states].
+                             Press C to switch
+        --heat-window N      Generations the heat colors count changes over, at most 64 [default: 16]
+        --theme THEME        A theme file, or the name of one in assets/themes such as dark, with the
+                             colors to draw the window in [default: red cells on white]
+        --density P          The percentage of cells the GUI's random fill (R) makes alive [default: 50]
+        --record FILE        Log everything done in the GUI to a file that replay plays back. With
+                             replay, --algorithm runs the whole log on one algorithm
+        --script SCRIPT      A Rhai script, or the name of one in assets/scripts, for X to run on the
+                             grid in the GUI
+    -h, --help               Show this help
+
+Algorithms:
+");
+    for algorithm in life_algorithms::ALGORITHMS.iter() {
+        out.push_str(&format!("    {:<12}{}\n", algorithm.name, algorithm.description));
+        let capabilities = algorithm.capability_names();
+        if !capabilities.is_empty() {
+            out.push_str(&format!("    {:<12}Supports {}\n", "", capabilities.join(", ")));
+        }
+    }
+    out.push_str("
+Exit codes:
+    0    Success
+    1    A file could not be loaded or written
+    2    The command line was not understood
+    3    Playing back an event log did not match the recording
+");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn the_default_algorithm_can_run_the_rule() {
+        assert_eq!(parse(&args("run")).unwrap().algorithm(), "sequential");
+        assert_eq!(parse(&args("run --rule B36/S23")).unwrap().algorithm(), "sequential");
+        assert_eq!(parse(&args("run --rule Wireworld")).unwrap().algorithm(), "hashlife");
+        assert_eq!(parse(&args("run --rule Wireworld --algorithm table")).unwrap().algorithm(), "table");
+        assert_eq!(parse(&args("r_pentomino parallel")).unwrap().algorithm(), "parallel");
+        assert!(parse(&args("run --algorithm quantum")).is_err());
+    }
+}
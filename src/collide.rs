@@ -0,0 +1,634 @@
+/*
+
+This file implements the collide command, which crashes objects into
+each other in every distinct way within a range and reports the
+interesting results, the way collisions are searched for when
+building glider syntheses.
+
+The first object is the target and stays at (0,0). Every other
+object is placed at each offset from it within the range, in each of
+its phases. Moving everything on by a common period gives the same
+collision again, a little further along, so each placement is slid
+along the paths of the objects to a canonical one and duplicates are
+dropped. Each collision is then set up with the objects pulled back
+along their paths until they're just too far apart to touch, and run
+for a number of generations after that.
+
+What's left is split into objects as the census does (see census.rs),
+and each one is run on its own to find whether it's a still life, an
+oscillator or a spaceship (see period.rs), and which way it's going.
+Pieces that don't repeat on their own, such as the two halves a
+pentadecathlon falls into in some phases, are run again together.
+A collision is:
+
+* a clean deletion if nothing is left,
+* a reflection if a spaceship leaves in a direction none came from,
+* an absorption if the still lifes that were there are all that's
+  left, as when an eater eats a glider,
+* new still lifes if only still lifes are left, but different ones,
+* no interaction if everything ends up where it would have anyway,
+* unsettled if something still hasn't settled down by the end,
+* and otherwise something else.
+
+The first four are reported with the starting pattern as RLE, so they
+can be loaded and looked at.
+
+*/
+
+use std::collections::{HashSet,BTreeMap};
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path,PathBuf};
+
+use census::{self,Census};
+use common::LifeAlgorithm;
+use error::Error;
+use library::{Pattern,Transform};
+use life_algorithms::{Algorithm,BoxedLife,Settings};
+use patterns::{read_apgcode,read_seed_from_file,stored_rle};
+use period::{find_period,Period};
+
+// The longest period looked for, in the objects given and in what's left of a collision
+const MAX_PERIOD: u64 = 256;
+
+// Objects this far apart, in cells between their bounds, can't affect each other in one generation
+const SEPARATION: isize = 3;
+
+// The directions objects can be sent in, with the signs of the way they move (y goes down)
+const DIRECTIONS: [(&'static str, (isize, isize)); 8] = [
+    ("n", (0, -1)), ("ne", (1, -1)), ("e", (1, 0)), ("se", (1, 1)),
+    ("s", (0, 1)), ("sw", (-1, 1)), ("w", (-1, 0)), ("nw", (-1, -1)),
+];
+
+/// An object to collide, turned to move the way it was asked to.
+pub struct Object {
+    /// The object as it was given, such as xq4_153:se
+    pub name: String,
+    /// The cells in each phase, moving along with the object, starting from its top left corner
+    phases: Vec<Vec<(isize, isize)>>,
+    period: Period,
+}
+
+impl Object {
+    /// Reads an object given as an apgcode, the name of a pattern in the library, or a pattern file
+    /// found by find, optionally followed by a colon and the direction to send it in, such as
+    /// xq4_153:se. Objects have to repeat, so they can be placed in each of their phases.
+    pub fn new<F>(spec: &str, library: &[Pattern], find: F, algorithm: &Algorithm, settings: &Settings) -> Result<Object, Error>
+        where F: Fn(&str) -> Result<PathBuf, Error>
+    {
+        let (code, direction) = match spec.rfind(':') {
+            Some(i) => (&spec[..i], Some(&spec[i+1..])),
+            None    => (spec, None),
+        };
+        let mut life_obj = try!(algorithm.create(settings));
+        try!(read_object(&mut life_obj, code, library, find));
+        let cells: Vec<(isize, isize)> = life_obj.live_cells().collect();
+        if cells.is_empty() {
+            return Err(Error::Argument(format!("{} has no live cells", code)))
+        }
+        let mut period = try!(find_period(&mut life_obj, MAX_PERIOD).ok_or_else(|| {
+            Error::Argument(format!("{} doesn't repeat within {} generations, so it can't be collided", code, MAX_PERIOD))
+        }));
+
+        // Of the eight ways to turn and flip the object, the first that sends it the right way
+        if let Some(direction) = direction {
+            let wanted = try!(DIRECTIONS.iter().find(|d| d.0 == direction).map(|d| d.1).ok_or_else(|| {
+                let names: Vec<&str> = DIRECTIONS.iter().map(|d| d.0).collect();
+                Error::Argument(format!("Unknown direction {}, expected one of {}", direction, names.join(", ")))
+            }));
+            if !period.is_spaceship() {
+                return Err(Error::Argument(format!("{} doesn't move, so it can't be sent {}", code, direction)))
+            }
+            let transform = try!(symmetries().into_iter().find(|t| signs(t.apply((period.dx, period.dy))) == wanted).ok_or_else(|| {
+                Error::Argument(format!("{} can't be turned to go {}", code, direction))
+            }));
+            life_obj.clear();
+            for &cell in &cells {
+                life_obj.set(transform.apply(cell), true);
+            }
+            life_obj.clean_up();
+            let (dx, dy) = transform.apply((period.dx, period.dy));
+            period = Period { period: period.period, dx: dx, dy: dy };
+        }
+
+        // Each phase is kept where the object has got to by then, relative to where it started
+        let cells: Vec<(isize, isize)> = life_obj.live_cells().collect();
+        let x_min = cells.iter().map(|c| c.0).min().unwrap();
+        let y_min = cells.iter().map(|c| c.1).min().unwrap();
+        let mut phases = vec![];
+        for _ in 0..period.period {
+            phases.push(life_obj.live_cells().map(|(x, y)| (x - x_min, y - y_min)).collect());
+            life_obj.advance_by(1);
+        }
+        Ok(Object { name: spec.to_string(), phases: phases, period: period })
+    }
+
+    // The cells of the object after it's run for some generations from the given phase
+    fn cells_at(&self, phase: u64, generations: u64, at: (isize, isize)) -> Vec<(isize, isize)> {
+        let n = phase + generations;
+        let periods = (n / self.period.period) as isize;
+        let (x, y) = (at.0 + self.period.dx * periods, at.1 + self.period.dy * periods);
+        self.phases[(n % self.period.period) as usize].iter().map(|&(cx, cy)| (cx + x, cy + y)).collect()
+    }
+
+    fn direction(&self) -> Option<(isize, isize)> {
+        if self.period.is_spaceship() { Some(signs((self.period.dx, self.period.dy))) } else { None }
+    }
+}
+
+// Reads an apgcode, a pattern from the library by name, or a pattern file
+fn read_object<L, F>(life_obj: &mut L, code: &str, library: &[Pattern], find: F) -> Result<(), Error>
+    where L: LifeAlgorithm + ?Sized, F: Fn(&str) -> Result<PathBuf, Error>
+{
+    if code.starts_with('x') && code.contains('_') {
+        return read_apgcode(life_obj, code)
+    }
+    let name = code.replace('_', " ").to_lowercase();
+    if let Some(pattern) = library.iter().find(|p| p.name.to_lowercase() == name) {
+        life_obj.clear();
+        for &cell in &pattern.cells {
+            life_obj.set(cell, true);
+        }
+        life_obj.clean_up();
+        return Ok(())
+    }
+    read_seed_from_file(life_obj, try!(find(code)))
+}
+
+// The eight ways to turn and flip a pattern
+fn symmetries() -> Vec<Transform> {
+    let mut out = vec![];
+    for &flipped in &[false, true] {
+        let mut transform = if flipped { Transform::new().flip() } else { Transform::new() };
+        for _ in 0..4 {
+            out.push(transform);
+            transform = transform.rotate(true);
+        }
+    }
+    out
+}
+
+fn signs(v: (isize, isize)) -> (isize, isize) {
+    (v.0.signum(), v.1.signum())
+}
+
+fn direction_name(direction: (isize, isize)) -> &'static str {
+    DIRECTIONS.iter().find(|d| d.1 == direction).map_or("nowhere", |d| d.0)
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// What a collision left behind.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Outcome {
+    CleanDeletion,
+    Reflection,
+    Absorption,
+    NewStillLifes,
+    Other,
+    Unsettled,
+    NoInteraction,
+}
+
+pub static OUTCOMES: [Outcome; 7] = [
+    Outcome::CleanDeletion, Outcome::Reflection, Outcome::Absorption, Outcome::NewStillLifes,
+    Outcome::Other, Outcome::Unsettled, Outcome::NoInteraction,
+];
+
+impl Outcome {
+    pub fn name(self) -> &'static str {
+        match self {
+            Outcome::CleanDeletion => "clean deletion",
+            Outcome::Reflection    => "reflection",
+            Outcome::Absorption    => "absorption",
+            Outcome::NewStillLifes => "new still lifes",
+            Outcome::Other         => "something else",
+            Outcome::Unsettled     => "unsettled",
+            Outcome::NoInteraction => "no interaction",
+        }
+    }
+
+    /// Returns whether collisions with this outcome are worth reporting one by one.
+    pub fn is_interesting(self) -> bool {
+        self < Outcome::Other
+    }
+}
+
+/// One collision: where the objects after the target started, and what was left.
+pub struct Collision {
+    /// The offset from the target and the phase of each object after the target
+    pub placements: Vec<((isize, isize), u64)>,
+    pub generations: u64,
+    pub outcome: Outcome,
+    /// What was left, such as "1 block, 1 glider going ne"
+    pub description: String,
+    /// The starting pattern, as RLE
+    pub start: String,
+}
+
+/// Runs every distinct collision of the objects within range cells of the first, for the given
+/// number of generations after they're set up to meet. Objects are only named under Conway's rule.
+pub fn collide(objects: &[Object], range: isize, generations: u64, algorithm: &Algorithm, settings: &Settings, conway: bool) -> Result<Vec<Collision>, Error> {
+    if relative_moves(objects).iter().all(|&m| m == (0, 0)) {
+        return Err(Error::Argument("None of the objects move towards the first, so they'd never collide".to_string()))
+    }
+    let census = Census::new(conway);
+    let mut life_obj = try!(algorithm.create(settings));
+    let mut out = vec![];
+    for placement in placements(objects, range) {
+        if let Some(collision) = try!(run_collision(objects, placement, range, generations, &mut life_obj, algorithm, settings, &census)) {
+            out.push(collision);
+        }
+    }
+    Ok(out)
+}
+
+// Sets up one placement of the objects to collide and runs it, or returns None if they can't be
+// pulled apart to start with
+fn run_collision(objects: &[Object], placement: Vec<((isize, isize), u64)>, range: isize, generations: u64, life_obj: &mut BoxedLife,
+                 algorithm: &Algorithm, settings: &Settings, census: &Census) -> Result<Option<Collision>, Error> {
+    // Objects that can't be pulled apart, such as two still lifes on top of each other, are skipped
+    let start = match pull_back(objects, &placement, range) {
+        Some(start) => start,
+        None => return Ok(None),
+    };
+    let total = start.1 + generations;
+
+    let mut starting_cells = vec![];
+    for (object, &(at, phase)) in objects.iter().zip(start.0.iter()) {
+        starting_cells.extend(object.cells_at(phase, 0, at));
+    }
+    life_obj.clear();
+    life_obj.set_generation(0);
+    for &cell in &starting_cells {
+        life_obj.set(cell, true);
+    }
+    life_obj.clean_up();
+    // Collisions start at generation 0, where the background is dead
+    let start_rle = stored_rle(&*life_obj);
+    life_obj.advance_by(total);
+
+    let mut left: Vec<(isize, isize)> = life_obj.live_cells().collect();
+    left.sort();
+    let mut expected = vec![];
+    for (object, &(at, phase)) in objects.iter().zip(start.0.iter()) {
+        expected.extend(object.cells_at(phase, total, at));
+    }
+    expected.sort();
+    expected.dedup();
+
+    let (outcome, description) = if left == expected {
+        (Outcome::NoInteraction, String::new())
+    } else {
+        try!(classify(objects, &left, &start.0, total, algorithm, settings, census))
+    };
+    Ok(Some(Collision {
+        placements: placement,
+        generations: total,
+        outcome: outcome,
+        description: description,
+        start: start_rle,
+    }))
+}
+
+// Every distinct way to place the objects after the first, within range cells of it, in each of
+// their phases. Placements that are the same collision a common period later are only given once
+fn placements(objects: &[Object], range: isize) -> Vec<Vec<((isize, isize), u64)>> {
+    let offsets: Vec<(isize, isize)> = (-range..range + 1).flat_map(|y| (-range..range + 1).map(move |x| (x, y))).collect();
+    let mut all: Vec<Vec<((isize, isize), u64)>> = vec![vec![]];
+    for object in &objects[1..] {
+        let mut next = vec![];
+        for placement in &all {
+            for &offset in &offsets {
+                for phase in 0..object.period.period {
+                    let mut placement = placement.clone();
+                    placement.push((offset, phase));
+                    next.push(placement);
+                }
+            }
+        }
+        all = next;
+    }
+
+    let moves = relative_moves(objects);
+    let mut seen = HashSet::new();
+    all.into_iter().filter(|placement| seen.insert(canonical(objects, placement, &moves))).collect()
+}
+
+// The period after which every object is back in the same phase
+fn common_period(objects: &[Object]) -> u64 {
+    objects.iter().fold(1, |l, o| l / gcd(l, o.period.period) * o.period.period)
+}
+
+// How far each object after the first moves relative to the first, over a common period
+fn relative_moves(objects: &[Object]) -> Vec<(isize, isize)> {
+    let common = common_period(objects) as isize;
+    let target = (objects[0].period.dx * common / objects[0].period.period as isize,
+                  objects[0].period.dy * common / objects[0].period.period as isize);
+    objects[1..].iter().map(|o| {
+        let periods = common / o.period.period as isize;
+        (o.period.dx * periods - target.0, o.period.dy * periods - target.1)
+    }).collect()
+}
+
+// The placement that stands for every one that's the same collision at another time. Running on by
+// a period of the target keeps it in phase 0, so the placements passed through in one common period
+// are each slid to the same stretch of their paths, and the least of them is taken
+fn canonical(objects: &[Object], placement: &[((isize, isize), u64)], moves: &[(isize, isize)]) -> Vec<((isize, isize), u64)> {
+    let step = objects[0].period.period;
+    (0..common_period(objects) / step).map(|n| {
+        let later = run_on(objects, placement, n * step);
+        let periods = canonical_periods(&later, moves);
+        slide(&later, moves, periods)
+    }).min().unwrap()
+}
+
+// Where the objects after the target are relative to it, and in which phase, some generations on
+fn run_on(objects: &[Object], placement: &[((isize, isize), u64)], generations: u64) -> Vec<((isize, isize), u64)> {
+    let target = &objects[0].period;
+    let target_periods = (generations / target.period) as isize;
+    objects[1..].iter().zip(placement.iter()).map(|(o, &((x, y), phase))| {
+        let n = phase + generations;
+        let periods = (n / o.period.period) as isize;
+        ((x + o.period.dx * periods - target.dx * target_periods, y + o.period.dy * periods - target.dy * target_periods),
+         n % o.period.period)
+    }).collect()
+}
+
+// The number of common periods to slide a placement back by so that the first object that moves
+// relative to the target ends up in the same stretch of its path as any other placement on it
+fn canonical_periods(placement: &[((isize, isize), u64)], moves: &[(isize, isize)]) -> isize {
+    for (&((x, y), _), &(dx, dy)) in placement.iter().zip(moves.iter()) {
+        if dx != 0 || dy != 0 {
+            let along = x * dx + y * dy;
+            let length = dx * dx + dy * dy;
+            return if along >= 0 { along / length } else { -((-along + length - 1) / length) }
+        }
+    }
+    0
+}
+
+fn slide(placement: &[((isize, isize), u64)], moves: &[(isize, isize)], periods: isize) -> Vec<((isize, isize), u64)> {
+    placement.iter().zip(moves.iter()).map(|(&((x, y), phase), &(dx, dy))| ((x - dx * periods, y - dy * periods), phase)).collect()
+}
+
+// Slides a placement back along the paths of the objects to just before any of them touch, and
+// returns where every object starts, the target included, with how many generations back that
+// is. Returns None if they touch however far back they go
+fn pull_back(objects: &[Object], placement: &[((isize, isize), u64)], range: isize) -> Option<(Vec<((isize, isize), u64)>, u64)> {
+    let moves = relative_moves(objects);
+    let common = common_period(objects);
+    let placement = slide(placement, &moves, canonical_periods(placement, &moves));
+    let size = objects.iter().flat_map(|o| o.phases.iter().flat_map(|p| p.iter().map(|&(x, y)| x.abs().max(y.abs())))).max().unwrap_or(0);
+
+    // Far enough back that everything that moves is clear of everything else
+    let furthest = 2 * (range + 2 * size + SEPARATION) + 1;
+    // Fast objects can pass right through another in a common period, so they have to stay clear
+    // for every generation of the period after, not just at its start
+    let apart = |periods: isize| {
+        let start = slide(&placement, &moves, periods);
+        (0..common).all(|generation| {
+            let mut boxes = vec![bounds(&objects[0].cells_at(0, generation, (0, 0)))];
+            for (object, &(at, phase)) in objects[1..].iter().zip(start.iter()) {
+                boxes.push(bounds(&object.cells_at(phase, generation, at)));
+            }
+            (0..boxes.len()).all(|i| (0..i).all(|j| separated(&boxes[i], &boxes[j])))
+        })
+    };
+    if !apart(furthest) {
+        return None
+    }
+    let mut periods = furthest;
+    while periods > 0 && apart(periods - 1) {
+        periods -= 1;
+    }
+    // The target starts at (0,0) in phase 0 as always, with the others moved back relative to it
+    let mut start = vec![((0, 0), 0)];
+    start.extend(slide(&placement, &moves, periods));
+    Some((start, periods as u64 * common))
+}
+
+fn bounds(cells: &[(isize, isize)]) -> (isize, isize, isize, isize) {
+    let x_min = cells.iter().map(|c| c.0).min().unwrap_or(0);
+    let x_max = cells.iter().map(|c| c.0).max().unwrap_or(0);
+    let y_min = cells.iter().map(|c| c.1).min().unwrap_or(0);
+    let y_max = cells.iter().map(|c| c.1).max().unwrap_or(0);
+    (x_min, x_max, y_min, y_max)
+}
+
+fn separated(a: &(isize, isize, isize, isize), b: &(isize, isize, isize, isize)) -> bool {
+    let gap_x = (b.0 - a.1).max(a.0 - b.1);
+    let gap_y = (b.2 - a.3).max(a.2 - b.3);
+    gap_x.max(gap_y) >= SEPARATION
+}
+
+// Works out the outcome of a collision from the cells left, and describes them
+fn classify(objects: &[Object], left: &[(isize, isize)], start: &[((isize, isize), u64)], generations: u64,
+            algorithm: &Algorithm, settings: &Settings, census: &Census) -> Result<(Outcome, String), Error> {
+    if left.is_empty() {
+        return Ok((Outcome::CleanDeletion, "nothing".to_string()))
+    }
+
+    let mut life_obj: BoxedLife = try!(algorithm.create(settings));
+    let mut period_of = |cells: &[(isize, isize)]| {
+        life_obj.clear();
+        life_obj.set_generation(0);
+        for &cell in cells {
+            life_obj.set(cell, true);
+        }
+        life_obj.clean_up();
+        find_period(&mut life_obj, MAX_PERIOD)
+    };
+    // Some oscillators, such as the pentadecathlon, fall apart in some of their phases into pieces
+    // that don't repeat on their own, so the pieces that don't are tried again as one object
+    let mut left_objects = vec![];
+    let mut pieces = vec![];
+    for object in census::split_objects(left.iter().cloned().collect()) {
+        match period_of(&object) {
+            Some(period) => left_objects.push((object, Some(period))),
+            None => pieces.push(object),
+        }
+    }
+    if !pieces.is_empty() {
+        let together: Vec<(isize, isize)> = pieces.iter().flat_map(|p| p.iter().cloned()).collect();
+        match period_of(&together) {
+            Some(period) => left_objects.push((together, Some(period))),
+            None => left_objects.extend(pieces.into_iter().map(|p| (p, None))),
+        }
+    }
+
+    let mut names: BTreeMap<String, usize> = BTreeMap::new();
+    let (mut unsettled, mut reflected, mut still) = (false, false, true);
+    for (object, period) in left_objects {
+        let name = census.name(&object).map_or_else(|| format!("{} cells: {}", object.len(), census::to_picture(&census::canonical(&object))), |n| n.to_string());
+        let name = match period {
+            Some(period) => {
+                still = still && period.is_still_life();
+                if period.is_spaceship() {
+                    let direction = signs((period.dx, period.dy));
+                    reflected = reflected || objects.iter().all(|o| o.direction() != Some(direction));
+                    format!("{} going {}", name, direction_name(direction))
+                } else {
+                    name
+                }
+            },
+            None => {
+                unsettled = true;
+                format!("{} (unsettled)", name)
+            },
+        };
+        *names.entry(name).or_insert(0) += 1;
+    }
+    let description: Vec<String> = names.iter().map(|(name, count)| format!("{} {}", count, name)).collect();
+
+    // The still lifes given, where they'd be at the end anyway
+    let mut still_lifes = vec![];
+    for (object, &(at, phase)) in objects.iter().zip(start.iter()) {
+        if object.period.is_still_life() {
+            still_lifes.extend(object.cells_at(phase, generations, at));
+        }
+    }
+    still_lifes.sort();
+
+    let outcome = if unsettled {
+        Outcome::Unsettled
+    } else if reflected {
+        Outcome::Reflection
+    } else if still && left == &still_lifes[..] {
+        Outcome::Absorption
+    } else if still {
+        Outcome::NewStillLifes
+    } else {
+        Outcome::Other
+    };
+    Ok((outcome, description.join(", ")))
+}
+
+/// Writes out how many collisions had each outcome, then the interesting ones with their starting
+/// patterns.
+pub fn report(objects: &[Object], collisions: &[Collision], range: isize, algorithm: &Algorithm) -> String {
+    let names: Vec<&str> = objects.iter().map(|o| &o.name[..]).collect();
+    let mut out = format!("Collided {} within {} cells on {}: {} distinct collisions\n",
+                          names.join(" with "), range, algorithm.name, collisions.len());
+    for &outcome in OUTCOMES.iter() {
+        let count = collisions.iter().filter(|c| c.outcome == outcome).count();
+        if count > 0 {
+            out.push_str(&format!("{:>8}  {}\n", count, outcome.name()));
+        }
+    }
+
+    let mut interesting: Vec<&Collision> = collisions.iter().filter(|c| c.outcome.is_interesting()).collect();
+    interesting.sort_by_key(|c| c.outcome);
+    for collision in interesting {
+        let placed: Vec<String> = objects[1..].iter().zip(collision.placements.iter())
+            .map(|(o, &((x, y), phase))| format!("{} at ({}, {}) in phase {}", o.name, x, y, phase))
+            .collect();
+        out.push_str(&format!("\n{}: {}, leaving {} after {} generations\n",
+                              collision.outcome.name(), placed.join(", "), collision.description, collision.generations));
+        out.push_str(&collision.start);
+    }
+    out
+}
+
+/// Writes a report to a file.
+pub fn write_report(report: &str, path: &Path) -> Result<(), Error> {
+    File::create(path).and_then(|mut f| f.write_all(report.as_bytes())).map_err(|e| Error::Io(path.to_path_buf(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use life_algorithms::{sequential,RuleKind};
+    use patterns::read_pattern;
+    use rules::Rule;
+
+    fn settings() -> Settings {
+        Settings::new(RuleKind::Life(Rule::conway()))
+    }
+
+    fn object(spec: &str, library: &[Pattern]) -> Object {
+        Object::new(spec, library, |name| Err(Error::Argument(format!("{} isn't in the library", name))), &sequential::ALGORITHM, &settings()).unwrap()
+    }
+
+    fn run(objects: &[Object], range: isize) -> Vec<Collision> {
+        collide(objects, range, 200, &sequential::ALGORITHM, &settings(), true).unwrap()
+    }
+
+    // A pattern as the library has it, read from its file in assets/library
+    fn library_pattern(name: &str, text: &str) -> Pattern {
+        let mut life_obj = sequential::Life::new();
+        read_pattern(&mut life_obj, text, Path::new(name)).unwrap();
+        Pattern { name: name.to_string(), category: String::new(), description: String::new(), cells: life_obj.live_cells().collect() }
+    }
+
+    // Runs one placement of the objects
+    fn collision(objects: &[Object], placement: Vec<((isize, isize), u64)>, range: isize) -> Collision {
+        let mut life_obj = sequential::ALGORITHM.create(&settings()).unwrap();
+        run_collision(objects, placement, range, 200, &mut life_obj, &sequential::ALGORITHM, &settings(), &Census::new(true)).unwrap().unwrap()
+    }
+
+    fn counts(collisions: &[Collision]) -> Vec<(Outcome, usize)> {
+        OUTCOMES.iter().map(|&o| (o, collisions.iter().filter(|c| c.outcome == o).count())).filter(|c| c.1 > 0).collect()
+    }
+
+    #[test]
+    fn each_collision_is_placed_once() {
+        let objects = vec![object("xs4_33", &[]), object("xq4_153:nw", &[])];
+        // A period earlier, a generation later and another phase further along are all the same collision
+        let first = canonical(&objects, &[((0, -3), 0)], &relative_moves(&objects));
+        for placement in &[[((1, -2), 0)], [((0, -3), 1)], [((-1, -4), 2)]] {
+            assert_eq!(canonical(&objects, placement, &relative_moves(&objects)), first);
+        }
+        // so only the lanes through the range are left, one for each diagonal
+        assert_eq!(placements(&objects, 3).len(), 13);
+    }
+
+    #[test]
+    fn objects_are_pulled_back_until_they_are_clear() {
+        let objects = vec![object("xs4_33", &[]), object("xq4_153:nw", &[])];
+        assert_eq!(pull_back(&objects, &[((0, 0), 0)], 3), Some((vec![((0, 0), 0), ((5, 5), 0)], 20)));
+        // Still lifes on top of each other never come apart
+        let objects = vec![object("xs4_33", &[]), object("xs4_33", &[])];
+        assert_eq!(pull_back(&objects, &[((1, 1), 0)], 3), None);
+    }
+
+    #[test]
+    fn glider_and_block_collisions() {
+        let objects = vec![object("xs4_33", &[]), object("xq4_153:nw", &[])];
+        let collisions = run(&objects, 3);
+        assert_eq!(collisions.len(), 13);
+        assert_eq!(counts(&collisions), [(Outcome::CleanDeletion, 6), (Outcome::NewStillLifes, 4), (Outcome::Other, 2), (Outcome::NoInteraction, 1)]);
+
+        // Head on, the glider and the block take each other out
+        let head_on = collision(&objects, vec![((0, 0), 0)], 3);
+        assert_eq!(head_on.outcome, Outcome::CleanDeletion);
+        assert_eq!(head_on.description, "nothing");
+        assert_eq!(head_on.start, "#CXRLE Pos=0,0\nx = 8, y = 8\n2o$2o4$5b2o$5bobo$5bo!\n");
+    }
+
+    #[test]
+    fn eater_1_absorbs_a_glider() {
+        let library = [library_pattern("Eater 1", include_str!("../assets/library/eaters/eater_1.rle"))];
+        let objects = vec![object("eater_1", &library), object("xq4_153:se", &[])];
+        let absorbed: Vec<Collision> = run(&objects, 3).into_iter().filter(|c| c.outcome == Outcome::Absorption).collect();
+        assert_eq!(absorbed.len(), 1);
+        assert_eq!(absorbed[0].placements, [((-3, -2), 0)]);
+        assert_eq!(absorbed[0].description, "1 7 cells: OO../O.../.OOO/...O");
+    }
+
+    #[test]
+    fn the_pentadecathlon_reflector_reflects() {
+        // The library has the reflector as one pattern, so it's split into the pentadecathlon and
+        // the glider, which is placed where the pattern has it
+        let reflector = library_pattern("reflector", include_str!("../assets/library/reflectors/pentadecathlon_reflector.rle"));
+        let (glider, pentadecathlon): (Vec<(isize, isize)>, Vec<(isize, isize)>) = reflector.cells.iter().partition(|c| c.1 < 3);
+        let library = [Pattern { name: "pentadecathlon".to_string(), cells: pentadecathlon, ..reflector.clone() },
+                       Pattern { name: "glider".to_string(), cells: glider, ..reflector.clone() }];
+        let objects = vec![object("pentadecathlon", &library), object("glider", &library)];
+        let reflected = collision(&objects, vec![((-13, -5), 0)], 13);
+        assert_eq!(reflected.outcome, Outcome::Reflection);
+        assert!(reflected.description.ends_with("1 glider going nw"));
+    }
+}
@@ -0,0 +1,209 @@
+/*
+
+This file implements the subcommands that run without a window:
+run, census, convert, replay and script, and the listing of what a
+search or the predecessor finder turns up (bench, collide and the
+searches themselves have their own files). Run, census and script
+take a life object that has already been set up with the algorithm
+and rule from the command line (see main.rs), and holds
+the pattern or the session being resumed. Replay makes its own from
+the event log, as it switches algorithms and rules along the way.
+
+*/
+
+use std::cmp::min;
+use std::fs::File;
+use std::io::{self,BufWriter};
+use std::io::prelude::*;
+use std::path::{Path,PathBuf};
+
+use num::BigUint;
+
+use census::{Census,to_picture};
+use cli::Options;
+use common::LifeAlgorithm;
+use error::Error;
+use library::Pattern;
+use life_algorithms::{self,BoxedLife};
+use patterns::{read_seed_from_file,write_life_105,write_life_106,write_rle};
+use rules::Rule;
+use recording::EventLog;
+use scripting::run_script;
+
+/// Advances a life object to the given generation, at most step generations at a time.
+pub fn advance_to<L: LifeAlgorithm + ?Sized>(life_obj: &mut L, generation: &BigUint, step: &BigUint) {
+    while life_obj.get_big_generation() < *generation {
+        let count = min(step.clone(), generation - life_obj.get_big_generation());
+        life_obj.advance_by_big(&count);
+    }
+}
+
+/// Advances a life object to the requested generation like advance_to, writing the generation,
+/// population and background to a CSV file after every step if a population log was asked for.
+/// While the background of a B0 rule is alive (1 in the background column), the population is the
+/// number of dead cells.
+fn advance_logged<L: LifeAlgorithm + ?Sized>(life_obj: &mut L, opts: &Options) -> Result<(), Error> {
+    let path = match opts.population_log {
+        Some(ref path) => path,
+        None => {
+            advance_to(life_obj, &opts.generations(), &opts.step);
+            return Ok(())
+        },
+    };
+    write_population_log(life_obj, opts, path).map_err(|e| Error::Io(path.clone(), e))
+}
+
+fn write_population_log<L: LifeAlgorithm + ?Sized>(life_obj: &mut L, opts: &Options, path: &Path) -> io::Result<()> {
+    let mut log = BufWriter::new(try!(File::create(path)));
+    try!(writeln!(log, "generation,population,background"));
+    try!(writeln!(log, "{},{},{}", life_obj.get_big_generation(), life_obj.population(), life_obj.background() as u8));
+    let generations = opts.generations();
+    while life_obj.get_big_generation() < generations {
+        let count = min(opts.step.clone(), &generations - life_obj.get_big_generation());
+        life_obj.advance_by_big(&count);
+        try!(writeln!(log, "{},{},{}", life_obj.get_big_generation(), life_obj.population(), life_obj.background() as u8));
+    }
+    log.flush()
+}
+
+/// Describes the live cells of a life object, taking a live background into account.
+fn population_text<L: LifeAlgorithm + ?Sized>(life_obj: &L) -> String {
+    let count = life_obj.population();
+    if life_obj.background() {
+        format!("all but {} cells alive", count)
+    } else {
+        format!("{} live cells", count)
+    }
+}
+
+/// Writes the live cells of a life object in the format asked for (life105, life106 or rle).
+pub fn write_pattern<L: LifeAlgorithm + ?Sized>(life_obj: &L, path: &Path, opts: &Options) -> Result<(), Error> {
+    let result = match opts.format() {
+        // Life 1.05 names the rule, which only Life-like rules have
+        "life105" => write_life_105(life_obj, Rule::parse(&opts.rule).ok(), path),
+        "rle"     => write_rle(life_obj, path),
+        _         => write_life_106(life_obj, path),
+    };
+    result.map_err(|e| Error::Io(path.to_path_buf(), e))
+}
+
+/// Runs the pattern up to the requested generation, then prints the population and saves the
+/// result if an output file was given.
+pub fn run<L: LifeAlgorithm>(mut life_obj: L, opts: &Options) -> Result<(), Error> {
+    try!(advance_logged(&mut life_obj, opts));
+    println!("Generation {}: {}", life_obj.get_big_generation(), population_text(&life_obj));
+    if let Some(ref output) = opts.output {
+        try!(write_pattern(&life_obj, output, opts));
+    }
+    Ok(())
+}
+
+/// Runs the pattern up to the requested generation, then counts the objects that are left.
+/// Objects are only named under Conway's rule.
+pub fn census<L: LifeAlgorithm>(mut life_obj: L, opts: &Options, conway: bool) -> Result<(), Error> {
+    try!(advance_logged(&mut life_obj, opts));
+    if life_obj.background() {
+        return Err(Error::Rule(format!("The background is alive at generation {}, so there are no objects to count", life_obj.get_big_generation())));
+    }
+    println!("Generation {}: {}", life_obj.get_big_generation(), population_text(&life_obj));
+    for (name, count) in Census::new(conway).take(life_obj.live_cells()) {
+        println!("{:>8}  {}", count, name);
+    }
+    Ok(())
+}
+
+/// Reads a pattern file and writes it out in the requested format.
+pub fn convert(opts: &Options, input: PathBuf, output: &Path) -> Result<(), Error> {
+    let mut life_obj = life_algorithms::sequential::Life::new();
+    try!(read_seed_from_file(&mut life_obj, input));
+    write_pattern(&life_obj, output, opts)
+}
+
+/// Plays back an event log recorded by the GUI on life objects made by create, from an algorithm
+/// name and a rule. Then prints the population and saves the result like run.
+pub fn replay<F>(opts: &Options, path: &Path, create: F) -> Result<(), Error>
+    where F: Fn(&str, &str) -> Result<BoxedLife, Error>
+{
+    let log = try!(EventLog::read(path));
+    let (life_obj, checks) = try!(log.play(opts.algorithm.as_ref().map(|a| &a[..]), create, path));
+    println!("Replayed {} events, and all {} checks matched the recording", log.len(), checks);
+    println!("Generation {}: {}", life_obj.get_big_generation(), population_text(&life_obj));
+    if let Some(ref output) = opts.output {
+        try!(write_pattern(&life_obj, output, opts));
+    }
+    Ok(())
+}
+
+/// Runs a script (see scripting.rs) on the life object, printing whatever it prints, then saves the
+/// grid it leaves if an output file was given. Patterns the script loads by name are found by find.
+pub fn script<F>(mut life_obj: BoxedLife, path: &Path, library: &[Pattern], find: F, opts: &Options) -> Result<(), Error>
+    where F: Fn(&str) -> Result<PathBuf, Error> + 'static
+{
+    try!(run_script(path, &mut life_obj, library, find, |line| println!("{}", line)));
+    if let Some(ref output) = opts.output {
+        try!(write_pattern(&life_obj, output, opts));
+    }
+    Ok(())
+}
+
+/// Prints the patterns a search found, by name or as pictures, and saves them side by side in rows
+/// if an output file was given.
+pub fn search(found: &[Vec<(isize, isize)>], description: &str, opts: &Options, conway: bool) -> Result<(), Error> {
+    println!("Found {} {}", found.len(), description);
+    let census = Census::new(conway);
+    for shape in found {
+        println!("{:>8}  {}", shape.len(), census.name(shape).map_or_else(|| to_picture(shape), |name| name.to_string()));
+    }
+    if let Some(ref output) = opts.output {
+        let mut life_obj = life_algorithms::sequential::Life::new();
+        let (mut x, mut y, mut row_height) = (0, 0, 0);
+        for (i, shape) in found.iter().enumerate() {
+            if i > 0 && i % 10 == 0 {
+                x = 0;
+                y += row_height + 4;
+                row_height = 0;
+            }
+            for &(cx, cy) in shape {
+                life_obj.set((x + cx, y + cy), true);
+            }
+            x += shape.iter().map(|c| c.0).max().unwrap_or(0) + 5;
+            row_height = row_height.max(shape.iter().map(|c| c.1).max().unwrap_or(0) + 1);
+        }
+        life_obj.clean_up();
+        try!(write_pattern(&life_obj, output, opts));
+    }
+    Ok(())
+}
+
+/// Reports what the predecessor search found, writing the predecessor to the output file if there
+/// is one.
+pub fn predecessor(found: Option<&[(isize, isize)]>, generations: u64, opts: &Options) -> Result<(), Error> {
+    let cells = match found {
+        Some(cells) => cells,
+        None => {
+            if generations == 1 {
+                println!("{} is a Garden of Eden: no pattern evolves into it", opts.pattern());
+            } else {
+                println!("No pattern evolves into {} in {} generations", opts.pattern(), generations);
+            }
+            return Ok(())
+        },
+    };
+    println!("Found a predecessor {} generation{} back with {} cells", generations, if generations == 1 { "" } else { "s" }, cells.len());
+    let mut life_obj = life_algorithms::sequential::Life::new();
+    for &cell in cells {
+        life_obj.set(cell, true);
+    }
+    life_obj.clean_up();
+    match opts.output {
+        Some(ref output) => write_pattern(&life_obj, output, opts),
+        None => {
+            // Pictures start from the top left corner
+            let x_min = cells.iter().map(|c| c.0).min().unwrap_or(0);
+            let y_min = cells.iter().map(|c| c.1).min().unwrap_or(0);
+            let shifted: Vec<(isize, isize)> = cells.iter().map(|&(x, y)| (x - x_min, y - y_min)).collect();
+            println!("{}", to_picture(&shifted));
+            Ok(())
+        },
+    }
+}
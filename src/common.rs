@@ -8,11 +8,30 @@ of our code having to be aware.
 It also defines the Bounds struct that keeps track of 
 the size of the map.
 
+The trait is object safe, so a life object of any algorithm can be
+kept behind a Box<LifeAlgorithm> and swapped for another at run time.
+Live cells are read through an iterator that borrows the life object
+rather than a copy of its cells.
+
+Checkpoints save a life object's grid and generation so that it can
+go back to them later, which is how runs are stepped backwards (see
+history.rs).
+
 */
 
+use std::any::Any;
+use std::cmp::{min,max};
 use std::iter::Iterator;
 
-pub trait LifeAlgorithm<I: Iterator<Item=(isize, isize)>> {
+use num::{BigUint,Integer,ToPrimitive,Zero};
+
+use error::Error;
+use tracking::CellTracker;
+
+/// An iterator over live cells that borrows the life object it came from
+pub type CellIter<'a> = Box<Iterator<Item=(isize, isize)> + 'a>;
+
+pub trait LifeAlgorithm {
 	/// Advances the simulation forward [count] step(s) 
     fn advance_by(&mut self,count:u64);
 	
@@ -27,6 +46,32 @@ pub trait LifeAlgorithm<I: Iterator<Item=(isize, isize)>> {
 	
     /// Get the current generation
     fn get_generation(&self) -> u64;
+
+    /// Sets the current generation without changing the grid. Under a B0 rule the cells already in
+    /// the grid are read against the new generation's background, so set it before the cells
+    fn set_generation(&mut self, generation: u64);
+	
+    /// Get the current generation, which may be too large for get_generation in engines that take
+    /// large steps
+    fn get_big_generation(&self) -> BigUint { BigUint::from(self.get_generation()) }
+
+    /// Sets the current generation like set_generation. Engines that only count to u64::MAX go to
+    /// the last generation they can reach with the same background
+    fn set_big_generation(&mut self, generation: &BigUint) {
+        let max = u64::max_value() - generation.is_even() as u64;
+        self.set_generation(generation.to_u64().unwrap_or(max));
+    }
+
+    /// Advances the simulation forward by a number of steps that may not fit in a u64
+    fn advance_by_big(&mut self, count: &BigUint) {
+        let chunk = BigUint::from(u64::max_value());
+        let mut count = count.clone();
+        while count > chunk {
+            self.advance_by(u64::max_value());
+            count = count - &chunk;
+        }
+        self.advance_by(count.to_u64().unwrap());
+    }
 	
     /// Gets the bounds of this life simulation
     fn get_bounds(&self) -> Bounds;
@@ -34,8 +79,127 @@ pub trait LifeAlgorithm<I: Iterator<Item=(isize, isize)>> {
     /// Gets the value of the cell (x,y) as a bool
     fn get_value(&self, cell: (isize, isize)) -> bool;
     
-    /// Gets an iterator over all the live cells. Used to draw on screen or output as ASCII in terminal
-	fn live_cells(&self) -> I;
+    /// Gets an iterator over all the live cells. Used to draw on screen or output as ASCII in terminal.
+    /// When background() is true these are the dead cells instead.
+	fn live_cells<'a>(&'a self) -> CellIter<'a>;
+
+    /// Gets the number of cells returned by live_cells
+    fn population(&self) -> usize { self.live_cells().count() }
+
+    /// Gets an iterator over the cells returned by live_cells that lie inside the region. Engines
+    /// that can skip the rest of the grid should override this.
+    fn live_cells_in<'a>(&'a self, region: &Bounds) -> CellIter<'a> {
+        let region = region.clone();
+        Box::new(self.live_cells().filter(move |&(x,y)| region.contains(x,y)))
+    }
+
+    /// Gets the number of cells returned by live_cells_in
+    fn population_in(&self, region: &Bounds) -> usize { self.live_cells_in(region).count() }
+
+    /// Sets the state of a cell (x,y) under a multi-state rule, where 0 is dead. Engines that only
+    /// simulate two states treat every other state as alive.
+    fn set_state(&mut self, cell: (isize, isize), state: u8) { self.set(cell, state != 0) }
+
+    /// Gets the state of a cell (x,y) under a multi-state rule, where 0 is dead
+    fn get_state(&self, cell: (isize, isize)) -> u8 { if self.get_value(cell) { 1 } else { 0 } }
+
+    /// Gets the number of states the rule has, dead included. Pattern files with states past it
+    /// are rejected (see patterns.rs)
+    fn num_states(&self) -> u8 { 2 }
+
+    /// Gets the value of every cell not returned by live_cells. This is only ever true on odd
+    /// generations of a B0 rule (see rules.rs)
+    fn background(&self) -> bool { false }
+
+    /// Gets the grid as a Macrocell pattern (see patterns.rs), for engines that keep it as a
+    /// quadtree and so can write huge patterns in a few nodes. Other engines return None
+    fn to_macrocell(&self) -> Option<String> { None }
+
+    /// Replaces the grid with a Macrocell pattern, node by node, for engines that keep it as a
+    /// quadtree. Other engines return None, and the pattern is read into them a cell at a time
+    fn from_macrocell(&mut self, _text: &str) -> Option<Result<(), Error>> { None }
+
+    /// Saves the grid and generation so that restore can go back to them. By default this copies
+    /// the live cells; engines that can share their grid with the checkpoint should override it
+    fn checkpoint(&self) -> Checkpoint {
+        // Under a B0 rule the cells are kept as they are stored, like the patterns in session.rs
+        let background = self.background();
+        let cells = self.live_cells().map(|cell| (cell, if background { 1 } else { self.get_state(cell) })).collect();
+        Checkpoint { generation: self.get_big_generation(), cells: cells, state: None }
+    }
+
+    /// Goes back to a checkpoint made by the same life object, or by another of the same algorithm
+    /// and rule
+    fn restore(&mut self, checkpoint: &Checkpoint) {
+        self.clear();
+        // Set the cells against a dead background, then move to the checkpoint's generation
+        self.set_big_generation(&BigUint::zero());
+        for &(cell, state) in &checkpoint.cells {
+            self.set_state(cell, state);
+        }
+        self.clean_up();
+        self.set_big_generation(&checkpoint.generation);
+    }
+
+    /// Starts or stops tracking the age and recent changes of every cell (see tracking.rs),
+    /// returning whether the engine is now doing as asked. By default engines can't track cells
+    fn set_tracking(&mut self, on: bool) -> bool { !on }
+
+    /// Gets what's been tracked about the cells, if tracking is on
+    fn tracker(&self) -> Option<&CellTracker> { None }
+}
+
+/// The grid and generation of a life object at one point in a run, made by
+/// LifeAlgorithm::checkpoint.
+pub struct Checkpoint {
+    pub generation: BigUint,
+    /// The cells returned by live_cells, with their states
+    pub cells: Vec<((isize, isize), u8)>,
+    /// Whatever an engine keeps instead of cells, such as the root of a hashlife tree. Only the
+    /// engine that made it knows what it is
+    pub state: Option<Box<Any>>,
+}
+
+/// Copies the generation and every cell of one life object into another, which may use a different
+/// algorithm. The destination is cleared first. The cells returned by live_cells are enough to copy
+/// the whole grid, since everything else is in the background state.
+pub fn transfer<A: LifeAlgorithm + ?Sized, B: LifeAlgorithm + ?Sized>(from: &A, to: &mut B) {
+    to.clear();
+    to.set_big_generation(&from.get_big_generation());
+    for cell in from.live_cells() {
+        to.set_state(cell, from.get_state(cell));
+    }
+    to.clean_up();
+}
+
+// Lets a boxed life object of any algorithm (see life_algorithms/mod.rs) be used wherever a life
+// object is expected
+impl<L: LifeAlgorithm + ?Sized> LifeAlgorithm for Box<L> {
+    fn advance_by(&mut self, count: u64) { (**self).advance_by(count) }
+    fn set(&mut self, cell: (isize, isize), value: bool) { (**self).set(cell, value) }
+    fn clean_up(&mut self) { (**self).clean_up() }
+    fn clear(&mut self) { (**self).clear() }
+    fn get_generation(&self) -> u64 { (**self).get_generation() }
+    fn set_generation(&mut self, generation: u64) { (**self).set_generation(generation) }
+    fn get_big_generation(&self) -> BigUint { (**self).get_big_generation() }
+    fn set_big_generation(&mut self, generation: &BigUint) { (**self).set_big_generation(generation) }
+    fn advance_by_big(&mut self, count: &BigUint) { (**self).advance_by_big(count) }
+    fn get_bounds(&self) -> Bounds { (**self).get_bounds() }
+    fn get_value(&self, cell: (isize, isize)) -> bool { (**self).get_value(cell) }
+    fn live_cells<'a>(&'a self) -> CellIter<'a> { (**self).live_cells() }
+    fn population(&self) -> usize { (**self).population() }
+    fn live_cells_in<'a>(&'a self, region: &Bounds) -> CellIter<'a> { (**self).live_cells_in(region) }
+    fn population_in(&self, region: &Bounds) -> usize { (**self).population_in(region) }
+    fn set_state(&mut self, cell: (isize, isize), state: u8) { (**self).set_state(cell, state) }
+    fn get_state(&self, cell: (isize, isize)) -> u8 { (**self).get_state(cell) }
+    fn num_states(&self) -> u8 { (**self).num_states() }
+    fn background(&self) -> bool { (**self).background() }
+    fn to_macrocell(&self) -> Option<String> { (**self).to_macrocell() }
+    fn from_macrocell(&mut self, text: &str) -> Option<Result<(), Error>> { (**self).from_macrocell(text) }
+    fn checkpoint(&self) -> Checkpoint { (**self).checkpoint() }
+    fn restore(&mut self, checkpoint: &Checkpoint) { (**self).restore(checkpoint) }
+    fn set_tracking(&mut self, on: bool) -> bool { (**self).set_tracking(on) }
+    fn tracker(&self) -> Option<&CellTracker> { (**self).tracker() }
 }
 
 #[derive(Clone)]
@@ -59,6 +223,19 @@ impl Bounds {
                  y_max:  s }
     }
 
+    /// Returns whether (x,y) lies inside the bounds, edges included
+    pub fn contains(&self, x: isize, y: isize) -> bool {
+        x >= self.x_min && x <= self.x_max && y >= self.y_min && y <= self.y_max
+    }
+
+    /// Grows the bounds to take in another set of bounds
+    pub fn merge(&mut self, other: &Bounds) {
+        self.x_min = min(self.x_min, other.x_min);
+        self.x_max = max(self.x_max, other.x_max);
+        self.y_min = min(self.y_min, other.y_min);
+        self.y_max = max(self.y_max, other.y_max);
+    }
+
     pub fn update_bounds(&mut self, x:isize, y:isize) {
         if x < self.x_min {
             self.x_min = x;
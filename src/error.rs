@@ -0,0 +1,84 @@
+/*
+
+This file defines the error type returned when loading patterns,
+rules and assets, so that a bad file can be reported instead of
+bringing the whole program down.
+
+*/
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be opened or read.
+    Io(PathBuf, io::Error),
+    /// The contents of a pattern file did not match any known format.
+    UnknownFormat(PathBuf),
+    /// A syntax error in a pattern or rule file, at a line and column counted from 1.
+    Syntax { path: PathBuf, line: usize, column: usize, message: String },
+    /// A rule that could not be parsed or cannot be simulated.
+    Rule(String),
+    /// The assets folder could not be found next to the executable.
+    MissingAssets,
+    /// A command line argument was not understood.
+    Argument(String),
+    /// The window could not be opened.
+    Window(String),
+    /// Playing back an event log didn't give the grid that was recorded.
+    Mismatch(String),
+    /// A script couldn't be read, or stopped with an error.
+    Script(PathBuf, String),
+}
+
+impl Error {
+    /// Returns a syntax error that is not yet attached to a file.
+    pub fn syntax(line: usize, column: usize, message: String) -> Error {
+        Error::Syntax { path: PathBuf::new(), line: line, column: column, message: message }
+    }
+
+    /// Attaches the file being read to a syntax error. Other errors are returned unchanged.
+    pub fn in_file(self, file: &PathBuf) -> Error {
+        match self {
+            Error::Syntax { line, column, message, .. } =>
+                Error::Syntax { path: file.clone(), line: line, column: column, message: message },
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref path, ref e) => write!(f, "Could not read {}: {}", path.display(), e),
+            Error::UnknownFormat(ref path) =>
+                write!(f, "{} is not a plaintext, RLE, Life 1.05, Life 1.06 or Macrocell file", path.display()),
+            Error::Syntax { ref path, line, column, ref message } =>
+                write!(f, "{}:{}:{}: {}", path.display(), line, column, message),
+            Error::Rule(ref message) => write!(f, "{}", message),
+            Error::MissingAssets => write!(f, "Could not find the assets folder"),
+            Error::Argument(ref message) => write!(f, "{}", message),
+            Error::Window(ref message) => write!(f, "Could not open a window: {}", message),
+            Error::Mismatch(ref message) => write!(f, "{}", message),
+            Error::Script(ref path, ref message) => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(_, ref e) => error::Error::description(e),
+            Error::UnknownFormat(_) => "unknown pattern format",
+            Error::Syntax { .. } => "syntax error",
+            Error::Rule(_) => "invalid rule",
+            Error::MissingAssets => "missing assets folder",
+            Error::Argument(_) => "invalid argument",
+            Error::Window(_) => "could not open a window",
+            Error::Mismatch(_) => "playback did not match the recording",
+            Error::Script(..) => "script failed",
+        }
+    }
+}
@@ -10,8 +10,94 @@ for interacting with the display.
 
 */
 
-use common::LifeAlgorithm;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use num::{BigUint,ToPrimitive,Zero};
+
+use browser::FileBrowser;
+use common::{LifeAlgorithm,Bounds};
+use error::Error;
+use library::{Library,Pattern,Transform};
 use piston_window::*;
+use session::View;
+use theme::Theme;
+use tools::{self,Tool,TOOLS};
+use tracking::ColorScheme;
+
+// How many of the latest populations the chart shows, its size in pixels, and the colors of its
+// line for populations of live cells and of dead cells on a live B0 background
+const CHART_LENGTH: usize = 200;
+const CHART_HEIGHT: f64 = 60.0;
+const CHART_COLORS: [[f32;4]; 2] = [[0.0, 0.0, 0.6, 1.0], [0.8, 0.0, 0.0, 1.0]];
+
+// Colors for the tracked color schemes: the youngest and oldest live cells, cells that changed
+// rarely and often, cells that haven't changed at all and cells that were once alive
+const AGE_COLORS: [[f32;4]; 2] = [[1.0, 0.85, 0.0, 1.0], [0.35, 0.0, 0.55, 1.0]];
+const AGE_OLDEST: f64 = 1000.0;
+const HEAT_COLORS: [[f32;4]; 2] = [[1.0, 0.9, 0.4, 1.0], [0.8, 0.0, 0.0, 1.0]];
+const STILL_COLOR: [f32;4] = [0.75, 0.75, 0.75, 1.0];
+const HISTORY_COLOR: [f32;4] = [0.75, 0.85, 1.0, 1.0];
+
+// Grid lines are drawn once cells are at least this many pixels wide, heavier every GRID_MAJOR cells
+const GRID_MIN_ZOOM: f64 = 6.0;
+const GRID_MAJOR: isize = 10;
+
+// The minimap's size in pixels, how many squares across it counts cells in, and the population
+// above which an engine with large steps (HashLife) counts its squares with population_in rather
+// than by going through every cell
+const MINIMAP_SIZE: f64 = 150.0;
+const MINIMAP_BINS: usize = 50;
+const MINIMAP_SCAN_LIMIT: usize = 100000;
+
+// The size in pixels of the preview of the pattern picked in the library, and the most pixels it
+// draws each cell in
+const PREVIEW_SIZE: f64 = 150.0;
+const PREVIEW_MAX_ZOOM: f64 = 10.0;
+
+// The height of the timeline, which runs along the bottom of the window right of the chart
+const TIMELINE_HEIGHT: f64 = 10.0;
+
+/// Something the GUI asks the main loop to do in response to a key or the mouse
+pub enum Action {
+	Nothing,
+	/// Load a pattern file picked in the file browser
+	Open(PathBuf),
+	/// Move the pattern to the next algorithm that can run the rule
+	SwitchAlgorithm,
+	/// Zoom and scroll so the whole pattern is on screen
+	ZoomToFit,
+	/// Save the pattern, rule, algorithm and view to the session file
+	SaveSession,
+	/// Replace everything with what was saved in the session file
+	LoadSession,
+	/// Go back a step, re-simulating from the checkpoint before it
+	StepBack,
+	/// Advance a step while paused
+	StepForward,
+	/// Go to a generation picked on the timeline
+	Seek(BigUint),
+	/// The grid was edited with the mouse
+	Edited,
+	/// Color cells another way, which may need the engine to track them
+	ColorScheme(ColorScheme),
+	/// Fill the selection with random cells
+	RandomFill(Bounds),
+	/// Replace the selection with a pattern that evolves into it, if there is one
+	FindPredecessor(Bounds),
+	/// Run the script given with --script on the grid
+	RunScript,
+	/// Something couldn't be done, for the reason given
+	Failed(String),
+}
+
+// A drag with one of the drawing tools: where it started, the latest cell it reached and whether
+// it makes cells alive (with the left button) or dead (with the right one)
+struct Stroke {
+	start:(isize, isize),
+	end:(isize, isize),
+	value:bool,
+}
 
 pub struct GUI {
 	paused:bool,
@@ -23,6 +109,32 @@ pub struct GUI {
 	mouse_pos:[f64;2],
 	mouse_last_pos:[f64;2],
 	mouse_middle_down:bool,
+	palette:Vec<[f32;4]>,
+	browse_dir:PathBuf,
+	browser:Option<FileBrowser>,
+	// The population of each step, and whether the background was alive so that it counted dead cells
+	populations:VecDeque<(usize, bool)>,
+	// The generations the timeline spans, where it was last drawn and whether it's being dragged
+	timeline:(BigUint, BigUint),
+	timeline_rect:[f64;4],
+	timeline_drag:bool,
+	color_scheme:ColorScheme,
+	heat_window:u32,
+	theme:Theme,
+	// Whether the minimap is shown, where it was last drawn, and the cell at its top left corner
+	// along with how many pixels it draws each cell in
+	minimap:bool,
+	minimap_rect:[f64;4],
+	minimap_view:(f64, f64, f64),
+	tool:Tool,
+	stroke:Option<Stroke>,
+	selection:Option<Bounds>,
+	// The pattern library, whether its panel is open, and the pattern picked from it to stamp along
+	// with how it's turned
+	library:Library,
+	library_open:bool,
+	stamp:Option<Pattern>,
+	transform:Transform,
 }
 
 impl GUI {
@@ -36,50 +148,357 @@ impl GUI {
             prev_offset_y: 0.0,
 			mouse_pos: [0.0,0.0],
             mouse_last_pos: [0.0,0.0],
-            mouse_middle_down: false
+            mouse_middle_down: false,
+            palette: vec![[1.0, 1.0, 1.0, 1.0], [1.0, 0.0, 0.0, 1.0]],
+            browse_dir: PathBuf::from("."),
+            browser: None,
+            populations: VecDeque::new(),
+            timeline: (BigUint::zero(), BigUint::zero()),
+            timeline_rect: [0.0, 0.0, 0.0, 0.0],
+            timeline_drag: false,
+            color_scheme: ColorScheme::States,
+            heat_window: 16,
+            theme: Theme::new(),
+            minimap: false,
+            minimap_rect: [0.0, 0.0, 0.0, 0.0],
+            minimap_view: (0.0, 0.0, 1.0),
+            tool: Tool::Draw,
+            stroke: None,
+            selection: None,
+            library: Library::new(),
+            library_open: false,
+            stamp: None,
+            transform: Transform::new(),
 		}
 	}
 
+	/// Sets the colors of the window. The colors of the cells come from the palette
+	pub fn set_theme(&mut self, theme: Theme) {
+		self.theme = theme;
+	}
+
+	pub fn theme(&self) -> &Theme { &self.theme }
+
+	/// Sets the color of each cell state. The first color is the background
+	pub fn set_palette(&mut self, palette: Vec<[f32;4]>) {
+		self.palette = palette;
+	}
+
+	/// Sets how cells are colored. Schemes that track cells fall back to coloring by state when the
+	/// life object isn't tracking them
+	pub fn set_color_scheme(&mut self, scheme: ColorScheme) {
+		self.color_scheme = scheme;
+	}
+
+	pub fn color_scheme(&self) -> ColorScheme { self.color_scheme }
+
+	/// Sets how many of the latest generations the heat colors count changes over
+	pub fn set_heat_window(&mut self, generations: u32) {
+		self.heat_window = generations;
+	}
+
+	/// Sets the directory the file browser starts in
+	pub fn set_browse_dir(&mut self, dir: PathBuf) {
+		self.browse_dir = dir;
+	}
+
 	pub fn is_paused(&self) -> bool { self.paused }
 
-	pub fn key_press(&mut self, key:Key){
-		if key == Key::Space {
+	/// Returns whether a drawing tool is being dragged, which holds the simulation back so that it
+	/// doesn't advance a grid that hasn't been cleaned up
+	pub fn is_drawing(&self) -> bool { self.stroke.is_some() }
+
+	/// Returns the list of tools for the overlay, with the one in use in brackets
+	pub fn tool_palette(&self) -> String {
+		let names: Vec<String> = TOOLS.iter().enumerate().map(|(i, &tool)| {
+			if tool == self.tool { format!("[{} {}]", i+1, tool.name()) } else { format!("{} {}", i+1, tool.name()) }
+		}).collect();
+		format!("Tools: {}", names.join("  "))
+	}
+
+	/// Returns the lines of the file browser if it is open
+	pub fn browser_lines(&self) -> Option<Vec<String>> {
+		self.browser.as_ref().map(|b| b.lines())
+	}
+
+	/// Sets the patterns the library panel searches
+	pub fn set_library(&mut self, library: Library) {
+		self.library = library;
+	}
+
+	pub fn library(&self) -> &Library {
+		&self.library
+	}
+
+	/// Returns the lines of the library panel if it is open
+	pub fn library_lines(&self) -> Option<Vec<String>> {
+		if self.library_open { Some(self.library.lines()) } else { None }
+	}
+
+	/// Returns what's being stamped and how it's turned, if a pattern was picked from the library
+	pub fn stamp_status(&self) -> Option<String> {
+		self.stamp.as_ref().map(|pattern| format!("Stamping: {}, {} (click to stamp, , and . to turn, / to flip, Esc to stop)",
+		                                          pattern.name, self.transform.name()))
+	}
+
+	// Returns whether the file browser or the library panel is taking the keyboard
+	fn panel_open(&self) -> bool {
+		self.browser.is_some() || self.library_open
+	}
+
+	/// Adds the population of a life object to the chart, dropping the oldest one once the chart is
+	/// full
+	pub fn record_population<L: LifeAlgorithm + ?Sized>(&mut self, life_obj: &L) {
+		self.populations.push_back((life_obj.population(), life_obj.background()));
+		if self.populations.len() > CHART_LENGTH {
+			self.populations.pop_front();
+		}
+	}
+
+	/// Empties the chart, for when a new pattern is loaded
+	pub fn clear_populations(&mut self) {
+		self.populations.clear();
+	}
+
+	/// Handles a key press, returning what the main loop should do about it
+	pub fn key_press(&mut self, key:Key) -> Result<Action, Error> {
+		// Spaces go into the library's search instead
+		if key == Key::Space && !self.library_open {
 			self.paused = !self.paused;
 		}
+		if key == Key::O && !self.library_open {
+			if self.browser.is_some() {
+				self.browser = None;
+			} else {
+				let browser = try!(FileBrowser::new(&self.browse_dir).map_err(|e| Error::Io(self.browse_dir.clone(), e)));
+				self.browser = Some(browser);
+			}
+			return Ok(Action::Nothing);
+		}
+		if key == Key::A && !self.panel_open() {
+			return Ok(Action::SwitchAlgorithm);
+		}
+		if key == Key::F && !self.panel_open() {
+			return Ok(Action::ZoomToFit);
+		}
+		if key == Key::S && !self.panel_open() {
+			return Ok(Action::SaveSession);
+		}
+		if key == Key::L && !self.panel_open() {
+			return Ok(Action::LoadSession);
+		}
+		if key == Key::P && !self.panel_open() {
+			self.library_open = true;
+			return Ok(Action::Nothing);
+		}
+		if key == Key::Escape {
+			if self.library_open {
+				self.library_open = false;
+			} else if self.browser.is_some() {
+				self.browser = None;
+			} else {
+				self.stamp = None;
+			}
+			return Ok(Action::Nothing);
+		}
+		if key == Key::Comma && self.browser.is_none() {
+			self.transform = self.transform.rotate(false);
+			return Ok(Action::Nothing);
+		}
+		if key == Key::Period && self.browser.is_none() {
+			self.transform = self.transform.rotate(true);
+			return Ok(Action::Nothing);
+		}
+		if key == Key::Slash && self.browser.is_none() {
+			self.transform = self.transform.flip();
+			return Ok(Action::Nothing);
+		}
+		if key == Key::M && !self.panel_open() {
+			self.minimap = !self.minimap;
+			return Ok(Action::Nothing);
+		}
+		let tool_keys = [Key::D1, Key::D2, Key::D3, Key::D4, Key::D5, Key::D6];
+		if let Some(i) = tool_keys.iter().position(|&k| k == key && !self.panel_open()) {
+			self.tool = TOOLS[i];
+			return Ok(Action::Nothing);
+		}
+		if key == Key::R && !self.panel_open() {
+			return Ok(match self.selection {
+				Some(ref selection) => Action::RandomFill(selection.clone()),
+				None => Action::Failed("Select a region to fill first, with the select tool (6)".to_string()),
+			});
+		}
+		if key == Key::G && !self.panel_open() {
+			return Ok(match self.selection {
+				Some(ref selection) => Action::FindPredecessor(selection.clone()),
+				None => Action::Failed("Select a region to find a predecessor of first, with the select tool (6)".to_string()),
+			});
+		}
+		if key == Key::C && !self.panel_open() {
+			return Ok(Action::ColorScheme(self.color_scheme.next()));
+		}
+		if key == Key::X && !self.panel_open() {
+			return Ok(Action::RunScript);
+		}
+		// Stepping pauses, so the step isn't lost among the ones that follow
+		if key == Key::Left && !self.panel_open() {
+			self.paused = true;
+			return Ok(Action::StepBack);
+		}
+		if key == Key::Right && !self.panel_open() {
+			self.paused = true;
+			return Ok(Action::StepForward);
+		}
+		if self.library_open {
+			match key {
+				Key::Up       => self.library.move_selection(-1),
+				Key::Down     => self.library.move_selection(1),
+				Key::PageUp   => self.library.move_selection(-10),
+				Key::PageDown => self.library.move_selection(10),
+				Key::Backspace => self.library.backspace(),
+				// The pattern picked follows the cursor until it's stamped
+				Key::Return   => if let Some(pattern) = self.library.selected().cloned() {
+					self.stamp = Some(pattern);
+					self.library_open = false;
+				},
+				_ => if let Some(c) = key_char(key) {
+					self.library.type_char(c);
+				},
+			}
+			return Ok(Action::Nothing);
+		}
+		let mut picked = None;
+		if let Some(ref mut browser) = self.browser {
+			let dir = browser.dir().to_path_buf();
+			match key {
+				Key::Up       => browser.move_selection(-1),
+				Key::Down     => browser.move_selection(1),
+				Key::PageUp   => browser.move_selection(-10),
+				Key::PageDown => browser.move_selection(10),
+				Key::Backspace => try!(browser.parent().map_err(|e| Error::Io(dir, e))),
+				Key::Return   => picked = try!(browser.activate().map_err(|e| Error::Io(dir, e))),
+				_ => (),
+			}
+		}
+		if let Some(path) = picked {
+			// Remember where we were so the browser opens there next time
+			if let Some(browser) = self.browser.take() {
+				self.browse_dir = browser.dir().to_path_buf();
+			}
+			return Ok(Action::Open(path));
+		}
+		Ok(Action::Nothing)
 	}
 	
-	pub fn mouse_press<I: Iterator<Item=(isize, isize)>, L: LifeAlgorithm<I>>(&mut self, mouse_btn: MouseButton, life_obj: &mut L, window: &mut PistonWindow) {
-		let w_size = window.size();
-		let window_width = w_size.width; 
-		let window_height = w_size.height;
+	/// Handles a mouse press, which stamps the pattern picked from the library or starts using the
+	/// tool picked, picks a generation on the timeline or moves the view to where the minimap was
+	/// clicked
+	pub fn mouse_press<L: LifeAlgorithm + ?Sized>(&mut self, mouse_btn: MouseButton, life_obj: &mut L, window: &mut PistonWindow) -> Action {
+		if mouse_btn == MouseButton::Left && self.minimap && inside(self.minimap_rect, self.mouse_pos) {
+			self.recenter_on_minimap();
+			return Action::Nothing;
+		}
+		if mouse_btn == MouseButton::Left && inside(self.timeline_rect, self.mouse_pos) {
+			// Scrubbing pauses, otherwise the run would carry on from wherever it was dropped
+			self.paused = true;
+			self.timeline_drag = true;
+			return Action::Seek(self.timeline_generation());
+		}
 
-		let x = (((self.mouse_pos[0] - self.offset_x) - (window_width as f64/2.0)) / self.zoom).floor() as isize;
-		let y = (((self.mouse_pos[1] - self.offset_y) - (window_height as f64/2.0)) / self.zoom).floor() as isize;
+		let cell = self.cursor_cell(window);
 
 		if mouse_btn == MouseButton::Middle {
 			self.mouse_middle_down=true;
+			return Action::Nothing;
 		}
-		if mouse_btn == MouseButton::Left {
-			// Set Alive
-			life_obj.set((x,y),true); 
+		// Stamping adds the pattern's cells to what's there, and right clicking stops it
+		if let Some(ref pattern) = self.stamp {
+			if mouse_btn == MouseButton::Left {
+				tools::paint(life_obj, &pattern.place(self.transform, cell), true);
+				return Action::Edited;
+			}
 		}
-		if mouse_btn == MouseButton::Right {
-			// Set Dead
-			life_obj.set((x,y),false);
+		if self.stamp.is_some() && mouse_btn == MouseButton::Right {
+			self.stamp = None;
+			return Action::Nothing;
+		}
+		// Left makes cells alive and right makes them dead, with whatever tool is picked
+		let value = match mouse_btn {
+			MouseButton::Left  => true,
+			MouseButton::Right => false,
+			_ => return Action::Nothing,
+		};
+		match self.tool {
+			Tool::Fill => match tools::flood_fill(life_obj, cell) {
+				Some(cells) => {
+					tools::paint(life_obj, &cells, value);
+					Action::Edited
+				},
+				None => Action::Failed("Only enclosed regions can be filled".to_string()),
+			},
+			// Right clicking with the select tool drops the selection
+			Tool::Select if !value => {
+				self.selection = None;
+				Action::Nothing
+			},
+			tool => {
+				if tool == Tool::Draw {
+					life_obj.set(cell, value);
+				}
+				self.stroke = Some(Stroke { start: cell, end: cell, value: value });
+				Action::Nothing
+			},
 		}
-
-    life_obj.clean_up();
 	}
 
-	pub fn mouse_release(&mut self, mouse_btn: MouseButton){
+	/// Handles a mouse button being let go, which finishes drawing the shape dragged out
+	pub fn mouse_release<L: LifeAlgorithm + ?Sized>(&mut self, mouse_btn: MouseButton, life_obj: &mut L) -> Action {
 		if mouse_btn == MouseButton::Middle {
 			//Stop moving 
 			self.mouse_middle_down = false;
 		}
+		if mouse_btn == MouseButton::Left {
+			self.timeline_drag = false;
+		}
+		// Only the button the drag started with finishes it
+		let released = match (&self.stroke, mouse_btn) {
+			(&Some(ref stroke), MouseButton::Left)  => stroke.value,
+			(&Some(ref stroke), MouseButton::Right) => !stroke.value,
+			_ => false,
+		};
+		if !released {
+			return Action::Nothing;
+		}
+		let stroke = self.stroke.take().unwrap();
+		match self.tool {
+			// The cells are already set, so they just need cleaning up
+			Tool::Draw      => life_obj.clean_up(),
+			Tool::Line      => tools::paint(life_obj, &tools::line(stroke.start, stroke.end), stroke.value),
+			Tool::Box       => tools::paint(life_obj, &tools::rectangle(stroke.start, stroke.end, false), stroke.value),
+			Tool::FilledBox => tools::paint(life_obj, &tools::rectangle(stroke.start, stroke.end, true), stroke.value),
+			Tool::Select    => {
+				self.selection = Some(tools::between(stroke.start, stroke.end));
+				return Action::Nothing;
+			},
+			Tool::Fill      => return Action::Nothing,
+		}
+		Action::Edited
 	}
 
-	pub fn mouse_move(&mut self,mot:[f64;2]){
+	/// Handles the mouse moving, which draws with the draw tool, drags out shapes with the others
+	/// and picks a generation while the timeline is being dragged
+	pub fn mouse_move<L: LifeAlgorithm + ?Sized>(&mut self, mot:[f64;2], life_obj: &mut L, window: &PistonWindow) -> Action {
 		self.mouse_pos =  mot;
+		let cell = self.cursor_cell(window);
+		if let Some(ref mut stroke) = self.stroke {
+			// Fill in the gap since the last cell, in case the mouse moved more than a cell
+			if self.tool == Tool::Draw {
+				for &c in &tools::line(stroke.end, cell) {
+					life_obj.set(c, stroke.value);
+				}
+			}
+			stroke.end = cell;
+		}
 		if self.mouse_middle_down == false {
 			self.mouse_last_pos = mot;
 			self.prev_offset_x = self.offset_x;
@@ -89,6 +508,27 @@ impl GUI {
 			self.offset_x = mot[0] - self.mouse_last_pos[0] + self.prev_offset_x;
 			self.offset_y = mot[1] - self.mouse_last_pos[1] + self.prev_offset_y;
 		}
+		if self.timeline_drag {
+			return Action::Seek(self.timeline_generation());
+		}
+		Action::Nothing
+	}
+
+	/// Sets the first and last generations the timeline spans
+	pub fn set_timeline(&mut self, first: BigUint, last: BigUint) {
+		self.timeline = (first, last);
+	}
+
+	// Returns the generation under the mouse on the timeline, worked out exactly so that any
+	// generation can be picked however far the run has gone
+	fn timeline_generation(&self) -> BigUint {
+		let (ref first, ref last) = self.timeline;
+		let (left, width) = (self.timeline_rect[0], self.timeline_rect[2]);
+		if width < 1.0 || last <= first {
+			return first.clone();
+		}
+		let pixel = (self.mouse_pos[0] - left).max(0.0).min(width) as u64;
+		first + (last - first) * BigUint::from(pixel) / BigUint::from(width as u64)
 	}
 
 	pub fn mouse_scroll(&mut self,scroll:[f64;2]){
@@ -101,7 +541,60 @@ impl GUI {
 		}
 	}
 
-	pub fn display_ascii<I: Iterator<Item=(isize, isize)>, L: LifeAlgorithm<I>>(&self, life_obj: &Box<L>) {
+	/// Returns the cell under the mouse
+	pub fn cursor_cell(&self, window: &PistonWindow) -> (isize, isize) {
+		let w_size = window.size();
+		self.cell_at(self.mouse_pos, w_size.width, w_size.height)
+	}
+
+	// Returns the cell at a point in the window
+	fn cell_at(&self, pos: [f64;2], window_width: u32, window_height: u32) -> (isize, isize) {
+		let x = (((pos[0] - self.offset_x) - (window_width as f64/2.0)) / self.zoom).floor() as isize;
+		let y = (((pos[1] - self.offset_y) - (window_height as f64/2.0)) / self.zoom).floor() as isize;
+		(x,y)
+	}
+
+	/// Returns where the window is looking, to save in a session
+	pub fn view(&self) -> View {
+		View { zoom: self.zoom, offset_x: self.offset_x, offset_y: self.offset_y }
+	}
+
+	/// Looks where a saved session was looking
+	pub fn set_view(&mut self, view: View) {
+		self.zoom = view.zoom;
+		self.offset_x = view.offset_x;
+		self.offset_y = view.offset_y;
+		self.prev_offset_x = view.offset_x;
+		self.prev_offset_y = view.offset_y;
+	}
+
+	/// Zooms and scrolls so that the bounds fill most of the window, centered
+	pub fn zoom_to_fit(&mut self, bounds: &Bounds, window: &PistonWindow) {
+		let w_size = window.size();
+		let width = (bounds.x_max - bounds.x_min + 1) as f64;
+		let height = (bounds.y_max - bounds.y_min + 1) as f64;
+		// Leave a margin so the edges of the pattern aren't against the edges of the window
+		self.zoom = 0.9 * (w_size.width as f64 / width).min(w_size.height as f64 / height);
+		self.offset_x = -self.zoom * (bounds.x_min as f64 + width/2.0);
+		self.offset_y = -self.zoom * (bounds.y_min as f64 + height/2.0);
+		self.prev_offset_x = self.offset_x;
+		self.prev_offset_y = self.offset_y;
+	}
+
+	// Returns the cells that are on screen, or None if the zoom is too far out to tell
+	fn visible_region(&self, window_width: u32, window_height: u32) -> Option<Bounds> {
+		if self.zoom <= 0.0 {
+			return None
+		}
+		let left = (-self.offset_x - window_width as f64/2.0) / self.zoom;
+		let top = (-self.offset_y - window_height as f64/2.0) / self.zoom;
+		Some(Bounds { x_min: left.floor() as isize,
+		              x_max: (left + window_width as f64/self.zoom).ceil() as isize,
+		              y_min: top.floor() as isize,
+		              y_max: (top + window_height as f64/self.zoom).ceil() as isize })
+	}
+
+	pub fn display_ascii<L: LifeAlgorithm + ?Sized>(&self, life_obj: &Box<L>) {
 		// Given any object that implements LifeAlgorithm, will display the grid in the terminal
 		let bounds = life_obj.get_bounds();
 	    let cells = life_obj.live_cells();
@@ -117,14 +610,19 @@ impl GUI {
         println!("{}\n", lines.join("\n"));
 	}
 	
-	pub fn draw<I: Iterator<Item=(isize, isize)>, L: LifeAlgorithm<I>>(&self, life_obj: &L, window: &mut PistonWindow, e: &Event){
+	pub fn draw<L: LifeAlgorithm + ?Sized>(&self, life_obj: &L, window: &mut PistonWindow, e: &Event){
 		// Given any object that implements LifeAlgorithm, will draw the grid to the screen
 		let w_size = window.size();
 		let window_width = w_size.width; 
 		let window_height = w_size.height;
 
+		// On odd generations of a B0 rule the background is alive and live_cells are the dead ones
+		let inverted = life_obj.background();
+		let background_color = if inverted { self.palette[1] } else { self.palette[0] };
+		let tracker = if self.color_scheme.tracks_cells() { life_obj.tracker() } else { None };
+
 		window.draw_2d(e, |c, g| {
-			clear([1.0, 1.0, 1.0, 1.0], g);
+			clear(background_color, g);
 
 			let half_width:f64 = (window_width as f64)/2.0;
 	        let half_height:f64 = (window_height as f64)/2.0;
@@ -133,15 +631,101 @@ impl GUI {
 	                                   .zoom(self.zoom)
 	                                   .trans(-half_width,-half_height);
 
-	        // Get the output to draw from the life object 
-	        let cells = life_obj.live_cells();
+	        // Get the output to draw from the life object, skipping cells that are off screen
+	        let region = self.visible_region(window_width, window_height);
+	        let cells = match region {
+	        	Some(ref region) => life_obj.live_cells_in(region),
+	        	None             => life_obj.live_cells(),
+	        };
+
+	        // Dead cells that were tracked go under the live ones
+	        if let Some(tracker) = tracker {
+	        	for ((x,y), record) in tracker.cells() {
+	        		if record.state != 0 || !region.as_ref().map_or(true, |r| r.contains(x,y)) {
+	        			continue;
+	        		}
+	        		let heat = record.heat(self.heat_window);
+	        		let cell_color = match self.color_scheme {
+	        			ColorScheme::Heat if heat > 0 => self.heat_color(heat),
+	        			ColorScheme::History          => HISTORY_COLOR,
+	        			_                             => continue,
+	        		};
+	        		rectangle(cell_color,
+	        				  [x as f64 + half_width, y as f64 + half_height, 1.0 ,1.0],
+	        				  transform, g);
+	        	}
+	        }
 
 	        // Iterate over all live cells and draw them 
 	        for (x,y) in cells {
-	        	rectangle([1.0, 0.0, 0.0, 1.0],
+	        	let record = tracker.and_then(|t| t.get((x,y)));
+	        	let cell_color = match (self.color_scheme, record) {
+	        		_ if inverted => self.palette[0],
+	        		(ColorScheme::Age, _) if tracker.is_some() => age_color(record.map_or(1, |r| r.age)),
+	        		(ColorScheme::Heat, Some(record)) => self.heat_color(record.heat(self.heat_window)),
+	        		_ if self.palette.len() > 2 => self.palette[life_obj.get_state((x,y)) as usize],
+	        		_ => self.palette[1],
+	        	};
+	        	rectangle(cell_color,
 	        			  [x as f64 + half_width, y as f64 + half_height, 1.0 ,1.0],
 	        			  transform, g);
 	        }
+
+	        // Grid lines, once the cells are big enough to place precisely
+	        if let Some(ref region) = region {
+	        	if self.zoom >= GRID_MIN_ZOOM {
+	        		for x in region.x_min..region.x_max+1 {
+	        			let radius = if x % GRID_MAJOR == 0 { 1.0 } else { 0.5 };
+	        			let screen_x = self.offset_x + half_width + self.zoom * x as f64;
+	        			line(self.theme.grid, radius, [screen_x, 0.0, screen_x, window_height as f64], c.transform, g);
+	        		}
+	        		for y in region.y_min..region.y_max+1 {
+	        			let radius = if y % GRID_MAJOR == 0 { 1.0 } else { 0.5 };
+	        			let screen_y = self.offset_y + half_height + self.zoom * y as f64;
+	        			line(self.theme.grid, radius, [0.0, screen_y, window_width as f64, screen_y], c.transform, g);
+	        		}
+	        	}
+	        }
+
+	        // Highlight the cell under the cursor, and the shape being dragged out
+	        let (x,y) = self.cell_at(self.mouse_pos, window_width, window_height);
+	        rectangle(self.theme.selection,
+	        		  [x as f64 + half_width, y as f64 + half_height, 1.0 ,1.0],
+	        		  transform, g);
+	        if let Some(ref stroke) = self.stroke {
+	        	let preview = match self.tool {
+	        		Tool::Line      => tools::line(stroke.start, stroke.end),
+	        		Tool::Box       => tools::rectangle(stroke.start, stroke.end, false),
+	        		_               => vec![],
+	        	};
+	        	for (x,y) in preview {
+	        		rectangle(self.theme.selection,
+	        				  [x as f64 + half_width, y as f64 + half_height, 1.0 ,1.0],
+	        				  transform, g);
+	        	}
+	        }
+
+	        if let Some(ref pattern) = self.stamp {
+	        	for (x,y) in pattern.place(self.transform, (x,y)) {
+	        		rectangle(self.theme.selection,
+	        				  [x as f64 + half_width, y as f64 + half_height, 1.0 ,1.0],
+	        				  transform, g);
+	        	}
+	        }
+
+	        // The selection, or the filled box being dragged out, is outlined
+	        let mut outline_color = self.theme.selection;
+	        outline_color[3] = 1.0;
+	        let outline = match self.stroke {
+	        	Some(ref stroke) if self.tool == Tool::FilledBox || self.tool == Tool::Select => Some(tools::between(stroke.start, stroke.end)),
+	        	_ => self.selection.clone(),
+	        };
+	        if let Some(r) = outline {
+	        	let screen = |x: isize, y: isize| [self.offset_x + half_width + self.zoom * x as f64,
+	        	                                   self.offset_y + half_height + self.zoom * y as f64];
+	        	let (top_left, bottom_right) = (screen(r.x_min, r.y_min), screen(r.x_max + 1, r.y_max + 1));
+	        	draw_outline(outline_color, [top_left[0], top_left[1], bottom_right[0], bottom_right[1]], c.transform, g);
+	        }
 	        
             //Reset transform
             c.reset();
@@ -149,7 +733,218 @@ impl GUI {
 		});
 
 	}
+
+	// Colors a cell by how many of the latest generations it changed in
+	fn heat_color(&self, heat: u32) -> [f32;4] {
+		if heat == 0 {
+			return STILL_COLOR;
+		}
+		blend(HEAT_COLORS[0], HEAT_COLORS[1], heat as f32 / self.heat_window as f32)
+	}
+
+	/// Draws the timeline of the generations that can be gone back to along the bottom of the
+	/// window, with a knob at the current generation
+	pub fn draw_timeline(&mut self, window: &mut PistonWindow, e: &Event, generation: &BigUint) {
+		let w_size = window.size();
+		let left = 5.0 + CHART_LENGTH as f64 + 10.0;
+		let width = (w_size.width as f64 - left - 10.0).max(0.0);
+		let top = w_size.height as f64 - 5.0 - TIMELINE_HEIGHT;
+		self.timeline_rect = [left, top, width, TIMELINE_HEIGHT];
+
+		// Only the leading bits matter for where the knob goes, so drop the rest before converting
+		let (ref first, ref last) = self.timeline;
+		let span = if last > first { last - first } else { BigUint::zero() };
+		let offset = if generation > first { generation - first } else { BigUint::zero() };
+		let shift = span.bits().saturating_sub(53);
+		let fraction = match ((offset >> shift).to_f64(), (span >> shift).to_f64()) {
+			(Some(offset), Some(span)) if span > 0.0 => (offset / span).min(1.0),
+			_ => 1.0,
+		};
+
+		window.draw_2d(e, |c, g| {
+			rectangle([0.9, 0.9, 0.9, 0.8], [left, top, width, TIMELINE_HEIGHT], c.transform, g);
+			rectangle([0.0, 0.0, 0.6, 1.0], [left + width * fraction - 2.0, top, 4.0, TIMELINE_HEIGHT], c.transform, g);
+		});
+	}
+
+	// Centers the view on the cell under the mouse on the minimap
+	fn recenter_on_minimap(&mut self) {
+		let (x0, y0, scale) = self.minimap_view;
+		let x = x0 + (self.mouse_pos[0] - self.minimap_rect[0]) / scale;
+		let y = y0 + (self.mouse_pos[1] - self.minimap_rect[1]) / scale;
+		self.offset_x = -self.zoom * x;
+		self.offset_y = -self.zoom * y;
+		self.prev_offset_x = self.offset_x;
+		self.prev_offset_y = self.offset_y;
+	}
+
+	/// Draws the minimap in the top right corner of the window, if it's shown: how densely packed
+	/// the cells are across the whole pattern, and where the window is looking. large_steps says
+	/// whether the engine can count a region without going through its cells
+	pub fn draw_minimap<L: LifeAlgorithm + ?Sized>(&mut self, life_obj: &L, large_steps: bool, window: &mut PistonWindow, e: &Event) {
+		if !self.minimap {
+			return;
+		}
+		let w_size = window.size();
+		let view = match self.visible_region(w_size.width, w_size.height) {
+			Some(view) => view,
+			None => return,
+		};
+		// The map takes in the view too, so that it still shows where the pattern is when the view
+		// has wandered away from it
+		let mut extent = view.clone();
+		if life_obj.population() > 0 {
+			extent.merge(&life_obj.get_bounds());
+		}
+
+		// Count the cells in squares of bin by bin cells
+		let width = extent.x_max as f64 - extent.x_min as f64 + 1.0;
+		let height = extent.y_max as f64 - extent.y_min as f64 + 1.0;
+		let bin = (width.max(height) / MINIMAP_BINS as f64).ceil().max(1.0);
+		let (bins_x, bins_y) = ((width / bin).ceil() as usize, (height / bin).ceil() as usize);
+		let mut counts = vec![0usize; bins_x * bins_y];
+		// Only HashLife's population_in skips over the cells. The sparse engines filter every live
+		// cell on each call, so for them one pass over the cells is always cheaper
+		if !large_steps || life_obj.population() <= MINIMAP_SCAN_LIMIT {
+			for (x,y) in life_obj.live_cells_in(&extent) {
+				let i = ((x as f64 - extent.x_min as f64) / bin) as usize;
+				let j = ((y as f64 - extent.y_min as f64) / bin) as usize;
+				counts[j.min(bins_y - 1) * bins_x + i.min(bins_x - 1)] += 1;
+			}
+		} else {
+			for j in 0..bins_y {
+				for i in 0..bins_x {
+					let x_min = extent.x_min as f64 + i as f64 * bin;
+					let y_min = extent.y_min as f64 + j as f64 * bin;
+					let square = Bounds { x_min: x_min as isize, x_max: (x_min + bin - 1.0) as isize,
+					                      y_min: y_min as isize, y_max: (y_min + bin - 1.0) as isize };
+					counts[j * bins_x + i] = life_obj.population_in(&square);
+				}
+			}
+		}
+		let most = counts.iter().cloned().max().unwrap_or(0).max(1) as f32;
+
+		let left = w_size.width as f64 - MINIMAP_SIZE - 10.0;
+		let top = 10.0;
+		let scale = MINIMAP_SIZE / (bin * bins_x.max(bins_y) as f64);
+		self.minimap_rect = [left, top, MINIMAP_SIZE, MINIMAP_SIZE];
+		self.minimap_view = (extent.x_min as f64, extent.y_min as f64, scale);
+
+		let mut background = self.theme.background;
+		background[3] = 0.9;
+		let mut outline = self.theme.selection;
+		outline[3] = 1.0;
+		let (live, grid) = (self.theme.live, self.theme.grid);
+		window.draw_2d(e, |c, g| {
+			rectangle(background, [left, top, MINIMAP_SIZE, MINIMAP_SIZE], c.transform, g);
+			for (k, &count) in counts.iter().enumerate().filter(|&(_, &count)| count > 0) {
+				// Square roots so that sparse squares still show up next to dense ones
+				let mut color = live;
+				color[3] = (count as f32 / most).sqrt().max(0.2);
+				let (i, j) = ((k % bins_x) as f64, (k / bins_x) as f64);
+				rectangle(color, [left + i * bin * scale, top + j * bin * scale, bin * scale, bin * scale], c.transform, g);
+			}
+			let (x0, y0) = (extent.x_min as f64, extent.y_min as f64);
+			let corners = [left + (view.x_min as f64 - x0) * scale, top + (view.y_min as f64 - y0) * scale,
+			               left + (view.x_max as f64 - x0 + 1.0) * scale, top + (view.y_max as f64 - y0 + 1.0) * scale];
+			draw_outline(outline, corners, c.transform, g);
+			draw_outline(grid, [left, top, left + MINIMAP_SIZE, top + MINIMAP_SIZE], c.transform, g);
+		});
+	}
+
+	/// Draws the pattern selected in the library panel, turned the way it would be stamped, in the
+	/// top right corner of the window below the minimap
+	pub fn draw_library_preview(&self, window: &mut PistonWindow, e: &Event) {
+		let pattern = match self.library.selected() {
+			Some(pattern) if self.library_open => pattern,
+			_ => return,
+		};
+		let cells = pattern.place(self.transform, (0, 0));
+		let x_min = cells.iter().map(|c| c.0).min().unwrap_or(0);
+		let x_max = cells.iter().map(|c| c.0).max().unwrap_or(0);
+		let y_min = cells.iter().map(|c| c.1).min().unwrap_or(0);
+		let y_max = cells.iter().map(|c| c.1).max().unwrap_or(0);
+		let (width, height) = ((x_max - x_min + 1) as f64, (y_max - y_min + 1) as f64);
+		let scale = (PREVIEW_SIZE / width.max(height)).min(PREVIEW_MAX_ZOOM);
+
+		let left = window.size().width as f64 - PREVIEW_SIZE - 10.0;
+		let top = if self.minimap { 20.0 + MINIMAP_SIZE } else { 10.0 };
+		// Centered in the box
+		let x0 = left + (PREVIEW_SIZE - width * scale) / 2.0;
+		let y0 = top + (PREVIEW_SIZE - height * scale) / 2.0;
+
+		let mut background = self.theme.background;
+		background[3] = 0.9;
+		let (live, grid) = (self.theme.live, self.theme.grid);
+		window.draw_2d(e, |c, g| {
+			rectangle(background, [left, top, PREVIEW_SIZE, PREVIEW_SIZE], c.transform, g);
+			for &(x,y) in &cells {
+				rectangle(live, [x0 + (x - x_min) as f64 * scale, y0 + (y - y_min) as f64 * scale, scale, scale], c.transform, g);
+			}
+			draw_outline(grid, [left, top, left + PREVIEW_SIZE, top + PREVIEW_SIZE], c.transform, g);
+		});
+	}
+
+	/// Draws the latest populations as a line chart in the bottom left corner of the window,
+	/// scaled so the largest of them reaches the top
+	pub fn draw_population_chart(&self, window: &mut PistonWindow, e: &Event) {
+		let window_height = window.size().height as f64;
+		let max = self.populations.iter().map(|&(p, _)| p).max().unwrap_or(0).max(1) as f64;
+		let (left, bottom) = (5.0, window_height - 5.0);
+
+		window.draw_2d(e, |c, g| {
+			rectangle([0.9, 0.9, 0.9, 0.8],
+			          [left, bottom - CHART_HEIGHT, CHART_LENGTH as f64, CHART_HEIGHT],
+			          c.transform, g);
+			// Under a B0 rule the counts of live and dead cells alternate, so each gets its own line
+			// rather than zig-zagging between the two
+			let mut last: [Option<[f64; 2]>; 2] = [None, None];
+			for (i, &(p, background)) in self.populations.iter().enumerate() {
+				let point = [left + i as f64, bottom - CHART_HEIGHT * p as f64 / max];
+				let k = background as usize;
+				if let Some(from) = last[k] {
+					line(CHART_COLORS[k], 0.5, [from[0], from[1], point[0], point[1]], c.transform, g);
+				}
+				last[k] = Some(point);
+			}
+		});
+	}
 }
 
+// Colors a live cell by how many generations it has been alive, on a log scale up to AGE_OLDEST
+fn age_color(age: u64) -> [f32;4] {
+	let t = ((age.max(1) as f64).ln() / AGE_OLDEST.ln()).min(1.0);
+	blend(AGE_COLORS[0], AGE_COLORS[1], t as f32)
+}
 
+// Mixes two colors, going from the first at 0 to the second at 1
+fn blend(from: [f32;4], to: [f32;4], t: f32) -> [f32;4] {
+	let mut out = [0.0; 4];
+	for i in 0..4 {
+		out[i] = from[i] + (to[i] - from[i]) * t;
+	}
+	out
+}
+
+// Returns the letter, digit or space a key types, for searching the library. Key codes follow
+// SDL's, which are the ASCII codes of the lower case characters
+fn key_char(key: Key) -> Option<char> {
+	let code = key.code();
+	match code {
+		0x20 | 0x30...0x39 | 0x61...0x7A => Some(code as u8 as char),
+		_ => None,
+	}
+}
 
+// Returns whether a point lies in a rectangle given as left, top, width and height
+fn inside(rect: [f64;4], pos: [f64;2]) -> bool {
+	pos[0] >= rect[0] && pos[0] <= rect[0] + rect[2] && pos[1] >= rect[1] && pos[1] <= rect[1] + rect[3]
+}
+
+// Draws the outline of a rectangle given by its left, top, right and bottom
+fn draw_outline<G: Graphics>(color: [f32;4], corners: [f64;4], transform: math::Matrix2d, g: &mut G) {
+	let (l, t, r, b) = (corners[0], corners[1], corners[2], corners[3]);
+	for edge in &[[l, t, r, t], [r, t, r, b], [r, b, l, b], [l, b, l, t]] {
+		line(color, 0.5, *edge, transform, g);
+	}
+}
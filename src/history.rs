@@ -0,0 +1,172 @@
+/*
+
+This file keeps the history of a run so that it can be stepped
+backwards, or scrubbed to any generation already visited.
+
+Every so many generations a checkpoint of the life object is saved
+(see LifeAlgorithm::checkpoint in common.rs). Going back to a
+generation restores the latest checkpoint at or before it and
+simulates forward from there, so at most one interval of
+generations is simulated again. Checkpoints of hashlife only keep
+the root of its tree, so they cost next to nothing, while the other
+algorithms copy their live cells.
+
+When there are too many checkpoints every other one is dropped and
+the interval doubles, so long runs take a bounded amount of memory
+and get a little slower to scrub instead.
+
+Editing the grid changes what comes after, so the checkpoints after
+the edit are dropped and the edited grid becomes the latest
+generation visited.
+
+*/
+
+use num::{BigUint,Zero};
+
+use common::{Checkpoint,LifeAlgorithm};
+
+// The most checkpoints kept at once
+const MAX_CHECKPOINTS: usize = 512;
+
+pub struct History {
+    /// Sorted by generation, with the first generation visited always first
+    checkpoints: Vec<Checkpoint>,
+    /// The most generations between checkpoints
+    interval: BigUint,
+    /// The latest generation visited
+    last: BigUint,
+}
+
+impl History {
+    /// Starts the history of a run at the current state of a life object, taking a checkpoint at
+    /// most every interval generations. If interval is 0, then the thread panics.
+    pub fn new<L: LifeAlgorithm + ?Sized>(life_obj: &L, interval: &BigUint) -> History {
+        assert!(!interval.is_zero());
+        History { checkpoints: vec![life_obj.checkpoint()], interval: interval.clone(), last: life_obj.get_big_generation() }
+    }
+
+    /// Returns the first generation that can be gone back to.
+    pub fn first(&self) -> &BigUint {
+        &self.checkpoints[0].generation
+    }
+
+    /// Returns the latest generation visited.
+    pub fn last(&self) -> &BigUint {
+        &self.last
+    }
+
+    // Returns the index of the latest checkpoint at or before the generation. There is always one
+    // unless the generation is before the first.
+    fn checkpoint_before(&self, generation: &BigUint) -> Option<usize> {
+        self.checkpoints.iter().rposition(|c| c.generation <= *generation)
+    }
+
+    /// Notes the generation a life object has reached after advancing, taking a checkpoint if it's
+    /// an interval or more past the one before it.
+    pub fn record<L: LifeAlgorithm + ?Sized>(&mut self, life_obj: &L) {
+        let generation = life_obj.get_big_generation();
+        let i = match self.checkpoint_before(&generation) {
+            Some(i) => i,
+            None => return,
+        };
+        if &generation - &self.checkpoints[i].generation >= self.interval {
+            self.checkpoints.insert(i+1, life_obj.checkpoint());
+        }
+        if generation > self.last {
+            self.last = generation;
+        }
+        if self.checkpoints.len() > MAX_CHECKPOINTS {
+            self.thin();
+        }
+    }
+
+    /// Notes that the grid of a life object was edited at its current generation, which replaces
+    /// everything that came after it.
+    pub fn edited<L: LifeAlgorithm + ?Sized>(&mut self, life_obj: &L) {
+        let generation = life_obj.get_big_generation();
+        // Only the first checkpoint is kept when it's the one edited, so the run still starts there
+        let keep = self.checkpoints.iter().take_while(|c| c.generation < generation).count().max(1);
+        self.checkpoints.truncate(keep);
+        if self.checkpoints[0].generation == generation {
+            self.checkpoints[0] = life_obj.checkpoint();
+        } else {
+            self.checkpoints.push(life_obj.checkpoint());
+        }
+        self.last = generation;
+    }
+
+    /// Moves a life object to a generation between first and last, which the history clamps it
+    /// to, by simulating forward from the checkpoint before it.
+    pub fn seek<L: LifeAlgorithm + ?Sized>(&self, life_obj: &mut L, generation: &BigUint) {
+        let generation = if generation < self.first() {
+            self.first().clone()
+        } else if *generation > self.last {
+            self.last.clone()
+        } else {
+            generation.clone()
+        };
+        // Carry on from where the life object is if that's nearer than any checkpoint
+        let i = self.checkpoint_before(&generation).unwrap();
+        let current = life_obj.get_big_generation();
+        if current > generation || current < self.checkpoints[i].generation {
+            life_obj.restore(&self.checkpoints[i]);
+        }
+        let remaining = &generation - life_obj.get_big_generation();
+        if !remaining.is_zero() {
+            life_obj.advance_by_big(&remaining);
+        }
+    }
+
+    // Drops every other checkpoint after the first and doubles the interval
+    fn thin(&mut self) {
+        let checkpoints = self.checkpoints.drain(..).enumerate().filter(|&(i, _)| i % 2 == 0).map(|(_, c)| c).collect();
+        self.checkpoints = checkpoints;
+        self.interval = &self.interval * BigUint::from(2u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use life_algorithms::sequential;
+
+    fn r_pentomino() -> sequential::Life {
+        let mut life_obj = sequential::Life::new();
+        for &cell in &[(1, 0), (2, 0), (0, 1), (1, 1), (1, 2)] {
+            life_obj.set(cell, true);
+        }
+        life_obj.clean_up();
+        life_obj
+    }
+
+    fn cells_at(generation: u64) -> Vec<(isize, isize)> {
+        let mut life_obj = r_pentomino();
+        life_obj.advance_by(generation);
+        let mut cells: Vec<(isize, isize)> = life_obj.live_cells().collect();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn long_runs_are_thinned_and_can_still_be_scrubbed() {
+        let mut life_obj = r_pentomino();
+        let mut history = History::new(&life_obj, &BigUint::from(1u64));
+        for _ in 0..MAX_CHECKPOINTS + 100 {
+            life_obj.advance_by(1);
+            history.record(&life_obj);
+        }
+        // Every other checkpoint went when there were one too many, and the interval doubled
+        assert_eq!(history.checkpoints.len(), MAX_CHECKPOINTS / 2 + 1 + 50);
+        assert_eq!(history.interval, BigUint::from(2u64));
+        assert!(history.checkpoints.iter().enumerate().all(|(i, c)| c.generation == BigUint::from(2 * i as u64)));
+        assert_eq!(*history.first(), BigUint::zero());
+        assert_eq!(*history.last(), BigUint::from((MAX_CHECKPOINTS + 100) as u64));
+
+        for &generation in &[0, 1, 301, 300, 611, 612, 2] {
+            history.seek(&mut life_obj, &BigUint::from(generation));
+            let mut cells: Vec<(isize, isize)> = life_obj.live_cells().collect();
+            cells.sort();
+            assert_eq!(cells, cells_at(generation), "generation {}", generation);
+        }
+    }
+}
@@ -0,0 +1,283 @@
+/*
+
+This file keeps the library of standard patterns that ships in
+assets/library, and the state of the in-GUI panel that searches it.
+
+Each directory in the library is a category, such as guns or
+methuselahs, holding RLE files. A pattern's name and description
+come from its "#N" and "#C" lines, the way Golly writes them, with
+the file name standing in for a missing name. The whole library is
+read when the GUI starts, so searching and previewing don't touch
+the disk.
+
+A pattern picked from the library is stamped into the grid centered
+on the cursor, turned and flipped by the current transform. Like the
+file browser, the panel doesn't draw anything itself, it only hands
+back the lines to show.
+
+*/
+
+use std::fs::{self,File};
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use common::LifeAlgorithm;
+use error::Error;
+use life_algorithms::sequential;
+use patterns::read_pattern;
+
+// How many patterns are listed at once
+const VISIBLE_ENTRIES: usize = 15;
+
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    pub name: String,
+    pub category: String,
+    pub description: String,
+    /// The live cells, with the top left corner of the pattern at (0,0)
+    pub cells: Vec<(isize, isize)>,
+}
+
+impl Pattern {
+    /// Reads a pattern of the given category from an RLE file.
+    pub fn from_file(path: &Path, category: &str) -> Result<Pattern, Error> {
+        let mut text = String::new();
+        if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut text)) {
+            return Err(Error::Io(path.to_path_buf(), e))
+        }
+        let mut life_obj = sequential::Life::new();
+        try!(read_pattern(&mut life_obj, &text, path));
+
+        let mut name = path.file_stem().map_or(String::new(), |n| n.to_string_lossy().replace('_', " "));
+        let mut description = vec![];
+        for line in text.lines().map(|l| l.trim()) {
+            if line.starts_with("#N") {
+                name = line[2..].trim().to_string();
+            } else if line.starts_with("#C") || line.starts_with("#c") {
+                description.push(line[2..].trim());
+            }
+        }
+
+        let cells: Vec<(isize, isize)> = life_obj.live_cells().collect();
+        let x_min = cells.iter().map(|c| c.0).min().unwrap_or(0);
+        let y_min = cells.iter().map(|c| c.1).min().unwrap_or(0);
+        let mut cells: Vec<(isize, isize)> = cells.into_iter().map(|(x, y)| (x - x_min, y - y_min)).collect();
+        cells.sort();
+        Ok(Pattern { name: name, category: category.to_string(), description: description.join(" "), cells: cells })
+    }
+
+    /// Returns the cells of the pattern turned by the transform and centered on a cell.
+    pub fn place(&self, transform: Transform, at: (isize, isize)) -> Vec<(isize, isize)> {
+        let moved: Vec<(isize, isize)> = self.cells.iter().map(|&c| transform.apply(c)).collect();
+        let x_min = moved.iter().map(|c| c.0).min().unwrap_or(0);
+        let x_max = moved.iter().map(|c| c.0).max().unwrap_or(0);
+        let y_min = moved.iter().map(|c| c.1).min().unwrap_or(0);
+        let y_max = moved.iter().map(|c| c.1).max().unwrap_or(0);
+        let left = at.0 - (x_max - x_min) / 2;
+        let top = at.1 - (y_max - y_min) / 2;
+        moved.into_iter().map(|(x, y)| (x - x_min + left, y - y_min + top)).collect()
+    }
+}
+
+/// How patterns are turned before they're stamped: flipped left to right, then turned clockwise a
+/// number of quarter turns.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transform {
+    quarter_turns: u8,
+    flipped: bool,
+}
+
+impl Transform {
+    /// Returns the transform that leaves patterns as they are.
+    pub fn new() -> Transform {
+        Transform { quarter_turns: 0, flipped: false }
+    }
+
+    /// Returns the transform followed by a quarter turn, clockwise or anticlockwise.
+    pub fn rotate(self, clockwise: bool) -> Transform {
+        let turn = if clockwise { 1 } else { 3 };
+        Transform { quarter_turns: (self.quarter_turns + turn) % 4, flipped: self.flipped }
+    }
+
+    /// Returns the transform followed by a flip from left to right.
+    pub fn flip(self) -> Transform {
+        // Flipping after turning is the same as flipping first and turning the other way
+        Transform { quarter_turns: (4 - self.quarter_turns) % 4, flipped: !self.flipped }
+    }
+
+    /// Moves a cell by the transform, about (0,0).
+    pub fn apply(self, cell: (isize, isize)) -> (isize, isize) {
+        let (mut x, mut y) = if self.flipped { (-cell.0, cell.1) } else { cell };
+        // With y going down the screen, (x,y) turns clockwise to (-y,x)
+        for _ in 0..self.quarter_turns {
+            let turned = (-y, x);
+            x = turned.0;
+            y = turned.1;
+        }
+        (x, y)
+    }
+
+    /// Describes the transform for the overlay, such as "turned 90 degrees, flipped".
+    pub fn name(self) -> String {
+        let mut name = format!("turned {} degrees", self.quarter_turns as usize * 90);
+        if self.flipped {
+            name.push_str(", flipped");
+        }
+        name
+    }
+}
+
+pub struct Library {
+    patterns: Vec<Pattern>,
+    query: String,
+    /// The patterns that match the query, as indices into patterns
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl Library {
+    /// Creates an empty library.
+    pub fn new() -> Library {
+        Library { patterns: vec![], query: String::new(), matches: vec![], selected: 0 }
+    }
+
+    /// Reads every RLE file in each directory of the library, sorted by category and then by file
+    /// name.
+    pub fn load(dir: &Path) -> Result<Library, Error> {
+        let io_error = |e: io::Error| Error::Io(dir.to_path_buf(), e);
+        let mut categories = vec![];
+        for entry in try!(fs::read_dir(dir).map_err(&io_error)) {
+            let path = try!(entry.map_err(&io_error)).path();
+            if path.is_dir() {
+                categories.push(path);
+            }
+        }
+        categories.sort();
+
+        let mut library = Library::new();
+        for category in categories {
+            let name = category.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+            let mut files = vec![];
+            for entry in try!(fs::read_dir(&category).map_err(|e| Error::Io(category.clone(), e))) {
+                let path = try!(entry.map_err(|e| Error::Io(category.clone(), e))).path();
+                if path.extension().map_or(false, |e| e == "rle") {
+                    files.push(path);
+                }
+            }
+            files.sort();
+            for file in files {
+                library.patterns.push(try!(Pattern::from_file(&file, &name)));
+            }
+        }
+        library.search();
+        Ok(library)
+    }
+
+    pub fn patterns(&self) -> &[Pattern] {
+        &self.patterns
+    }
+
+    /// Adds a letter, digit or space to the search.
+    pub fn type_char(&mut self, c: char) {
+        if c.is_alphanumeric() || c == ' ' {
+            self.query.push(c);
+            self.search();
+        }
+    }
+
+    /// Takes the last character off the search.
+    pub fn backspace(&mut self) {
+        self.query.pop();
+        self.search();
+    }
+
+    // Finds the patterns whose name, category or description contain every word of the query
+    fn search(&mut self) {
+        let words: Vec<String> = self.query.to_lowercase().split_whitespace().map(|w| w.to_string()).collect();
+        self.matches = self.patterns.iter().enumerate().filter(|&(_, p)| {
+            let text = format!("{} {} {}", p.name, p.category, p.description).to_lowercase();
+            words.iter().all(|w| text.contains(w.as_str()))
+        }).map(|(i, _)| i).collect();
+        self.selected = 0;
+    }
+
+    /// Moves the selection up (negative) or down (positive), stopping at either end of the list.
+    pub fn move_selection(&mut self, delta: isize) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let last = self.matches.len() as isize - 1;
+        self.selected = (self.selected as isize + delta).max(0).min(last) as usize;
+    }
+
+    /// Returns the selected pattern, if any match the search.
+    pub fn selected(&self) -> Option<&Pattern> {
+        self.matches.get(self.selected).map(|&i| &self.patterns[i])
+    }
+
+    /// Returns the lines to display: the search, then a window of the patterns that match it around
+    /// the selection, with the selected one marked and its description after them.
+    pub fn lines(&self) -> Vec<String> {
+        let mut out = vec![format!("Pattern library: {}_ (type to search, arrows to choose, Enter to pick, Esc to close)", self.query)];
+        if self.matches.is_empty() {
+            out.push("  (no patterns match)".to_string());
+            return out;
+        }
+        let first = if self.selected < VISIBLE_ENTRIES / 2 { 0 } else { self.selected - VISIBLE_ENTRIES / 2 };
+        let first = first.min(self.matches.len().saturating_sub(VISIBLE_ENTRIES));
+        for (i, &k) in self.matches.iter().enumerate().skip(first).take(VISIBLE_ENTRIES) {
+            let marker = if i == self.selected { ">" } else { " " };
+            out.push(format!("{} {}: {}", marker, self.patterns[k].category, self.patterns[k].name));
+        }
+        if let Some(pattern) = self.selected() {
+            out.push(String::new());
+            out.push(pattern.description.clone());
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A turn clockwise and a flip, one step at a time
+    fn turn(cell: (isize, isize)) -> (isize, isize) {
+        (-cell.1, cell.0)
+    }
+
+    fn flip(cell: (isize, isize)) -> (isize, isize) {
+        (-cell.0, cell.1)
+    }
+
+    #[test]
+    fn transforms_compose_in_order() {
+        let cell = (1, 2);
+        assert_eq!(Transform::new().rotate(true).flip().apply(cell), flip(turn(cell)));
+        assert_eq!(Transform::new().flip().rotate(true).apply(cell), turn(flip(cell)));
+        assert_eq!(Transform::new().rotate(true).rotate(true).flip().rotate(false).apply(cell), turn(turn(turn(flip(turn(turn(cell)))))));
+        assert_eq!(Transform::new().rotate(true).flip().flip(), Transform::new().rotate(true));
+        assert_eq!(Transform::new().rotate(false), Transform::new().rotate(true).rotate(true).rotate(true));
+    }
+
+    #[test]
+    fn patterns_are_placed_turned_about_their_middle() {
+        // A glider, going se
+        let glider = Pattern {
+            name: "glider".to_string(),
+            category: "gliders".to_string(),
+            description: String::new(),
+            cells: vec![(0, 2), (1, 0), (1, 2), (2, 1), (2, 2)],
+        };
+        let mut placed = glider.place(Transform::new(), (10, 10));
+        placed.sort();
+        assert_eq!(placed, [(9, 11), (10, 9), (10, 11), (11, 10), (11, 11)]);
+
+        // Turned it goes sw, and flipped after that it goes se again, in another phase,
+        // where flipping first and then turning would send it nw
+        let mut placed = glider.place(Transform::new().rotate(true).flip(), (10, 10));
+        placed.sort();
+        assert_eq!(placed, [(9, 10), (10, 11), (11, 9), (11, 10), (11, 11)]);
+    }
+}
@@ -1,30 +1,154 @@
 #![allow(unused_variables,dead_code)]
 
+use std::cmp::min;
 use std::sync::Arc;
-use std::collections::HashSet;
-use std::collections::hash_set;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::{isize,mem,usize};
 
-use common::{LifeAlgorithm,Bounds};
+use num::{BigInt,BigUint,Integer,One,Signed,ToPrimitive,Zero};
 
-#[derive(PartialEq, Eq, Hash, Clone)]
+use common::{LifeAlgorithm,Bounds,CellIter,Checkpoint};
+use error::Error;
+use patterns::{parse_macrocell,MacrocellNode};
+use rules::{Rule,RulePhases};
+use rule_table::{TableRule,MOORE_NEIGHBOURS};
+use super::{Algorithm,BoxedLife,Capabilities,RuleKind,Settings};
+
+pub static ALGORITHM: Algorithm = Algorithm {
+    name: "hashlife",
+    description: "Gosper's HashLife, for large or long-running patterns",
+    capabilities: Capabilities { custom_rules: true, rule_tables: true, topologies: false, large_steps: true, cell_tracking: false },
+    constructor: create,
+};
+
+fn create(settings: &Settings) -> Result<BoxedLife, Error> {
+    match settings.rule {
+        RuleKind::Life(rule)      => Ok(Box::new(try!(Life::with_rule(rule)))),
+        RuleKind::Table(ref rule) => Ok(Box::new(Life::with_table(rule.clone()))),
+    }
+}
+
+/// Returns the highest level of node whose cells fit in an isize relative to its center, and whose
+/// side length fits in an isize. Nodes up to this level work in isizes, and larger ones in BigInts.
+fn max_small_level() -> u64 {
+    (mem::size_of::<isize>() * 8 - 2) as u64
+}
+
+/// Returns 2^n as a BigInt.
+fn big_pow2(n: u64) -> BigInt {
+    BigInt::one() << n as usize
+}
+
+/// Bounds that may not fit in an isize, for patterns that have grown past max_small_level.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BigBounds {
+    pub x_min: BigInt,
+    pub x_max: BigInt,
+    pub y_min: BigInt,
+    pub y_max: BigInt,
+}
+
+impl BigBounds {
+    fn from_bounds(b: &Bounds) -> BigBounds {
+        BigBounds { x_min: BigInt::from(b.x_min as i64),
+                    x_max: BigInt::from(b.x_max as i64),
+                    y_min: BigInt::from(b.y_min as i64),
+                    y_max: BigInt::from(b.y_max as i64) }
+    }
+
+    fn shifted(&self, dx: &BigInt, dy: &BigInt) -> BigBounds {
+        BigBounds { x_min: &self.x_min + dx, x_max: &self.x_max + dx, y_min: &self.y_min + dy, y_max: &self.y_max + dy }
+    }
+
+    fn merge(&mut self, other: &BigBounds) {
+        if other.x_min < self.x_min { self.x_min = other.x_min.clone(); }
+        if other.x_max > self.x_max { self.x_max = other.x_max.clone(); }
+        if other.y_min < self.y_min { self.y_min = other.y_min.clone(); }
+        if other.y_max > self.y_max { self.y_max = other.y_max.clone(); }
+    }
+
+    /// Returns the bounds with every coordinate clamped to the range of an isize.
+    pub fn to_bounds(&self) -> Bounds {
+        fn clamp(n: &BigInt) -> isize {
+            n.to_isize().unwrap_or(if n.is_negative() { isize::MIN } else { isize::MAX })
+        }
+        Bounds { x_min: clamp(&self.x_min), x_max: clamp(&self.x_max), y_min: clamp(&self.y_min), y_max: clamp(&self.y_max) }
+    }
+}
+
+#[derive(Clone)]
 enum LifeData {
-    Leaf(bool),
+    Leaf(u8),
     Split(Arc<LifeNode>, Arc<LifeNode>, Arc<LifeNode>, Arc<LifeNode>),
 }
 
 use self::LifeData::*;
 
-#[derive(PartialEq, Eq, Hash, Clone)]
+// Every node is canonicalized through do_arc, so two nodes are equal exactly when their components
+// are the same Arcs. Comparing and hashing by pointer keeps both O(1) instead of walking the tree.
+impl PartialEq for LifeData {
+    fn eq(&self, other: &LifeData) -> bool {
+        match (self, other) {
+            (&Leaf(a), &Leaf(b)) => a == b,
+            (&Split(ref a0, ref a1, ref a2, ref a3), &Split(ref b0, ref b1, ref b2, ref b3)) => {
+                Arc::ptr_eq(a0, b0) && Arc::ptr_eq(a1, b1) && Arc::ptr_eq(a2, b2) && Arc::ptr_eq(a3, b3)
+            },
+            _ => false,
+        }
+    }
+}
+
+impl Eq for LifeData {}
+
+impl Hash for LifeData {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            &Leaf(v) => v.hash(state),
+            &Split(ref ne, ref nw, ref sw, ref se) => {
+                for node in &[ne, nw, sw, se] {
+                    (&***node as *const LifeNode).hash(state);
+                }
+            },
+        }
+    }
+}
+
+#[derive(Clone)]
 struct LifeNode {
     level: u64,
     info: LifeData,
+    /// The number of cells with a non-zero state. A level n node holds up to 4^n cells, which
+    /// overflows a u64 past level 32. Past level 64 it can overflow this too, so it saturates
+    population: u128,
+    /// The smallest bounds holding every cell with a non-zero state, relative to the center of the
+    /// node, or None if there are none. Above max_small_level they may not fit, so they are always
+    /// None and worked out when needed by big_bounds
+    bounds: Option<Bounds>,
+}
+
+// The population and bounds follow from the components, so they are left out of comparisons and
+// hashes
+impl PartialEq for LifeNode {
+    fn eq(&self, other: &LifeNode) -> bool {
+        self.level == other.level && self.info == other.info
+    }
+}
+
+impl Eq for LifeNode {}
+
+impl Hash for LifeNode {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.level.hash(state);
+        self.info.hash(state);
+    }
 }
 
 impl LifeNode {
-    /// Creates a new leaf LifeNode object with state v.
-    pub fn new(v: bool) -> LifeNode {
-        LifeNode { level: 0, info: Leaf(v) }
+    /// Creates a new leaf LifeNode object with state v, where 0 is dead.
+    pub fn new(v: u8) -> LifeNode {
+        let bounds = if v != 0 { Some(Bounds::new()) } else { None };
+        LifeNode { level: 0, info: Leaf(v), population: (v != 0) as u128, bounds: bounds }
     }
 
     /// Creates a new non-leaf LifeNode object with the specified components. If the components'
@@ -36,71 +160,46 @@ impl LifeNode {
         assert_eq!(ne.get_level(), nw.get_level());
         assert_eq!(nw.get_level(), sw.get_level());
         assert_eq!(sw.get_level(), se.get_level());
-        LifeNode { level: ne.get_level()+1, info: Split(ne, nw, sw, se) }
-    }
-
-    /// Turns a square grid of same-level LifeNodes into a single LifeNode
-    pub fn from_grid(grid: Vec<Vec<Arc<LifeNode>>>,
-                     hashes: &mut HashMap<LifeNode, Arc<LifeNode>>,
-                     dead_squares: &Vec<Arc<LifeNode>>) -> LifeNode {
-        if grid.len() == 2 {
-            LifeNode::with_components(grid[1][1].clone(),
-                                      grid[0][1].clone(),
-                                      grid[0][0].clone(),
-                                      grid[1][0].clone())
-        } else if grid.len()%2 != 0 {
-            let num = grid.len();
-            let mut new: Vec<Vec<Arc<LifeNode>>> = vec![vec![]; 2*num];
-            for i in 0..num {
-                for j in 0..num {
-                    new[2*i].push(grid[i][j].get_sw());
-                    new[2*i].push(grid[i][j].get_nw());
-                    new[2*i+1].push(grid[i][j].get_se());
-                    new[2*i+1].push(grid[i][j].get_nw());
-                }
-            }
-            LifeNode::from_grid(new, hashes, dead_squares)
-        } else {
-            let num = grid.len()/2;
-            let goal = num.next_power_of_two();
-            let lvl = grid[0][0].get_level() as usize;
-            let mut grid_ne: Vec<Vec<Arc<LifeNode>>> = vec![];
-            let mut grid_nw: Vec<Vec<Arc<LifeNode>>> = vec![];
-            let mut grid_sw: Vec<Vec<Arc<LifeNode>>> = vec![];
-            let mut grid_se: Vec<Vec<Arc<LifeNode>>> = vec![];
-            for i in 0..goal-num {
-                grid_nw.push(vec![dead_squares[lvl].clone(); goal]);
-                grid_sw.push(vec![dead_squares[lvl].clone(); goal]);
-            }
-            for i in 0..num {
-                grid_ne.push(vec![]);
-                grid_nw.push(vec![]);
-                grid_sw.push(vec![]);
-                grid_se.push(vec![]);
-                for j in 0..goal-num {
-                    grid_sw[i+goal-num].push(dead_squares[lvl].clone());
-                    grid_se[i].push(dead_squares[lvl].clone());
-                }
-                for j in 0..num {
-                    grid_ne[i].push(grid[i+num][j+num].clone());
-                    grid_nw[i+goal-num].push(grid[i][j+num].clone());
-                    grid_sw[i+goal-num].push(grid[i][j].clone());
-                    grid_se[i].push(grid[i+num][j].clone());
-                }
-                for j in 0..goal-num {
-                    grid_ne[i].push(dead_squares[lvl].clone());
-                    grid_nw[i+goal-num].push(dead_squares[lvl].clone());
-                }
-            }
-            for i in 0..goal-num {
-                grid_ne.push(vec![dead_squares[lvl].clone(); goal]);
-                grid_nw.push(vec![dead_squares[lvl].clone(); goal]);
+        let population = ne.population.saturating_add(nw.population)
+                                      .saturating_add(sw.population)
+                                      .saturating_add(se.population);
+        let bounds = if ne.get_level() < max_small_level() { LifeNode::merged_bounds(&ne, &nw, &sw, &se) } else { None };
+        LifeNode { level: ne.get_level()+1, info: Split(ne, nw, sw, se), population: population, bounds: bounds }
+    }
+
+    // Returns the bounds of a node with the given components, relative to its center. If the node
+    // would be above max_small_level, then they overflow.
+    fn merged_bounds(ne: &LifeNode, nw: &LifeNode, sw: &LifeNode, se: &LifeNode) -> Option<Bounds> {
+        // The quadrant offsets of the new node, as in quadrant_offsets
+        let half = 1 << ne.get_level();
+        let (e, w) = (half/2, half/2 - half);
+        let mut bounds: Option<Bounds> = None;
+        for &(quadrant, dx, dy) in &[(ne, e, e), (nw, w, e), (sw, w, w), (se, e, w)] {
+            if let Some(ref b) = quadrant.bounds {
+                let b = Bounds { x_min: b.x_min+dx, x_max: b.x_max+dx, y_min: b.y_min+dy, y_max: b.y_max+dy };
+                bounds = Some(match bounds {
+                    Some(mut acc) => { acc.merge(&b); acc },
+                    None          => b,
+                });
             }
-            LifeNode::with_components(LifeNode::from_grid(grid_ne, hashes, dead_squares).do_arc(hashes),
-                                      LifeNode::from_grid(grid_nw, hashes, dead_squares).do_arc(hashes),
-                                      LifeNode::from_grid(grid_sw, hashes, dead_squares).do_arc(hashes),
-                                      LifeNode::from_grid(grid_se, hashes, dead_squares).do_arc(hashes))
         }
+        bounds
+    }
+
+    /// Returns the number of cells in the node with a non-zero state.
+    pub fn get_population(&self) -> u128 {
+        self.population
+    }
+
+    /// Returns true if the twelve squares of level self.level-2 around the edge of this node are
+    /// all dead. If level < 2, then the thread panics.
+    pub fn has_empty_border(&self) -> bool {
+        assert!(self.level >= 2);
+        vec![self.get_ne().get_se(), self.get_ne().get_ne(), self.get_ne().get_nw(),
+             self.get_nw().get_ne(), self.get_nw().get_nw(), self.get_nw().get_sw(),
+             self.get_sw().get_nw(), self.get_sw().get_sw(), self.get_sw().get_se(),
+             self.get_se().get_sw(), self.get_se().get_se(), self.get_se().get_ne()]
+            .iter().all(|node| node.population == 0)
     }
 
     /// Returns the level of the node.
@@ -108,9 +207,8 @@ impl LifeNode {
         self.level
     }
 
-    /// Returns whether the current node is alive or dead as a bool. If the node is not a leaf, then
-    /// the thread panics.
-    pub fn is_alive(&self) -> bool {
+    /// Returns the state of the current node. If the node is not a leaf, then the thread panics.
+    pub fn get_state(&self) -> u8 {
         if let &Leaf(v) = &self.info {
             v
         } else {
@@ -154,150 +252,275 @@ impl LifeNode {
         }
     }
 
-    /// Returns half the side length of the square represented by this node.
+    /// Returns all four quadrants, in the order ne, nw, sw, se. If the node is a leaf, then the
+    /// thread panics.
+    fn quadrants(&self) -> [Arc<LifeNode>; 4] {
+        [self.get_ne(), self.get_nw(), self.get_sw(), self.get_se()]
+    }
+
+    /// Returns the side length of the square represented by this node. If the node is above
+    /// max_small_level, then it overflows.
     pub fn side_len(&self) -> isize {
-        (2 as isize) << ((self.level-1) as isize)
+        1 << self.level
+    }
+
+    /// Returns the offsets from the center of this node to the centers of its eastern and western
+    /// (or northern and southern) quadrants. Leaves have their single cell at (0,0), so the
+    /// quadrants of a level 1 node are at offsets 0 and -1.
+    fn quadrant_offsets(&self) -> (isize, isize) {
+        let half = self.side_len()/2;
+        (half/2, half/2 - half)
     }
 
     /// Returns true if the (x,y) coordinates lie inside the current node and false otherwise.
     pub fn is_inside(&self, x: isize, y: isize) -> bool {
-        let bound = self.side_len();
+        let bound = self.side_len()/2;
         (x == 0 && y == 0) || (x >= -bound && x < bound && y >= -bound && y < bound)
     }
 
-    /// Returns the node of the specified level at the specified offset of nodes of that size from
-    /// the center. The southwest-most subnode of ne of the specified level has offset (0,0), the
-    /// one to its right has offset (1,0), etc. If this node is smaller than the one being searched
-    /// for, the thread panics.
-    pub fn get_chunk(&self, lvl: u64, x: isize, y: isize,
-                     hashes: &mut HashMap<LifeNode, Arc<LifeNode>>) -> Arc<LifeNode> {
-        assert!(self.level >= lvl);
-        let cap = (2 as isize) << ((self.level-lvl) as isize);
-        assert!((x == 0 && y == 0) || (x >= -cap && x < cap && y >= -cap && y < cap));
-        if self.level == lvl {
-            self.clone().do_arc(hashes)
-        } else if self.level == lvl+1 {
-            match (x,y) {
-                (0 , 0 ) => self.get_ne(),
-                (-1, 0 ) => self.get_nw(),
-                (-1, -1) => self.get_sw(),
-                (0 , -1) => self.get_se(),
-                _        => panic!("This is really weird.")
-            }
-        } else if x >= 0 && y >= 0 {
-            self.get_ne().get_chunk(lvl, x-cap/2, y-cap/2, hashes)
-        } else if x <  0 && y >= 0 {
-            self.get_nw().get_chunk(lvl, x+cap  , y-cap/2, hashes)
-        } else if x <  0 && y <  0 {
-            self.get_sw().get_chunk(lvl, x+cap  , y+cap  , hashes)
-        } else {
-            self.get_se().get_chunk(lvl, x-cap/2, y+cap  , hashes)
-        }
-    }
-
-    /// Returns the alive/dead value of the node at the given coordinates, assuming that the
-    /// southwest corner of the northeast subnode is (0,0). If the coordinates are invalid, then
-    /// the thread panics.
-    pub fn get_value(&self, x: isize, y: isize) -> bool {
+    /// Returns the state of the node at the given coordinates, assuming that the southwest corner
+    /// of the northeast subnode is (0,0). If the coordinates are invalid, then the thread panics.
+    pub fn get_value(&self, x: isize, y: isize) -> u8 {
         assert!(self.is_inside(x,y));
-        let bound = self.side_len();
         if self.level == 0 {
-            self.is_alive()
-        } else if x >= 0 && y >= 0 {
-            self.get_ne().get_value(x+bound/2, y+bound/2)
+            return self.get_state()
+        }
+        let (e, w) = self.quadrant_offsets();
+        if x >= 0 && y >= 0 {
+            self.get_ne().get_value(x-e, y-e)
         } else if x < 0 && y >= 0 {
-            self.get_nw().get_value(x+bound  , y+bound/2)
+            self.get_nw().get_value(x-w, y-e)
         } else if x < 0 && y < 0 {
-            self.get_sw().get_value(x+bound  , y+bound  )
+            self.get_sw().get_value(x-w, y-w)
         } else {
-            self.get_se().get_value(x+bound/2, y+bound  )
+            self.get_se().get_value(x-e, y-w)
         }
     }
 
-    /// Returns the Arc corresponding to the given node in hashes or inserts it if it does not
-    /// already exist.
-    fn do_arc(self, hashes: &mut HashMap<LifeNode, Arc<LifeNode>>) -> Arc<LifeNode> {
-        let or_value = Arc::new(self.clone());
-        hashes.entry(self).or_insert(or_value).clone()
+    /// Returns true if the coordinates lie inside the current node, like is_inside but at any level.
+    pub fn is_inside_big(&self, x: &BigInt, y: &BigInt) -> bool {
+        if self.level == 0 {
+            return x.is_zero() && y.is_zero()
+        }
+        let bound = big_pow2(self.level-1);
+        let low = -&bound;
+        *x >= low && *x < bound && *y >= low && *y < bound
     }
 
-    fn find_all_live(&self, acc: &mut HashSet<(isize, isize)>, x: isize, y: isize, dead_squares: &Vec<Arc<LifeNode>>) {
-        if self.level == 0 && self.is_alive() {
-            acc.insert((x,y));
-        } else {
-            let lvl = (self.level-1) as usize;
-            let num = self.side_len()/2;
-            if self.get_ne() != dead_squares[lvl] {
-                self.get_ne().find_all_live(acc, x+num, y+num, dead_squares);
+    /// Returns the index (as in quadrants) of the quadrant holding the given coordinates, along
+    /// with the offsets to its center, for nodes above max_small_level.
+    fn big_quadrant(&self, x: &BigInt, y: &BigInt) -> (usize, BigInt, BigInt) {
+        let e = big_pow2(self.level-2);
+        let w = -&e;
+        match (x.is_negative(), y.is_negative()) {
+            (false, false) => (0, e.clone(), e),
+            (true, false)  => (1, w, e),
+            (true, true)   => (2, w.clone(), w),
+            (false, true)  => (3, e, w),
+        }
+    }
+
+    /// Returns the state of the node at the given coordinates, like get_value but at any level.
+    /// If the coordinates are invalid, then the thread panics.
+    pub fn get_value_big(&self, x: &BigInt, y: &BigInt) -> u8 {
+        assert!(self.is_inside_big(x, y));
+        if self.level <= max_small_level() {
+            return self.get_value(x.to_isize().unwrap(), y.to_isize().unwrap())
+        }
+        let (i, dx, dy) = self.big_quadrant(x, y);
+        self.quadrants()[i].get_value_big(&(x - dx), &(y - dy))
+    }
+
+    /// Returns a copy of the node with the given cell changed, like change_value but at any level.
+    /// If the coordinates are invalid, then the thread panics.
+    fn change_value_big(&self, x: &BigInt, y: &BigInt, val: u8,
+                        hashes: &mut HashMap<LifeNode, Arc<LifeNode>>) -> Arc<LifeNode> {
+        assert!(self.is_inside_big(x, y));
+        if self.level <= max_small_level() {
+            return self.change_value(x.to_isize().unwrap(), y.to_isize().unwrap(), val, hashes)
+        }
+        let (i, dx, dy) = self.big_quadrant(x, y);
+        let mut quadrants = self.quadrants();
+        quadrants[i] = quadrants[i].change_value_big(&(x - dx), &(y - dy), val, hashes);
+        LifeNode::with_components(quadrants[0].clone(), quadrants[1].clone(),
+                                  quadrants[2].clone(), quadrants[3].clone()).do_arc(hashes)
+    }
+
+    /// Returns the smallest bounds holding every cell with a non-zero state, relative to the
+    /// center of the node, at any level. The bounds of nodes above max_small_level are remembered
+    /// in memo, since the same node turns up in many places.
+    fn big_bounds(&self, memo: &mut HashMap<*const LifeNode, Option<BigBounds>>) -> Option<BigBounds> {
+        if self.level <= max_small_level() {
+            return self.bounds.as_ref().map(BigBounds::from_bounds)
+        }
+        if self.population == 0 {
+            return None
+        }
+        let key = self as *const LifeNode;
+        if let Some(out) = memo.get(&key) {
+            return out.clone()
+        }
+        let e = big_pow2(self.level-2);
+        let w = -&e;
+        let mut out: Option<BigBounds> = None;
+        for &(ref quadrant, dx, dy) in &[(self.get_ne(), &e, &e), (self.get_nw(), &w, &e),
+                                     (self.get_sw(), &w, &w), (self.get_se(), &e, &w)] {
+            if let Some(b) = quadrant.big_bounds(memo) {
+                let b = b.shifted(dx, dy);
+                out = Some(match out {
+                    Some(mut acc) => { acc.merge(&b); acc },
+                    None          => b,
+                });
             }
-            if self.get_nw() != dead_squares[lvl] {
-                self.get_nw().find_all_live(acc, x-num, y+num, dead_squares);
+        }
+        memo.insert(key, out.clone());
+        out
+    }
+
+    /// Adds to out the nodes of level at most max_small_level whose cells have isize coordinates,
+    /// with their centers, given that the center of this node is at (x,y). Cells outside of them
+    /// can only be reached with BigInt coordinates.
+    fn small_nodes<'a>(&'a self, x: BigInt, y: BigInt, out: &mut Vec<(&'a LifeNode, isize, isize)>) {
+        if self.population == 0 {
+            return
+        }
+        if self.level <= max_small_level() {
+            // The centers of nodes of this level are odd multiples of a quarter of the isize range,
+            // so every cell of a node with an isize center also has isize coordinates
+            if let (Some(x), Some(y)) = (x.to_isize(), y.to_isize()) {
+                out.push((self, x, y));
             }
-            if self.get_sw() != dead_squares[lvl] {
-                self.get_sw().find_all_live(acc, x-num, y-num, dead_squares);
+            return
+        }
+        let half = big_pow2(self.level-1);
+        let (low, high) = (BigInt::from(isize::MIN as i64), BigInt::from(isize::MAX as i64));
+        if &x + &half <= low || &x - &half > high || &y + &half <= low || &y - &half > high {
+            return
+        }
+        let e = big_pow2(self.level-2);
+        let w = -&e;
+        if let Split(ref ne, ref nw, ref sw, ref se) = self.info {
+            ne.small_nodes(&x + &e, &y + &e, out);
+            nw.small_nodes(&x + &w, &y + &e, out);
+            sw.small_nodes(&x + &w, &y + &w, out);
+            se.small_nodes(&x + &e, &y + &w, out);
+        }
+    }
+
+    /// Adds the lines of a Macrocell file (see patterns.rs) for this node and any of its quadrants
+    /// not already written to lines, returning the line number of the node, or 0 if it's empty.
+    /// Two-state nodes stop at 8x8 squares and multi-state ones at level 1. numbers holds the line
+    /// numbers of the nodes already written.
+    fn write_macrocell(&self, squares: bool, numbers: &mut HashMap<*const LifeNode, usize>, lines: &mut Vec<String>) -> usize {
+        if self.population == 0 {
+            return 0
+        }
+        let key = self as *const LifeNode;
+        if let Some(&n) = numbers.get(&key) {
+            return n
+        }
+        // Macrocell rows go down the screen, so our southern quadrants are its northern ones
+        let line = if squares && self.level == 3 {
+            let mut line = String::new();
+            for y in -4..4 {
+                let row: Vec<bool> = (-4..4).map(|x| self.get_value(x, y) != 0).collect();
+                if let Some(last) = row.iter().rposition(|&alive| alive) {
+                    line.extend(row[..last+1].iter().map(|&alive| if alive { '*' } else { '.' }));
+                }
+                line.push('$');
             }
-            if self.get_se() != dead_squares[lvl] {
-                self.get_se().find_all_live(acc, x+num, y-num, dead_squares);
+            // Empty rows at the end can be left out
+            while line.ends_with("$$") {
+                line.pop();
             }
+            line
+        } else if self.level == 1 {
+            format!("1 {} {} {} {}", self.get_sw().get_state(), self.get_se().get_state(),
+                                     self.get_nw().get_state(), self.get_ne().get_state())
+        } else {
+            let quadrants: Vec<usize> = [self.get_sw(), self.get_se(), self.get_nw(), self.get_ne()].iter()
+                .map(|q| q.write_macrocell(squares, numbers, lines))
+                .collect();
+            format!("{} {} {} {} {}", self.level, quadrants[0], quadrants[1], quadrants[2], quadrants[3])
+        };
+        lines.push(line);
+        numbers.insert(key, lines.len());
+        lines.len()
+    }
+
+    /// Returns the Arc corresponding to the given node in hashes or inserts it if it does not
+    /// already exist.
+    fn do_arc(self, hashes: &mut HashMap<LifeNode, Arc<LifeNode>>) -> Arc<LifeNode> {
+        if let Some(out) = hashes.get(&self) {
+            return out.clone()
+        }
+        let out = Arc::new(self.clone());
+        hashes.insert(self, out.clone());
+        out
+    }
+
+    // Returns the offsets from the center of this node to its lowest and highest cells
+    fn extent(&self) -> (isize, isize) {
+        if self.level == 0 { (0, 0) } else { (-self.side_len()/2, self.side_len()/2 - 1) }
+    }
+
+    /// Returns whether any cell of this node lies inside the region, given that the center of the
+    /// node is at (x,y).
+    fn overlaps(&self, x: isize, y: isize, region: &Bounds) -> bool {
+        let (low, high) = self.extent();
+        x+high >= region.x_min && x+low <= region.x_max && y+high >= region.y_min && y+low <= region.y_max
+    }
+
+    /// Returns the number of cells with a non-zero state inside the region, given that the center
+    /// of the node is at (x,y). Nodes wholly inside the region use their cached population.
+    fn population_in(&self, x: isize, y: isize, region: &Bounds) -> u128 {
+        let (low, high) = self.extent();
+        if self.population == 0 || !self.overlaps(x, y, region) {
+            return 0
+        }
+        if region.contains(x+low, y+low) && region.contains(x+high, y+high) {
+            return self.population
+        }
+        match self.info {
+            Leaf(_) => unreachable!(),
+            Split(ref ne, ref nw, ref sw, ref se) => {
+                let (e, w) = self.quadrant_offsets();
+                ne.population_in(x+e, y+e, region) + nw.population_in(x+w, y+e, region) +
+                sw.population_in(x+w, y+w, region) + se.population_in(x+e, y+w, region)
+            },
         }
     }
 
-    pub fn change_value(&self, x: isize, y: isize, val: bool,
+    pub fn change_value(&self, x: isize, y: isize, val: u8,
                         hashes: &mut HashMap<LifeNode, Arc<LifeNode>>) -> Arc<LifeNode> {
         assert!(self.is_inside(x,y));
-        let bound = self.side_len();
         if self.level == 0 {
-            LifeNode::new(val).do_arc(hashes)
-        } else if x >= 0 && y >= 0 {
-            LifeNode::with_components(self.get_ne().change_value(x+bound/2, y+bound/2, val, hashes),
+            return LifeNode::new(val).do_arc(hashes)
+        }
+        let (e, w) = self.quadrant_offsets();
+        if x >= 0 && y >= 0 {
+            LifeNode::with_components(self.get_ne().change_value(x-e, y-e, val, hashes),
                                       self.get_nw(),
                                       self.get_sw(),
                                       self.get_se()).do_arc(hashes)
         } else if x < 0 && y >= 0 {
             LifeNode::with_components(self.get_ne(),
-                                      self.get_nw().change_value(x+bound  , y+bound/2, val, hashes),
+                                      self.get_nw().change_value(x-w, y-e, val, hashes),
                                       self.get_sw(),
                                       self.get_se()).do_arc(hashes)
         } else if x < 0 && y < 0 {
             LifeNode::with_components(self.get_ne(),
                                       self.get_nw(),
-                                      self.get_sw().change_value(x+bound  , y+bound  , val, hashes),
+                                      self.get_sw().change_value(x-w, y-w, val, hashes),
                                       self.get_se()).do_arc(hashes)
         } else {
             LifeNode::with_components(self.get_ne(),
                                       self.get_nw(),
                                       self.get_sw(),
-                                      self.get_se().change_value(x+bound/2, y+bound  , val, hashes)).do_arc(hashes)
+                                      self.get_se().change_value(x-e, y-w, val, hashes)).do_arc(hashes)
         }
     }
 
-    /// Returns true if all the border nodes of self with level self.level-2 are equal to target
-    /// and false otherwise. If self.level < 2, the thread panics.
-    pub fn is_uniform_border(&self,
-                             hashes: &HashMap<LifeNode, Arc<LifeNode>>,
-                             target: Arc<LifeNode>) -> bool {
-        assert!(self.level >= 2);
-        let mut acc = true;
-        for node in vec![self.get_ne().get_se(),
-                         self.get_ne().get_ne(),
-                         self.get_ne().get_nw(),
-                         self.get_nw().get_ne(),
-                         self.get_nw().get_nw(),
-                         self.get_nw().get_sw(),
-                         self.get_sw().get_nw(),
-                         self.get_sw().get_sw(),
-                         self.get_sw().get_se(),
-                         self.get_se().get_sw(),
-                         self.get_se().get_se(),
-                         self.get_se().get_ne()] {
-            acc &= node == target;
-            if !acc {
-                break;
-            }
-        }
-        acc
-    }
-
     /// Returns the node representing the centered square inside the current node of half the side
     /// length. If level < 2, then the thread panics.
     pub fn centered_forward(&self,
@@ -337,105 +560,149 @@ impl LifeNode {
                                   s.get_ne()).do_arc(hashes)
     }
 
-    /// Returns the next value of a cell, given its neighbors. If the neighbors object does not have
-    /// length 8, the thread panics.
-    fn next_value_from_neighbors(current: bool,
-                                 neighbors: Vec<bool>,
+    /// Returns the next state of the cell at (x,y) in a 4x4 grid of states indexed as grid[y][x].
+    /// If the cell is on the edge of the grid, then the thread panics.
+    fn next_value_from_neighbors(grid: &[[u8; 4]; 4],
+                                 x: usize,
+                                 y: usize,
+                                 rule: &Transition,
+                                 phase: usize,
                                  hashes: &mut HashMap<LifeNode, Arc<LifeNode>>) -> Arc<LifeNode> {
-        assert_eq!(neighbors.len(), 8);
-        let mut neighbors_sum: u8 = 0;
-        for n in neighbors {
-            neighbors_sum += if n { 1 } else { 0 };
+        let mut neighbors = [0u8; 8];
+        for (n, &(dx, dy)) in neighbors.iter_mut().zip(MOORE_NEIGHBOURS.iter()) {
+            *n = grid[(y as isize + dy) as usize][(x as isize + dx) as usize];
         }
-        LifeNode::new((neighbors_sum == 3) || (current && (neighbors_sum == 2))).do_arc(hashes)
+        LifeNode::new(rule.next_state(phase, grid[y][x], &neighbors)).do_arc(hashes)
     }
 
     /// Returns the node representing the centered square inside the current node of half the side
-    /// length, advanced by 2^(level-2) generations. If level < 2, then the thread panics.
-    pub fn advanced_center(&self,
-                           hashes: &mut HashMap<LifeNode, Arc<LifeNode>>,
-                           memos: &mut HashMap<LifeNode, Arc<LifeNode>>) -> Arc<LifeNode> {
-        if let Some(out) = memos.get(self) {
+    /// length, advanced by 2^step generations. The first generation is stepped in the given phase
+    /// of the rule, after which the phases alternate. If level < 2 or step > level-2, then the
+    /// thread panics.
+    fn advanced_center(&self,
+                       step: u64,
+                       rule: &Transition,
+                       phase: usize,
+                       hashes: &mut HashMap<LifeNode, Arc<LifeNode>>,
+                       memos: &mut HashMap<(LifeNode, u64, usize), Arc<LifeNode>>) -> Arc<LifeNode> {
+        let key = (self.clone(), step, phase);
+        if let Some(out) = memos.get(&key) {
             return out.clone()
         }
         assert!(self.level >= 2);
-        if self.level == 2 {
-            let new_ne = LifeNode::next_value_from_neighbors(self.get_ne().get_sw().is_alive(),
-                                                             vec![self.get_ne().get_se().is_alive(),
-                                                                  self.get_ne().get_ne().is_alive(),
-                                                                  self.get_ne().get_nw().is_alive(),
-                                                                  self.get_nw().get_ne().is_alive(),
-                                                                  self.get_nw().get_se().is_alive(),
-                                                                  self.get_sw().get_ne().is_alive(),
-                                                                  self.get_se().get_nw().is_alive(),
-                                                                  self.get_se().get_ne().is_alive()],
-                                                             hashes);
-            let new_nw = LifeNode::next_value_from_neighbors(self.get_nw().get_se().is_alive(),
-                                                             vec![self.get_ne().get_sw().is_alive(),
-                                                                  self.get_ne().get_nw().is_alive(),
-                                                                  self.get_nw().get_ne().is_alive(),
-                                                                  self.get_nw().get_nw().is_alive(),
-                                                                  self.get_nw().get_sw().is_alive(),
-                                                                  self.get_sw().get_nw().is_alive(),
-                                                                  self.get_sw().get_ne().is_alive(),
-                                                                  self.get_se().get_nw().is_alive()],
-                                                             hashes);
-            let new_sw = LifeNode::next_value_from_neighbors(self.get_sw().get_ne().is_alive(),
-                                                             vec![self.get_se().get_nw().is_alive(),
-                                                                  self.get_ne().get_sw().is_alive(),
-                                                                  self.get_nw().get_se().is_alive(),
-                                                                  self.get_nw().get_sw().is_alive(),
-                                                                  self.get_sw().get_nw().is_alive(),
-                                                                  self.get_sw().get_sw().is_alive(),
-                                                                  self.get_sw().get_se().is_alive(),
-                                                                  self.get_se().get_sw().is_alive()],
-                                                             hashes);
-            let new_se = LifeNode::next_value_from_neighbors(self.get_se().get_nw().is_alive(),
-                                                             vec![self.get_se().get_ne().is_alive(),
-                                                                  self.get_ne().get_se().is_alive(),
-                                                                  self.get_ne().get_sw().is_alive(),
-                                                                  self.get_nw().get_se().is_alive(),
-                                                                  self.get_sw().get_ne().is_alive(),
-                                                                  self.get_sw().get_se().is_alive(),
-                                                                  self.get_se().get_sw().is_alive(),
-                                                                  self.get_se().get_se().is_alive()],
-                                                             hashes);
-            return LifeNode::with_components(new_ne, new_nw, new_sw, new_se).do_arc(hashes)
+        assert!(step <= self.level-2);
+        let out = if self.level == 2 {
+            let mut grid = [[0u8; 4]; 4];
+            for y in 0..4 {
+                for x in 0..4 {
+                    grid[y][x] = self.get_value(x as isize - 2, y as isize - 2);
+                }
+            }
+            let new_ne = LifeNode::next_value_from_neighbors(&grid, 2, 2, rule, phase, hashes);
+            let new_nw = LifeNode::next_value_from_neighbors(&grid, 1, 2, rule, phase, hashes);
+            let new_sw = LifeNode::next_value_from_neighbors(&grid, 1, 1, rule, phase, hashes);
+            let new_se = LifeNode::next_value_from_neighbors(&grid, 2, 1, rule, phase, hashes);
+            LifeNode::with_components(new_ne, new_nw, new_sw, new_se).do_arc(hashes)
         } else {
-            let node_ce = LifeNode::vertical_forward(self.get_ne(), self.get_se(), hashes).advanced_center(hashes, memos);
-            let node_ne = self.get_ne().advanced_center(hashes, memos);
-            let node_nc = LifeNode::horizontal_forward(self.get_nw(), self.get_ne(), hashes).advanced_center(hashes, memos);
-            let node_nw = self.get_nw().advanced_center(hashes, memos);
-            let node_cw = LifeNode::vertical_forward(self.get_nw(), self.get_sw(), hashes).advanced_center(hashes, memos);
-            let node_sw = self.get_sw().advanced_center(hashes, memos);;
-            let node_sc = LifeNode::horizontal_forward(self.get_sw(), self.get_se(), hashes).advanced_center(hashes, memos);
-            let node_se = self.get_se().advanced_center(hashes, memos);
-            let node_cc = self.centered_forward(hashes).advanced_center(hashes, memos);
+            // The nine overlapping subnodes of half the side length, either advanced by half the
+            // generations (at full speed) or just cut down to their centers (at lower speeds)
+            let full_speed = step == self.level-2;
+            let sub_step = if full_speed { step-1 } else { step };
+            let mut nine = vec![self.get_ne(),
+                                LifeNode::horizontal_forward(self.get_nw(), self.get_ne(), hashes),
+                                self.get_nw(),
+                                LifeNode::vertical_forward(self.get_nw(), self.get_sw(), hashes),
+                                self.get_sw(),
+                                LifeNode::horizontal_forward(self.get_sw(), self.get_se(), hashes),
+                                self.get_se(),
+                                LifeNode::vertical_forward(self.get_ne(), self.get_se(), hashes),
+                                self.centered_forward(hashes)];
+            for node in nine.iter_mut() {
+                *node = if full_speed {
+                    node.advanced_center(sub_step, rule, phase, hashes, memos)
+                } else {
+                    node.centered_forward(hashes)
+                };
+            }
+            let (node_ne, node_nc, node_nw, node_cw, node_sw, node_sc, node_se, node_ce, node_cc) =
+                (nine[0].clone(), nine[1].clone(), nine[2].clone(), nine[3].clone(), nine[4].clone(),
+                 nine[5].clone(), nine[6].clone(), nine[7].clone(), nine[8].clone());
+            // After an odd number of generations a strobing rule switches phase
+            let phase = if full_speed && sub_step == 0 && rule.is_strobing() { 1-phase } else { phase };
             let new_ne = LifeNode::with_components(node_ne.clone(),
                                                    node_nc.clone(),
                                                    node_cc.clone(),
-                                                   node_ce.clone()).advanced_center(hashes, memos);
+                                                   node_ce.clone()).advanced_center(sub_step, rule, phase, hashes, memos);
             let new_nw = LifeNode::with_components(node_nc.clone(),
                                                    node_nw.clone(),
                                                    node_cw.clone(),
-                                                   node_cc.clone()).advanced_center(hashes, memos);
+                                                   node_cc.clone()).advanced_center(sub_step, rule, phase, hashes, memos);
             let new_sw = LifeNode::with_components(node_cc.clone(),
                                                    node_cw.clone(),
                                                    node_sw.clone(),
-                                                   node_sc.clone()).advanced_center(hashes, memos);
+                                                   node_sc.clone()).advanced_center(sub_step, rule, phase, hashes, memos);
             let new_se = LifeNode::with_components(node_ce.clone(),
                                                    node_cc.clone(),
                                                    node_sc.clone(),
-                                                   node_se.clone()).advanced_center(hashes, memos);
+                                                   node_se.clone()).advanced_center(sub_step, rule, phase, hashes, memos);
             LifeNode::with_components(new_ne, new_nw, new_sw, new_se).do_arc(hashes)
+        };
+        memos.insert(key, out.clone());
+        out
+    }
+}
+
+/// The rule applied to the leaves: either a Life-like rule, which may strobe between two phases, or
+/// a multi-state rule table.
+#[derive(Clone)]
+enum Transition {
+    Life(RulePhases),
+    Table(Arc<TableRule>),
+}
+
+impl Transition {
+    /// Returns the phase used to step from the given generation. Phases only depend on whether the
+    /// generation is odd.
+    fn index(&self, generation: &BigUint) -> usize {
+        match *self {
+            Transition::Life(ref phases) => phases.index(generation.is_odd() as u64),
+            Transition::Table(_)         => 0,
+        }
+    }
+
+    fn is_strobing(&self) -> bool {
+        match *self {
+            Transition::Life(ref phases) => phases.is_strobing(),
+            Transition::Table(_)         => false,
+        }
+    }
+
+    fn background(&self, generation: &BigUint) -> bool {
+        match *self {
+            Transition::Life(ref phases) => phases.background(generation.is_odd() as u64),
+            Transition::Table(_)         => false,
+        }
+    }
+
+    /// Returns the next state of a cell given its state and its neighbours' states in the order of
+    /// MOORE_NEIGHBOURS.
+    fn next_state(&self, phase: usize, center: u8, neighbors: &[u8; 8]) -> u8 {
+        match *self {
+            Transition::Life(ref phases) => {
+                let count = neighbors.iter().filter(|&&n| n != 0).count() as u8;
+                phases.phases()[phase].next_value(center != 0, count) as u8
+            },
+            Transition::Table(ref table) => table.next_state(center, neighbors),
         }
     }
 }
 
 pub struct Life {
-    generation: u64,
+    /// The generation, which can go far beyond a u64 by taking large steps
+    generation: BigUint,
+    rule: Transition,
     hashes: HashMap<LifeNode, Arc<LifeNode>>,
-    advanced_centers: HashMap<LifeNode, Arc<LifeNode>>,
+    advanced_centers: HashMap<(LifeNode, u64, usize), Arc<LifeNode>>,
     dead_squares: Vec<Arc<LifeNode>>,
     root: Arc<LifeNode>,
 }
@@ -443,13 +710,29 @@ pub struct Life {
 impl Life {
     /// Returns a new completely dead board. The root node will be level 3.
     pub fn new() -> Life {
+        Life::with_rule(Rule::conway()).unwrap()
+    }
+
+    /// Returns a new completely dead board simulating the given rule, or an error if the rule
+    /// cannot be simulated.
+    pub fn with_rule(rule: Rule) -> Result<Life, Error> {
+        Ok(Life::with_transition(Transition::Life(try!(RulePhases::new(rule)))))
+    }
+
+    /// Returns a new completely dead board simulating the given rule table.
+    pub fn with_table(table: Arc<TableRule>) -> Life {
+        Life::with_transition(Transition::Table(table))
+    }
+
+    fn with_transition(rule: Transition) -> Life {
         let mut hashes_temp: HashMap<LifeNode, Arc<LifeNode>> = HashMap::new();
-        let dead_cell = LifeNode::new(false).do_arc(&mut hashes_temp);
+        let dead_cell = LifeNode::new(0).do_arc(&mut hashes_temp);
         let root_temp = LifeNode::with_components(dead_cell.clone(),
                                                   dead_cell.clone(),
                                                   dead_cell.clone(),
                                                   dead_cell.clone()).do_arc(&mut hashes_temp);
-        let mut out = Life { generation: 0,
+        let mut out = Life { generation: BigUint::zero(),
+                             rule: rule,
                              hashes: hashes_temp.clone(),
                              advanced_centers: HashMap::new(),
                              dead_squares: vec![dead_cell, root_temp.clone()],
@@ -493,45 +776,165 @@ impl Life {
                                                padder.clone(),
                                                padder.clone()).do_arc(&mut self.hashes);
         let new_se = LifeNode::with_components(padder.clone(),
-                                               self.root.get_sw(),
+                                               self.root.get_se(),
                                                padder.clone(),
                                                padder.clone()).do_arc(&mut self.hashes);
         self.root = LifeNode::with_components(new_ne,
                                               new_nw,
                                               new_sw,
                                               new_se).do_arc(&mut self.hashes);
+        // LiveCells expects a dead node for every level below the root's
+        Life::canonical_dead(lvl as usize, &mut self.dead_squares, &mut self.hashes);
         self
     }
 
     /// Resizes the root node so that all live cells are contained in the center inner square of
     /// level root.level-2. Returns self.
     fn expand_to_fit(&mut self) -> &mut Life {
-        let dead_large = Life::canonical_dead((self.root.get_level()-2) as usize,
-                                              &mut self.dead_squares,
-                                              &mut self.hashes);
-        let dead_small = Life::canonical_dead((self.root.get_level()-3) as usize,
-                                              &mut self.dead_squares,
-                                              &mut self.hashes);
-        if !self.root.is_uniform_border(&self.hashes, dead_large) {
-            self.pad().pad();
-            self
-        } else if !LifeNode::with_components(self.root.get_ne().get_sw(),
-                                             self.root.get_nw().get_se(),
-                                             self.root.get_sw().get_ne(),
-                                             self.root.get_se().get_nw()).is_uniform_border(&self.hashes,
-                                                                                            dead_small) {
+        loop {
+            // Advancing leaves the root a level smaller, which may be too small to look inside
+            if self.root.get_level() < 3 {
+                self.pad();
+            } else if !self.root.has_empty_border() {
+                self.pad().pad();
+            } else if !LifeNode::with_components(self.root.get_ne().get_sw(),
+                                                 self.root.get_nw().get_se(),
+                                                 self.root.get_sw().get_ne(),
+                                                 self.root.get_se().get_nw()).has_empty_border() {
+                self.pad();
+            } else {
+                return self
+            }
+        }
+    }
+
+    /// Advances the board by 2^step generations.
+    fn advance_pow2(&mut self, step: u64) -> &mut Life {
+        self.expand_to_fit();
+        while self.root.get_level() < step+3 {
             self.pad();
-            self
-        } else {
-            self
         }
+        let phase = self.rule.index(&self.generation);
+        self.root = self.root.advanced_center(step, &self.rule, phase,
+                                              &mut self.hashes, &mut self.advanced_centers);
+        self.generation = &self.generation + (BigUint::one() << step as usize);
+        self
     }
 
     /// Advances the board by 2^(self.root.level-2) generations.
     pub fn advance_arbitrary(&mut self) -> &mut Life {
-        self.generation += (2 as u64) << ((self.root.get_level()-2) as u64);
-        self.root = self.root.advanced_center(&mut self.hashes, &mut self.advanced_centers);
-        self.expand_to_fit()
+        let step = self.root.get_level()-2;
+        self.advance_pow2(step)
+    }
+
+    /// Returns the state at the given coordinates, which may be too large for an isize.
+    pub fn get_state_at(&self, x: &BigInt, y: &BigInt) -> u8 {
+        let stored = if self.root.is_inside_big(x, y) { self.root.get_value_big(x, y) } else { 0 };
+        if self.background() { 1-stored } else { stored }
+    }
+
+    /// Sets the state at the given coordinates, which may be too large for an isize. Life-like
+    /// rules treat every non-zero state as alive.
+    pub fn set_state_at(&mut self, x: &BigInt, y: &BigInt, state: u8) {
+        let state = self.stored_state(state);
+        while !self.root.is_inside_big(x, y) {
+            self.pad();
+        }
+        self.root = self.root.change_value_big(x, y, state, &mut self.hashes);
+    }
+
+    /// Returns the smallest bounds containing all live cells, or None if there are none, however
+    /// far the pattern has grown.
+    pub fn big_bounds(&self) -> Option<BigBounds> {
+        self.root.big_bounds(&mut HashMap::new())
+    }
+
+    // Returns the state to store for a cell, which is flipped while the background of a strobing
+    // rule is alive
+    fn stored_state(&self, state: u8) -> u8 {
+        match self.rule {
+            Transition::Life(_)  => ((state != 0) != self.background()) as u8,
+            Transition::Table(_) => state,
+        }
+    }
+
+    // Returns the nodes that cells with isize coordinates are found in, with their centers
+    fn small_nodes(&self) -> Vec<(&LifeNode, isize, isize)> {
+        if self.root.get_level() <= max_small_level() {
+            return vec![(&*self.root, 0, 0)]
+        }
+        let mut out = vec![];
+        self.root.small_nodes(BigInt::zero(), BigInt::zero(), &mut out);
+        out
+    }
+
+    /// Returns the tree as a Macrocell file (see patterns.rs), which keeps the stored cells as they
+    /// are, so that a strobing rule reads them back against the same background.
+    pub fn write_macrocell(&self) -> String {
+        let squares = match self.rule {
+            Transition::Life(_)  => true,
+            Transition::Table(_) => false,
+        };
+        let mut lines = vec![];
+        self.root.write_macrocell(squares, &mut HashMap::new(), &mut lines);
+        if lines.is_empty() {
+            lines.push(if squares { "$" } else { "1 0 0 0 0" }.to_string());
+        }
+        format!("[M2] (Rusty Game of Life)\n#G {}\n{}\n", self.generation, lines.join("\n"))
+    }
+
+    /// Replaces the tree with the nodes of a Macrocell file, keeping the generation.
+    pub fn read_macrocell(&mut self, text: &str) -> Result<(), Error> {
+        let nodes = try!(parse_macrocell(text, self.num_states()));
+        let mut built: Vec<Arc<LifeNode>> = vec![];
+        for node in &nodes {
+            // Macrocell quadrants are in the order nw, ne, sw, se going down the screen, which are
+            // our sw, se, nw and ne
+            let next = match *node {
+                MacrocellNode::Square(ref rows) => {
+                    let mut out = Life::canonical_dead(3, &mut self.dead_squares, &mut self.hashes);
+                    for (y, row) in rows.iter().enumerate() {
+                        for x in 0..8 {
+                            if row & (1 << x) != 0 {
+                                out = out.change_value(x - 4, y as isize - 4, 1, &mut self.hashes);
+                            }
+                        }
+                    }
+                    out
+                },
+                MacrocellNode::States(ref states) => {
+                    let mut leaves = vec![];
+                    for &state in states {
+                        let state = match self.rule {
+                            Transition::Life(_)  => (state != 0) as u8,
+                            Transition::Table(_) => state,
+                        };
+                        leaves.push(LifeNode::new(state).do_arc(&mut self.hashes));
+                    }
+                    LifeNode::with_components(leaves[3].clone(), leaves[2].clone(),
+                                              leaves[0].clone(), leaves[1].clone()).do_arc(&mut self.hashes)
+                },
+                MacrocellNode::Node(level, ref quadrants) => {
+                    let mut q = vec![];
+                    for &n in quadrants {
+                        q.push(if n == 0 {
+                            Life::canonical_dead((level-1) as usize, &mut self.dead_squares, &mut self.hashes)
+                        } else {
+                            built[n-1].clone()
+                        });
+                    }
+                    LifeNode::with_components(q[3].clone(), q[2].clone(), q[0].clone(), q[1].clone()).do_arc(&mut self.hashes)
+                },
+            };
+            built.push(next);
+        }
+        self.root = built.pop().unwrap();
+        while self.root.get_level() < 3 {
+            self.pad();
+        }
+        // LiveCells expects a dead node for every level below the root's
+        Life::canonical_dead(self.root.get_level() as usize, &mut self.dead_squares, &mut self.hashes);
+        Ok(())
     }
 
     /// Toggles the value of the specified cell.
@@ -542,80 +945,203 @@ impl Life {
 
 }
 
-impl LifeAlgorithm<hash_set::IntoIter<(isize, isize)>> for Life {
-    /// Returns the current generation.
+/// Walks the live cells of a tree without copying them, skipping quadrants that are entirely dead
+/// and, if there is a region, quadrants that lie outside it.
+struct LiveCells<'a> {
+    /// Nodes still to visit, with the coordinates of their centers
+    stack: Vec<(&'a LifeNode, isize, isize)>,
+    dead_squares: &'a Vec<Arc<LifeNode>>,
+    region: Option<Bounds>,
+}
+
+impl<'a> LiveCells<'a> {
+    fn new(life: &'a Life, region: Option<Bounds>) -> LiveCells<'a> {
+        let mut out = LiveCells { stack: vec![], dead_squares: &life.dead_squares, region: region };
+        for (node, x, y) in life.small_nodes() {
+            out.visit(node, x, y);
+        }
+        out
+    }
+
+    fn visit(&mut self, node: &'a LifeNode, x: isize, y: isize) {
+        if let Some(ref region) = self.region {
+            if !node.overlaps(x, y, region) {
+                return
+            }
+        }
+        self.stack.push((node, x, y));
+    }
+}
+
+impl<'a> Iterator for LiveCells<'a> {
+    type Item = (isize, isize);
+
+    fn next(&mut self) -> Option<(isize, isize)> {
+        while let Some((node, x, y)) = self.stack.pop() {
+            match node.info {
+                Leaf(state) => if state != 0 { return Some((x,y)) },
+                Split(ref ne, ref nw, ref sw, ref se) => {
+                    let dead_squares = self.dead_squares;
+                    let dead = &dead_squares[(node.level-1) as usize];
+                    let (e, w) = node.quadrant_offsets();
+                    // Pushed in reverse so that the northeast quadrant is visited first
+                    for &(quadrant, qx, qy) in &[(se, x+e, y+w), (sw, x+w, y+w), (nw, x+w, y+e), (ne, x+e, y+e)] {
+                        if quadrant != dead {
+                            self.visit(quadrant, qx, qy);
+                        }
+                    }
+                },
+            }
+        }
+        None
+    }
+}
+
+impl LifeAlgorithm for Life {
+    /// Returns the current generation, or u64::MAX if it has gone past that.
     fn get_generation(&self) -> u64 {
-        self.generation
+        self.generation.to_u64().unwrap_or(u64::max_value())
+    }
+
+    /// Sets the current generation. The memoized results stay valid, since they are keyed on the
+    /// phase of the rule.
+    fn set_generation(&mut self, generation: u64) {
+        self.generation = BigUint::from(generation);
+    }
+
+    fn get_big_generation(&self) -> BigUint {
+        self.generation.clone()
+    }
+
+    fn set_big_generation(&mut self, generation: &BigUint) {
+        self.generation = generation.clone();
     }
 
-    /// Returns a bounds object containing all live cells.
+    /// Returns the smallest bounds object containing all live cells, which is cached in the root
+    /// unless the pattern has grown past max_small_level. Then the bounds are clamped to isizes.
     fn get_bounds(&self) -> Bounds {
-        Bounds::from_half_side(self.root.side_len()/2)
+        if self.root.get_level() <= max_small_level() {
+            self.root.bounds.clone().unwrap_or(Bounds::new())
+        } else {
+            self.big_bounds().map_or(Bounds::new(), |b| b.to_bounds())
+        }
     }
 
     /// Returns the alive/dead value at the given coordinates. The southwest corner of the
     /// northeast quadrant is assumed to be (0,0).
-    fn get_value(&self, (x,y): (isize, isize)) -> bool {
-        let bound: isize = (2 as isize) << ((self.root.get_level()-1) as isize);
-        if x < -bound || y < -bound || x >= bound || y >= bound {
-            false
-        } else {
-            self.root.get_value(x,y)
-        }
+    fn get_value(&self, cell: (isize, isize)) -> bool {
+        self.get_state(cell) != 0
     }
 
     /// Sets the specified cell to the specified value.
-    fn set(&mut self, (x,y): (isize, isize), val: bool) {
-        let mut bound = self.root.side_len();
-        while x < -bound || y < -bound || x >= bound || y >= bound {
+    fn set(&mut self, cell: (isize, isize), val: bool) {
+        self.set_state(cell, val as u8);
+    }
+
+    /// Returns the state at the given coordinates.
+    fn get_state(&self, (x,y): (isize, isize)) -> u8 {
+        if self.root.get_level() > max_small_level() {
+            return self.get_state_at(&BigInt::from(x as i64), &BigInt::from(y as i64))
+        }
+        let stored = if self.root.is_inside(x,y) { self.root.get_value(x,y) } else { 0 };
+        if self.background() { 1-stored } else { stored }
+    }
+
+    /// Sets the specified cell to the specified state. Life-like rules treat every non-zero state
+    /// as alive.
+    fn set_state(&mut self, (x,y): (isize, isize), state: u8) {
+        while self.root.get_level() <= max_small_level() && !self.root.is_inside(x,y) {
             self.pad();
-            bound = self.root.side_len();
         }
-        self.root = self.root.change_value(x, y, val, &mut self.hashes);
+        if self.root.get_level() > max_small_level() {
+            return self.set_state_at(&BigInt::from(x as i64), &BigInt::from(y as i64), state)
+        }
+        let state = self.stored_state(state);
+        self.root = self.root.change_value(x, y, state, &mut self.hashes);
     }
 
-    /// Advances the game by the specified number of generations.
     fn advance_by(&mut self, time: u64) {
-        let mut time = time;
-        while time > 0 {
-            let amt = (self.root.side_len()/2) as u64; // the starting side length of the live region of root
-            if time >= amt {
-                self.generation += amt;
-                self.root = self.root.advanced_center(&mut self.hashes, &mut self.advanced_centers);
-                self.expand_to_fit();
-                time -= amt
-            } else {
-                let ex = 63u64 - time.leading_zeros() as u64;
-                let bin = 2u64 << ex; // number of generations we advance by in this iteration of the loop
-                let side = 2*bin; // the side length of the squares we will recompose root from
-                let num = (amt/side+1) as isize; // the number of squares on each side needed to tile root in these
-                let mut values: Vec<Vec<Arc<LifeNode>>> = vec![];
-                for i in -num/2..num/2 {
-                    values.push(vec![]);
-                    for j in -num/2..num/2 {
-                        values[(i+num/2) as usize].push(LifeNode::with_components(self.root.get_chunk(ex+1, i  , j  , &mut self.hashes),
-                                                                                  self.root.get_chunk(ex+1, i-1, j  , &mut self.hashes),
-                                                                                  self.root.get_chunk(ex+1, i-1, j-1, &mut self.hashes),
-                                                                                  self.root.get_chunk(ex+1, i  , j-1, &mut self.hashes)).advanced_center(&mut self.hashes, &mut self.advanced_centers));
-                    }
-                }
-                self.root = LifeNode::from_grid(values, &mut self.hashes, &self.dead_squares).do_arc(&mut self.hashes);
-                self.generation += bin;
-                self.expand_to_fit();
-                time -= bin;
+        self.advance_by_big(&BigUint::from(time));
+    }
+
+    /// Advances the game by the specified number of generations, one power of two at a time.
+    fn advance_by_big(&mut self, time: &BigUint) {
+        let mut time = time.clone();
+        let mut step = 0;
+        while !time.is_zero() {
+            if time.is_odd() {
+                self.advance_pow2(step);
             }
+            time = time >> 1;
+            step += 1;
         }
     }
 
-    fn live_cells(&self) -> hash_set::IntoIter<(isize, isize)> {
-        let mut cells: HashSet<(isize, isize)> = HashSet::new();
-        self.root.find_all_live(&mut cells, 0isize, 0isize, &self.dead_squares);
-        cells.into_iter()
+    /// Only finds cells with isize coordinates. Cells further out can be read with get_state_at.
+    fn live_cells<'a>(&'a self) -> CellIter<'a> {
+        Box::new(LiveCells::new(self, None))
+    }
+
+    /// Walks only the quadrants that overlap the region.
+    fn live_cells_in<'a>(&'a self, region: &Bounds) -> CellIter<'a> {
+        Box::new(LiveCells::new(self, Some(region.clone())))
+    }
+
+    fn background(&self) -> bool {
+        self.rule.background(&self.generation)
+    }
+
+    fn num_states(&self) -> u8 {
+        match self.rule {
+            Transition::Life(_)         => 2,
+            Transition::Table(ref rule) => rule.num_states(),
+        }
+    }
+
+    fn to_macrocell(&self) -> Option<String> {
+        Some(self.write_macrocell())
+    }
+
+    fn from_macrocell(&mut self, text: &str) -> Option<Result<(), Error>> {
+        Some(self.read_macrocell(text))
+    }
+
+    /// Keeps the root of the tree, which nothing ever changes, so checkpoints cost next to nothing.
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint { generation: self.generation.clone(), cells: vec![], state: Some(Box::new(self.root.clone())) }
+    }
+
+    fn restore(&mut self, checkpoint: &Checkpoint) {
+        match checkpoint.state.as_ref().and_then(|state| state.downcast_ref::<Arc<LifeNode>>()) {
+            Some(root) => {
+                self.root = root.clone();
+                self.generation = checkpoint.generation.clone();
+            },
+            // Made by another algorithm, so the cells were copied
+            None => {
+                self.clear();
+                for &((x,y), state) in &checkpoint.cells {
+                    self.set_state((x,y), state);
+                }
+                self.generation = checkpoint.generation.clone();
+            },
+        }
+    }
+
+    /// Reads the population cached in the root, so it takes constant time.
+    fn population(&self) -> usize {
+        min(self.root.get_population(), usize::MAX as u128) as usize
+    }
+
+    fn population_in(&self, region: &Bounds) -> usize {
+        let population = self.small_nodes().iter()
+                             .fold(0u128, |acc, &(node, x, y)| acc.saturating_add(node.population_in(x, y, region)));
+        min(population, usize::MAX as u128) as usize
     }
 
     fn clear(&mut self) {
         self.root = Life::canonical_dead(3, &mut self.dead_squares, &mut self.hashes);
-        self.generation = 0;
+        self.generation = BigUint::zero();
     }
 
     fn clean_up(&mut self){
@@ -623,3 +1149,44 @@ impl LifeAlgorithm<hash_set::IntoIter<(isize, isize)>> for Life {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glider_runs_for_2_to_the_100_generations() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let mut life_obj = Life::new();
+        for &cell in &glider {
+            life_obj.set(cell, true);
+        }
+        life_obj.clean_up();
+        let generations = BigUint::one() << 100;
+        life_obj.advance_by_big(&generations);
+        assert_eq!(life_obj.get_big_generation(), generations);
+        // A glider moves one cell down and to the right every 4 generations
+        let shift = BigInt::one() << 98;
+        for y in -1..4 {
+            for x in -1..4 {
+                let state = life_obj.get_state_at(&(&shift + BigInt::from(x)), &(&shift + BigInt::from(y)));
+                assert_eq!(state, glider.contains(&(x, y)) as u8, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn cells_past_isize_can_be_set_and_read() {
+        let far = BigInt::one() << 100;
+        let mut life_obj = Life::new();
+        life_obj.set_state_at(&far, &-far.clone(), 1);
+        life_obj.set((0, 0), true);
+        assert_eq!(life_obj.get_state_at(&far, &-far.clone()), 1);
+        assert_eq!(life_obj.get_state_at(&-far.clone(), &far), 0);
+        assert!(life_obj.get_value((0, 0)));
+        // Both cells are alone, so they die
+        life_obj.advance_by(1);
+        assert_eq!(life_obj.get_state_at(&far, &-far.clone()), 0);
+        assert!(!life_obj.get_value((0, 0)));
+    }
+}
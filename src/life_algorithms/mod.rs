@@ -1,6 +1,139 @@
-// This just exports all the game-of-life algorithms so they're 
-// all conveniently under one namespace
+// This just exports all the game-of-life algorithms so they're
+// all conveniently under one namespace, and keeps the registry of
+// them that the command line, GUI and benchmarks pick from.
+//
+// To add an algorithm, write a module that implements LifeAlgorithm
+// and defines an ALGORITHM entry describing it (see sequential.rs),
+// then add the module below and its entry to ALGORITHMS.
 
-pub mod sequential; // A simple sequential version 
+use std::sync::Arc;
+
+use common::{LifeAlgorithm,transfer};
+use error::Error;
+use rules::Rule;
+use rule_table::TableRule;
+
+pub mod sequential; // A simple sequential version
 pub mod parallel; // A parallel version that takes runs advance and cleanup across many threads
 pub mod hashlife; // An implementation of the hashlife algorithm for memoizing common patterns
+pub mod table; // A sparse version of sequential for multi-state rule tables such as Wireworld
+
+/// Every registered algorithm, in the order they're listed in
+pub static ALGORITHMS: [&'static Algorithm; 4] = [
+    &sequential::ALGORITHM,
+    &parallel::ALGORITHM,
+    &hashlife::ALGORITHM,
+    &table::ALGORITHM,
+];
+
+/// A life object of any registered algorithm
+pub type BoxedLife = Box<LifeAlgorithm>;
+
+/// The rule to simulate: a Life-like rule, or a multi-state rule table
+#[derive(Clone)]
+pub enum RuleKind {
+    Life(Rule),
+    Table(Arc<TableRule>),
+}
+
+impl RuleKind {
+    pub fn is_table(&self) -> bool {
+        match *self {
+            RuleKind::Life(_)  => false,
+            RuleKind::Table(_) => true,
+        }
+    }
+}
+
+/// Everything an algorithm may need to create a life object
+#[derive(Clone)]
+pub struct Settings {
+    pub rule: RuleKind,
+    /// The number of threads for algorithms that use several. None picks a default
+    pub threads: Option<usize>,
+}
+
+impl Settings {
+    pub fn new(rule: RuleKind) -> Settings {
+        Settings { rule: rule, threads: None }
+    }
+}
+
+/// What an algorithm can do
+#[derive(Clone, Copy, Debug)]
+pub struct Capabilities {
+    /// Runs Life-like rules given in B/S notation, including B3/S23 itself
+    pub custom_rules: bool,
+    /// Runs multi-state rule tables
+    pub rule_tables: bool,
+    /// Runs on grids other than the infinite plane
+    pub topologies: bool,
+    /// Advances many generations at once much faster than one at a time
+    pub large_steps: bool,
+    /// Can track the age and recent changes of every cell for the GUI to color them by
+    pub cell_tracking: bool,
+}
+
+/// A registered algorithm
+pub struct Algorithm {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub capabilities: Capabilities,
+    /// Creates an empty life object. Only called with rules the capabilities allow
+    pub constructor: fn(&Settings) -> Result<BoxedLife, Error>,
+}
+
+impl Algorithm {
+    /// Returns whether the algorithm can run the given rule
+    pub fn supports(&self, rule: &RuleKind) -> bool {
+        match *rule {
+            RuleKind::Life(_)  => self.capabilities.custom_rules,
+            RuleKind::Table(_) => self.capabilities.rule_tables,
+        }
+    }
+
+    /// Creates an empty life object running the given rule
+    pub fn create(&self, settings: &Settings) -> Result<BoxedLife, Error> {
+        if !self.supports(&settings.rule) {
+            let needs = if settings.rule.is_table() { "Rule tables need" } else { "B/S rules need" };
+            let able: Vec<&str> = ALGORITHMS.iter().filter(|a| a.supports(&settings.rule)).map(|a| a.name).collect();
+            return Err(Error::Argument(format!("The {} algorithm cannot run this rule. {} one of: {}.", self.name, needs, able.join(", "))))
+        }
+        (self.constructor)(settings)
+    }
+
+    /// Creates a life object running the given rule, holding the same generation and cells as
+    /// another life object. Used to switch algorithms part way through a run
+    pub fn create_from<L: LifeAlgorithm + ?Sized>(&self, settings: &Settings, life_obj: &L) -> Result<BoxedLife, Error> {
+        let mut out = try!(self.create(settings));
+        transfer(life_obj, &mut out);
+        Ok(out)
+    }
+
+    /// Returns a short list of what the algorithm supports, for help text
+    pub fn capability_names(&self) -> Vec<&'static str> {
+        let caps = &self.capabilities;
+        let mut names = vec![];
+        if caps.custom_rules { names.push("custom rules"); }
+        if caps.rule_tables { names.push("rule tables"); }
+        if caps.topologies { names.push("topologies"); }
+        if caps.large_steps { names.push("fast large steps"); }
+        if caps.cell_tracking { names.push("cell tracking"); }
+        names
+    }
+}
+
+/// Looks up a registered algorithm by name
+pub fn find(name: &str) -> Result<&'static Algorithm, Error> {
+    match ALGORITHMS.iter().find(|a| a.name == name) {
+        Some(algorithm) => Ok(*algorithm),
+        None => Err(Error::Argument(format!("{:?} is not a recognized algorithm. Run with --help for a list of algorithms.", name))),
+    }
+}
+
+/// Picks the algorithm that runs a rule fastest over many generations: the first that takes large
+/// steps, or else the first that can run it at all
+pub fn fastest(rule: &RuleKind) -> Option<&'static Algorithm> {
+    let able: Vec<&'static Algorithm> = ALGORITHMS.iter().cloned().filter(|a| a.supports(rule)).collect();
+    able.iter().cloned().find(|a| a.capabilities.large_steps).or(able.first().cloned())
+}
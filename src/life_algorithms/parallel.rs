@@ -2,24 +2,34 @@ extern crate num_cpus;
 extern crate rand;
 
 
-use std::cmp::{min,max};
 use std::sync::Arc;
 use std::collections::HashSet;
-use std::collections::hash_set;
 use std::collections::HashMap;
 use std::thread;
 use std;
 
-use common::{LifeAlgorithm,Bounds};
+use common::{LifeAlgorithm,Bounds,CellIter};
+use error::Error;
+use rules::{Rule,RulePhases};
+use tracking::CellTracker;
+use super::{Algorithm,BoxedLife,Capabilities,RuleKind,Settings};
 
-// Extra functionality for bounds 
-impl Bounds {
-    fn merge(&mut self, other:Bounds) {
-        self.x_min = min(self.x_min, other.x_min);
-        self.x_max = max(self.x_max, other.x_max);
-        self.y_min = min(self.y_min, other.y_min);
-        self.y_max = max(self.y_max, other.y_max);
+pub static ALGORITHM: Algorithm = Algorithm {
+    name: "parallel",
+    description: "The sparse algorithm split across many threads",
+    capabilities: Capabilities { custom_rules: true, rule_tables: false, topologies: false, large_steps: false, cell_tracking: true },
+    constructor: create,
+};
+
+fn create(settings: &Settings) -> Result<BoxedLife, Error> {
+    let mut life = match settings.rule {
+        RuleKind::Life(rule) => try!(Life::with_rule(rule)),
+        RuleKind::Table(_)   => unreachable!(),
+    };
+    if let Some(n) = settings.threads {
+        life.set_num_threads(n);
     }
+    Ok(Box::new(life))
 }
 
 #[derive(Clone)]
@@ -29,30 +39,46 @@ pub struct Life {
     parts: Vec<Arc<HashSet<(isize, isize)>>>,
     rect: Bounds,
     num_threads:usize,
+    rule: RulePhases,
+    // The number of true values in cells, kept up to date as they change
+    population: usize,
+    tracker: Option<CellTracker>,
 }
 
 impl Life {
     pub fn new() -> Life {
+        Life::with_rule(Rule::conway()).unwrap()
+    }
+
+    pub fn with_rule(rule: Rule) -> Result<Life, Error> {
         let num_threads = num_cpus::get() * 2; //Use twice as many threads as we have cores
-        Life { generation: 0, cells: Arc::new(HashMap::new()), parts: vec![Arc::new(HashSet::new()); num_threads], rect: Bounds::new(), num_threads:num_threads }
+        Ok(Life { generation: 0, cells: Arc::new(HashMap::new()), parts: vec![Arc::new(HashSet::new()); num_threads], rect: Bounds::new(), num_threads:num_threads, rule: try!(RulePhases::new(rule)), population: 0, tracker: None })
+    }
+
+    /// Sets how many threads each step is split across, spreading the cells already in the grid
+    /// over the new threads. If num_threads is 0, then the thread panics.
+    pub fn set_num_threads(&mut self, num_threads: usize) {
+        assert!(num_threads > 0);
+        let mut parts = vec![HashSet::new(); num_threads];
+        for (i, &cell) in self.parts.iter().flat_map(|p| p.iter()).enumerate() {
+            parts[i % num_threads].insert(cell);
+        }
+        self.parts = parts.into_iter().map(Arc::new).collect();
+        self.num_threads = num_threads;
     }
 
     fn cells_access_record(s:&str) {
         println!("Arc::get_mut(&mut self.cells) returned None at {}", s);
     }
 
-    fn next_val_from_arc(cells_ref:&Arc<HashMap<(isize, isize), bool>>, x:isize, y:isize) -> bool {
+    fn next_val_from_arc(cells_ref:&Arc<HashMap<(isize, isize), bool>>, rule:&Rule, x:isize, y:isize) -> bool {
         let mut neighbors: i8 = 0;
         for (i,j) in Life::get_adjacent(x,y) {
             if cells_ref.contains_key(&(i,j)) {
                 if cells_ref[&(i,j)] == true { neighbors+=1; }
             }
         }
-        if (neighbors == 3) | ((neighbors == 2) & (cells_ref[&(x,y)] == true)) {
-            true
-        } else {
-            false
-        }
+        rule.next_value(cells_ref[&(x,y)], neighbors as u8)
     }
 
     fn get_adjacent(x:isize, y:isize) -> Vec<(isize, isize)> {
@@ -67,17 +93,18 @@ impl Life {
     }
 }
 
-impl LifeAlgorithm<hash_set::IntoIter<(isize, isize)>> for Life {
+impl LifeAlgorithm for Life {
     fn advance_by(&mut self, count:u64){
         for _ in 0..count {
             let mut thread_handles = vec![];
             for k in 0..self.num_threads {
                 let my_cells = self.cells.clone();
                 let my_part = self.parts[k].clone();
+                let my_rule = *self.rule.at(self.generation);
                 thread_handles.push(thread::spawn(move || {
                     let mut cells_new = HashMap::new();
                     for &(x,y) in my_part.iter() {
-                        cells_new.insert((x,y), Life::next_val_from_arc(&my_cells,x,y));
+                        cells_new.insert((x,y), Life::next_val_from_arc(&my_cells,&my_rule,x,y));
                     }
                     cells_new
                 }));
@@ -86,6 +113,8 @@ impl LifeAlgorithm<hash_set::IntoIter<(isize, isize)>> for Life {
             for hand in thread_handles {
                 cells_new.push(hand.join().unwrap());
             }
+            // Every cell is in exactly one part, so the new values cover the whole map
+            self.population = cells_new.iter().map(|part| part.values().filter(|&&v| v).count()).sum();
             for k in 0..self.num_threads {
                 for (&(x,y),v) in &cells_new[k] {
                     if let Some(re) = Arc::get_mut(&mut self.cells) {
@@ -99,11 +128,18 @@ impl LifeAlgorithm<hash_set::IntoIter<(isize, isize)>> for Life {
             }
             self.clean_up();
             self.generation += 1;
+            if let Some(ref mut tracker) = self.tracker {
+                tracker.update(self.cells.iter().filter(|&(_, value)| *value).map(|(&key, _)| (key, 1)));
+            }
         }
         
     }
 
     fn set(&mut self, (x, y): (isize, isize), v: bool){
+        let v = v != self.background();
+        let was_alive = self.cells.get(&(x,y)) == Some(&true);
+        if v && !was_alive { self.population += 1; }
+        if !v && was_alive { self.population -= 1; }
         if !self.cells.contains_key(&(x,y)) {
             if let Some(re) = Arc::get_mut(&mut self.cells) {
                 (*re).insert((x,y), v);
@@ -174,7 +210,7 @@ impl LifeAlgorithm<hash_set::IntoIter<(isize, isize)>> for Life {
         let mut to_dels: Vec<Vec<(isize, isize)>> = vec![];
         for hand in thread_handles {
             let ret = hand.join().unwrap();
-            self.rect.merge(ret.0);
+            self.rect.merge(&ret.0);
             to_adds.push(ret.1);
             to_dels.push(ret.2);
         }
@@ -216,33 +252,60 @@ impl LifeAlgorithm<hash_set::IntoIter<(isize, isize)>> for Life {
         self.generation
     }
 
+    fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
+
     fn get_bounds(&self) -> Bounds {
         self.rect.clone()
     }
 
     fn get_value(&self, cell: (isize, isize)) -> bool {
         if let Some(v) = self.cells.get(&cell) {
-            *v
+            *v != self.background()
         } else {
-            false
+            self.background()
         }
     }
 
+    fn background(&self) -> bool {
+        self.rule.background(self.generation)
+    }
+
     fn clear(&mut self) {
         if let Some(re) = Arc::get_mut(&mut self.cells) {
             (*re).drain();
         } else {
             Life::cells_access_record("Life::clear");
         }
+        for part in &mut self.parts {
+            (*Arc::make_mut(part)).clear();
+        }
+        self.population = 0;
+        if let Some(ref mut tracker) = self.tracker {
+            tracker.clear();
+        }
     }
 
-    fn live_cells(&self) -> hash_set::IntoIter<(isize, isize)> {
-        let mut out: HashSet<(isize, isize)> = HashSet::new();
-        for (key, value) in self.cells.iter() {
-            if *value {
-                out.insert(*key);
-            }
+    fn population(&self) -> usize {
+        self.population
+    }
+
+    fn live_cells<'a>(&'a self) -> CellIter<'a> {
+        Box::new(self.cells.iter().filter(|&(_, value)| *value).map(|(&key, _)| key))
+    }
+
+    /// Tracks cells unless the rule has B0, which makes the whole background flicker.
+    fn set_tracking(&mut self, on: bool) -> bool {
+        if on && self.rule.is_strobing() {
+            return false
         }
-        out.into_iter()
+        let tracker = if on { Some(CellTracker::start(self.live_cells().map(|cell| (cell, 1)))) } else { None };
+        self.tracker = tracker;
+        true
+    }
+
+    fn tracker(&self) -> Option<&CellTracker> {
+        self.tracker.as_ref()
     }
 }
@@ -1,19 +1,42 @@
-use std::collections::HashSet;
-use std::collections::hash_set;
 use std::collections::HashMap;
 use std;
 
-use common::{LifeAlgorithm,Bounds};
+use common::{LifeAlgorithm,Bounds,CellIter};
+use error::Error;
+use rules::{Rule,RulePhases};
+use tracking::CellTracker;
+use super::{Algorithm,BoxedLife,Capabilities,RuleKind,Settings};
+
+pub static ALGORITHM: Algorithm = Algorithm {
+    name: "sequential",
+    description: "A simple sparse algorithm that updates one cell at a time",
+    capabilities: Capabilities { custom_rules: true, rule_tables: false, topologies: false, large_steps: false, cell_tracking: true },
+    constructor: create,
+};
+
+fn create(settings: &Settings) -> Result<BoxedLife, Error> {
+    match settings.rule {
+        RuleKind::Life(rule) => Ok(Box::new(try!(Life::with_rule(rule)))),
+        RuleKind::Table(_)   => unreachable!(),
+    }
+}
 
 pub struct Life {
     pub generation: u64,
     pub cells: HashMap<(isize, isize), bool>,
     rect: Bounds,
+    rule: RulePhases,
+    // The number of true values in cells, kept up to date as they change
+    population: usize,
+    tracker: Option<CellTracker>,
 }
 
 impl Life {
     pub fn new() -> Life {
-        Life { generation: 0,cells: HashMap::new(), rect: Bounds::new()}
+        Life::with_rule(Rule::conway()).unwrap()
+    }
+    pub fn with_rule(rule: Rule) -> Result<Life, Error> {
+        Ok(Life { generation: 0,cells: HashMap::new(), rect: Bounds::new(), rule: try!(RulePhases::new(rule)), population: 0, tracker: None })
     }
     fn next_val(&self, x:isize, y:isize) -> bool {
         let mut neighbors: i8 = 0;
@@ -22,11 +45,7 @@ impl Life {
                 if self.cells[&(i,j)] == true { neighbors+=1; }
             }
         }
-        if (neighbors == 3) | ((neighbors == 2) & (self.cells[&(x,y)] == true)) {
-            true
-        } else {
-            false
-        }
+        self.rule.at(self.generation).next_value(self.cells[&(x,y)], neighbors as u8)
     }
     fn get_adjacent(&self, x:isize, y:isize) -> Vec<(isize, isize)> {
         vec![(x+1, y  ),
@@ -40,16 +59,23 @@ impl Life {
     }
 }
 
-impl LifeAlgorithm<hash_set::IntoIter<(isize, isize)>> for Life {
+impl LifeAlgorithm for Life {
     fn advance_by(&mut self,count:u64){
         for _ in 0..count {
             let mut cells_new: HashMap<(isize, isize), bool> = HashMap::new();
+            let mut population = 0;
             for &(x,y) in self.cells.keys() {
-               cells_new.insert((x,y), self.next_val(x,y));
+               let value = self.next_val(x,y);
+               if value { population += 1; }
+               cells_new.insert((x,y), value);
             }
             self.cells = cells_new;
+            self.population = population;
             self.clean_up();
             self.generation += 1;
+            if let Some(ref mut tracker) = self.tracker {
+                tracker.update(self.cells.iter().filter(|&(_, &value)| value).map(|(&key, _)| (key, 1)));
+            }
         }
         
     }
@@ -57,6 +83,10 @@ impl LifeAlgorithm<hash_set::IntoIter<(isize, isize)>> for Life {
     fn set(&mut self,cell:(isize,isize), value: bool){
         let x = cell.0;
         let y = cell.1;
+        let value = value != self.background();
+        let was_alive = self.cells.get(&(x,y)) == Some(&true);
+        if value && !was_alive { self.population += 1; }
+        if !value && was_alive { self.population -= 1; }
         if !self.cells.contains_key(&(x,y)) {
             self.cells.insert((x,y), value);
         }
@@ -112,30 +142,54 @@ impl LifeAlgorithm<hash_set::IntoIter<(isize, isize)>> for Life {
         self.generation
     }
 
+    fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
+
     fn get_bounds(&self) -> Bounds {
         self.rect.clone()
     }
 
     fn get_value(&self, cell: (isize, isize)) -> bool {
         if let Some(v) = self.cells.get(&cell) {
-            *v
+            *v != self.background()
         } else {
-            false
+            self.background()
         }
     }
 
+    fn background(&self) -> bool {
+        self.rule.background(self.generation)
+    }
+
     fn clear(&mut self) {
         self.cells.drain();
+        self.population = 0;
+        if let Some(ref mut tracker) = self.tracker {
+            tracker.clear();
+        }
     }
 
-    fn live_cells(&self) -> hash_set::IntoIter<(isize, isize)> {
-        let mut out: HashSet<(isize, isize)> = HashSet::new();
-        for (&key, &value) in &self.cells {
-            if value {
-                out.insert(key);
-            }
+    fn population(&self) -> usize {
+        self.population
+    }
+
+    fn live_cells<'a>(&'a self) -> CellIter<'a> {
+        Box::new(self.cells.iter().filter(|&(_, &value)| value).map(|(&key, _)| key))
+    }
+
+    /// Tracks cells unless the rule has B0, which makes the whole background flicker.
+    fn set_tracking(&mut self, on: bool) -> bool {
+        if on && self.rule.is_strobing() {
+            return false
         }
-        out.into_iter()
+        let tracker = if on { Some(CellTracker::start(self.live_cells().map(|cell| (cell, 1)))) } else { None };
+        self.tracker = tracker;
+        true
+    }
+
+    fn tracker(&self) -> Option<&CellTracker> {
+        self.tracker.as_ref()
     }
 
 }
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std;
+
+use common::{LifeAlgorithm,Bounds,CellIter};
+use error::Error;
+use rule_table::{TableRule,MOORE_NEIGHBOURS};
+use tracking::CellTracker;
+use super::{Algorithm,BoxedLife,Capabilities,RuleKind,Settings};
+
+pub static ALGORITHM: Algorithm = Algorithm {
+    name: "table",
+    description: "A sparse algorithm for multi-state rule tables such as Wireworld",
+    capabilities: Capabilities { custom_rules: false, rule_tables: true, topologies: false, large_steps: false, cell_tracking: true },
+    constructor: create,
+};
+
+fn create(settings: &Settings) -> Result<BoxedLife, Error> {
+    match settings.rule {
+        RuleKind::Table(ref rule) => Ok(Box::new(Life::new(rule.clone()))),
+        RuleKind::Life(_)         => unreachable!(),
+    }
+}
+
+// A sparse engine for multi-state rule tables, along the lines of the sequential one. The map
+// holds every cell with a non-zero state along with its dead neighbours.
+pub struct Life {
+    pub generation: u64,
+    pub cells: HashMap<(isize, isize), u8>,
+    rect: Bounds,
+    rule: Arc<TableRule>,
+    // The number of non-zero states in cells, kept up to date as they change
+    population: usize,
+    tracker: Option<CellTracker>,
+}
+
+impl Life {
+    pub fn new(rule: Arc<TableRule>) -> Life {
+        Life { generation: 0, cells: HashMap::new(), rect: Bounds::new(), rule: rule, population: 0, tracker: None }
+    }
+    fn next_val(&self, x:isize, y:isize) -> u8 {
+        let mut neighbors = [0u8; 8];
+        for (n, (i,j)) in neighbors.iter_mut().zip(self.get_adjacent(x,y)) {
+            *n = self.get_state((i,j));
+        }
+        self.rule.next_state(self.cells[&(x,y)], &neighbors)
+    }
+    fn get_adjacent(&self, x:isize, y:isize) -> Vec<(isize, isize)> {
+        MOORE_NEIGHBOURS.iter().map(|&(dx,dy)| (x+dx, y+dy)).collect()
+    }
+}
+
+impl LifeAlgorithm for Life {
+    fn advance_by(&mut self,count:u64){
+        for _ in 0..count {
+            let mut cells_new: HashMap<(isize, isize), u8> = HashMap::new();
+            let mut population = 0;
+            for &(x,y) in self.cells.keys() {
+               let state = self.next_val(x,y);
+               if state != 0 { population += 1; }
+               cells_new.insert((x,y), state);
+            }
+            self.cells = cells_new;
+            self.population = population;
+            self.clean_up();
+            self.generation += 1;
+            if let Some(ref mut tracker) = self.tracker {
+                tracker.update(self.cells.iter().filter(|&(_, &state)| state != 0).map(|(&key, &state)| (key, state)));
+            }
+        }
+    }
+
+    fn set(&mut self, cell:(isize,isize), value: bool){
+        self.set_state(cell, value as u8);
+    }
+
+    fn set_state(&mut self, cell:(isize,isize), state: u8){
+        let was_alive = self.get_state(cell) != 0;
+        if state != 0 && !was_alive { self.population += 1; }
+        if state == 0 && was_alive { self.population -= 1; }
+        self.cells.insert(cell, state);
+    }
+
+    fn clean_up(&mut self){
+        self.rect.x_min = std::isize::MAX;
+        self.rect.x_max = std::isize::MIN;
+        self.rect.y_min = std::isize::MAX;
+        self.rect.y_max = std::isize::MIN;
+        let mut to_add: Vec<(isize, isize)> = vec![];
+        let mut to_del: Vec<(isize, isize)> = vec![];
+        for (&(x,y),&v) in &self.cells {
+            if v != 0 {
+                self.rect.update_bounds(x,y);
+                for (i,j) in self.get_adjacent(x,y) {
+                    if !self.cells.contains_key(&(i,j)) {
+                        to_add.push((i,j));
+                    }
+                }
+            } else {
+                let barren = self.get_adjacent(x,y).into_iter().all(|c| self.get_state(c) == 0);
+                if barren {
+                    to_del.push((x,y));
+                } else {
+                    self.rect.update_bounds(x,y);
+                }
+            }
+        }
+        for (x,y) in to_add {
+            self.cells.insert((x,y), 0);
+            self.rect.update_bounds(x,y);
+        }
+        for (x,y) in to_del {
+            self.cells.remove(&(x,y));
+        }
+    }
+
+    fn get_generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
+
+    fn get_bounds(&self) -> Bounds {
+        self.rect.clone()
+    }
+
+    fn get_value(&self, cell: (isize, isize)) -> bool {
+        self.get_state(cell) != 0
+    }
+
+    fn num_states(&self) -> u8 {
+        self.rule.num_states()
+    }
+
+    fn get_state(&self, cell: (isize, isize)) -> u8 {
+        if let Some(v) = self.cells.get(&cell) {
+            *v
+        } else {
+            0
+        }
+    }
+
+    fn clear(&mut self) {
+        self.cells.drain();
+        self.population = 0;
+        if let Some(ref mut tracker) = self.tracker {
+            tracker.clear();
+        }
+    }
+
+    fn population(&self) -> usize {
+        self.population
+    }
+
+    fn live_cells<'a>(&'a self) -> CellIter<'a> {
+        Box::new(self.cells.iter().filter(|&(_, &value)| value != 0).map(|(&key, _)| key))
+    }
+
+    fn set_tracking(&mut self, on: bool) -> bool {
+        let tracker = if on { Some(CellTracker::start(self.cells.iter().filter(|&(_, &state)| state != 0).map(|(&key, &state)| (key, state)))) } else { None };
+        self.tracker = tracker;
+        true
+    }
+
+    fn tracker(&self) -> Option<&CellTracker> {
+        self.tracker.as_ref()
+    }
+}
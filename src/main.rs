@@ -1,27 +1,95 @@
 extern crate piston_window;
 extern crate time;
 extern crate find_folder;
+extern crate num;
+extern crate rhai;
+extern crate varisat;
 
 use std::iter::Iterator;
 use std::env;
-use std::io::prelude::*;
-use std::io::BufReader;
-use std::fs::File;
+use std::io::{self,Write};
+use std::process;
+use std::sync::Arc;
+use std::cell::RefCell;
+use std::rc::Rc;
 
 use piston_window::*;
 use find_folder::Search;
-use std::path::PathBuf;
-
+use std::path::{Path,PathBuf};
+use num::{BigUint,ToPrimitive,Zero};
+
+mod bench;
+mod browser;
+mod census;
+mod cli;
+mod collide;
+mod commands;
 mod common;
+mod error;
+mod history;
+mod library;
+mod rules;
+mod rule_table;
+mod scripting;
+mod search;
 mod life_algorithms;
+mod patterns;
+mod period;
+mod predecessor;
+mod recording;
+mod session;
+mod theme;
+mod tools;
+mod tracking;
 mod gui;
 
-use common::LifeAlgorithm;
-use gui::GUI;
+use cli::{Command,Options};
+use common::{Bounds,LifeAlgorithm};
+use error::Error;
+use history::History;
+use library::Library;
+use life_algorithms::{Algorithm,BoxedLife,RuleKind,Settings};
+use rules::Rule;
+use rule_table::TableRule;
+use patterns::read_seed_from_file;
+use recording::{Event,Recorder,Recording};
+use session::{Session,View};
+use theme::Theme;
+use tracking::ColorScheme;
+use gui::{Action,GUI};
+
+// Returns the next registered algorithm after the given one that can run the rule, if there is one
+fn next_algorithm(current: &Algorithm, settings: &Settings) -> Option<&'static Algorithm> {
+    let algorithms = &life_algorithms::ALGORITHMS;
+    let start = algorithms.iter().position(|a| a.name == current.name).unwrap_or(0);
+    (1..algorithms.len()).map(|i| algorithms[(start + i) % algorithms.len()])
+                         .find(|a| a.supports(&settings.rule))
+}
+
+fn window_title(algorithm: &Algorithm, rule: &str) -> String {
+    format!("Rusty Game of Life - {} - {}", algorithm.name, rule)
+}
+
+// The colors of the cell states, starting with the background. Rule tables bring their own
+fn palette(rule: &RuleKind, theme: &Theme) -> Vec<[f32; 4]> {
+    match *rule {
+        RuleKind::Table(ref t) => t.colors().clone(),
+        RuleKind::Life(_)      => vec![theme.background, theme.live],
+    }
+}
 
-fn run_life_with_gui<I: Iterator<Item=(isize, isize)>, L: LifeAlgorithm<I>>(mut life_obj: L, window: &mut PistonWindow, seed_file: PathBuf, font_file: PathBuf) {
-    // Read pattern from file      
-    read_seed_from_file(&mut life_obj,seed_file);
+// Everything being simulated, along with the rule as it was given so that it can be saved. A saved
+// session can replace all of it while the window is open
+struct Simulation {
+    life_obj: BoxedLife,
+    algorithm: &'static Algorithm,
+    settings: Settings,
+    rule: String,
+}
+
+fn run_life_with_gui(mut sim: Simulation, window: &mut PistonWindow, seed_file: PathBuf, font_file: PathBuf, theme: Theme, library: Library, script: Option<PathBuf>, view: Option<View>, mut error_message: Option<String>, opts: &Options) {
+    // Messages that aren't errors, such as where a session was saved, share the error's line
+    let mut notice: Option<String> = None;
 
     // Get window events 
     let mut events = window.events();
@@ -32,6 +100,24 @@ fn run_life_with_gui<I: Iterator<Item=(isize, isize)>, L: LifeAlgorithm<I>>(mut
 
     // Initialize GUI 
     let mut gui_obj = GUI::new();
+    gui_obj.set_palette(palette(&sim.settings.rule, &theme));
+    gui_obj.set_theme(theme);
+    gui_obj.set_library(library);
+    if let Some(dir) = seed_file.parent() {
+        gui_obj.set_browse_dir(dir.to_path_buf());
+    }
+    if let Some(view) = view {
+        gui_obj.set_view(view);
+    }
+    gui_obj.set_heat_window(opts.heat_window);
+    gui_obj.set_color_scheme(opts.colors);
+    if let Some(message) = track_cells(&mut sim, opts.colors) {
+        error_message = error_message.or(Some(message));
+    }
+    gui_obj.record_population(&sim.life_obj);
+
+    // Checkpoints to step back to, which start over whenever the life object is replaced
+    let mut history = History::new(&sim.life_obj, &opts.checkpoint_interval);
 
     // Some variables for benchmarking
     let mut time_taken = 0.0;
@@ -40,25 +126,233 @@ fn run_life_with_gui<I: Iterator<Item=(isize, isize)>, L: LifeAlgorithm<I>>(mut
 
     let mut do_update = true;
 
+    // With --record, everything done to the grid is logged for replay to play back
+    let mut recorder = None;
+    if let Some(ref path) = opts.event_log {
+        match Recorder::create(path, &sim.life_obj, sim.algorithm.name, &sim.rule, &opts.checkpoint_interval) {
+            Ok(r) => recorder = Some(r),
+            Err(e) => error_message = Some(e.to_string()),
+        }
+    }
+
     // Capture all the events we need and call the gui functions for each
     while let Some(e) = events.next(window) {
 
+        let was_paused = gui_obj.is_paused();
+        // Events are recorded at the generation they happen in, before any step they make
+        let generation = sim.life_obj.get_big_generation();
+
         if let Some(Button::Keyboard(key)) = e.press_args() {
-            gui_obj.key_press(key);
+            notice = None;
+            record(&mut recorder, &mut error_message, |r| r.record(&generation, &Event::Key(format!("{:?}", key))));
+            match gui_obj.key_press(key) {
+                // A file picked in the file browser replaces the running pattern. Files dropped on the
+                // window would be opened here as well, but piston_window 0.58 has no drop event
+                Ok(Action::Open(path)) => {
+                    error_message = read_seed_from_file(&mut sim.life_obj, path).err().map(|e| e.to_string());
+                    record(&mut recorder, &mut error_message, |r| r.load(&sim.life_obj));
+                    history = History::new(&sim.life_obj, &opts.checkpoint_interval);
+                    gui_obj.clear_populations();
+                    gui_obj.record_population(&sim.life_obj);
+                },
+                // The pattern and generation carry over to the next algorithm, which starts timing afresh
+                Ok(Action::SwitchAlgorithm) => match next_algorithm(sim.algorithm, &sim.settings) {
+                    Some(next) => match next.create_from(&sim.settings, &sim.life_obj) {
+                        Ok(new_life) => {
+                            sim.life_obj = new_life;
+                            sim.algorithm = next;
+                            history = History::new(&sim.life_obj, &opts.checkpoint_interval);
+                            window.set_title(window_title(sim.algorithm, &sim.rule));
+                            running_average_count = 0.0;
+                            running_average_time = 0.0;
+                            error_message = track_cells(&mut sim, gui_obj.color_scheme());
+                            record(&mut recorder, &mut error_message, |r| {
+                                try!(r.record(&generation, &Event::Switch(next.name.to_string())));
+                                r.check(&sim.life_obj)
+                            });
+                        },
+                        Err(e) => error_message = Some(e.to_string()),
+                    },
+                    None => error_message = Some(format!("No other algorithm can run the rule {}", sim.rule)),
+                },
+                Ok(Action::ZoomToFit) => if sim.life_obj.population() > 0 {
+                    gui_obj.zoom_to_fit(&sim.life_obj.get_bounds(), window);
+                },
+                Ok(Action::SaveSession) => {
+                    let path = opts.session_path();
+                    let session = Session::capture(&sim.life_obj, sim.algorithm.name, &sim.rule, Some(gui_obj.view()));
+                    match session.write(&path) {
+                        Ok(()) => {
+                            error_message = None;
+                            notice = Some(format!("Saved the session to {}", path.display()));
+                        },
+                        Err(e) => error_message = Some(e.to_string()),
+                    }
+                },
+                // Everything is replaced, including the rule and algorithm, and timing starts afresh
+                Ok(Action::LoadSession) => {
+                    let path = opts.session_path();
+                    match resume_session(&path, opts.threads) {
+                        Ok((session, new_sim)) => {
+                            sim = new_sim;
+                            history = History::new(&sim.life_obj, &opts.checkpoint_interval);
+                            let colors = palette(&sim.settings.rule, gui_obj.theme());
+                            gui_obj.set_palette(colors);
+                            if let Some(view) = session.view {
+                                gui_obj.set_view(view);
+                            }
+                            window.set_title(window_title(sim.algorithm, &sim.rule));
+                            gui_obj.clear_populations();
+                            gui_obj.record_population(&sim.life_obj);
+                            running_average_count = 0.0;
+                            running_average_time = 0.0;
+                            error_message = track_cells(&mut sim, gui_obj.color_scheme());
+                            record(&mut recorder, &mut error_message, |r| {
+                                try!(r.record(&generation, &Event::Rule(sim.algorithm.name.to_string(), sim.rule.clone())));
+                                r.load(&sim.life_obj)
+                            });
+                            notice = Some(format!("Loaded the session from {}", path.display()));
+                        },
+                        Err(e) => error_message = Some(e.to_string()),
+                    }
+                },
+                // A step back stops at the first generation there's a checkpoint for
+                Ok(Action::StepBack) => {
+                    let generation = sim.life_obj.get_big_generation();
+                    let target = if generation > opts.step { generation - &opts.step } else { BigUint::zero() };
+                    history.seek(&mut sim.life_obj, &target);
+                    gui_obj.record_population(&sim.life_obj);
+                    record(&mut recorder, &mut error_message, |r| record_seek(r, &generation, target, &sim.life_obj));
+                },
+                Ok(Action::StepForward) => {
+                    sim.life_obj.advance_by_big(&opts.step);
+                    history.record(&sim.life_obj);
+                    gui_obj.record_population(&sim.life_obj);
+                    record(&mut recorder, &mut error_message, |r| record_step(r, &generation, &opts.step, &sim.life_obj));
+                },
+                Ok(Action::Seek(target)) => {
+                    history.seek(&mut sim.life_obj, &target);
+                    gui_obj.record_population(&sim.life_obj);
+                    record(&mut recorder, &mut error_message, |r| record_seek(r, &generation, target, &sim.life_obj));
+                },
+                // The scheme is picked even if the algorithm can't track cells, so the next press
+                // moves on from it
+                Ok(Action::ColorScheme(scheme)) => {
+                    gui_obj.set_color_scheme(scheme);
+                    error_message = track_cells(&mut sim, scheme);
+                },
+                // A soup in the selection, which replaces whatever came after it like any other edit
+                Ok(Action::RandomFill(region)) => {
+                    let edits = {
+                        let mut editing = Recording::new(&mut sim.life_obj);
+                        tools::random_fill(&mut editing, &region, opts.density as f64 / 100.0);
+                        editing.events()
+                    };
+                    history.edited(&sim.life_obj);
+                    record(&mut recorder, &mut error_message, |r| r.edits(&sim.life_obj, &edits, true));
+                },
+                // The predecessor takes the place of the selection and the cells around it, which
+                // it spreads into, as an edit. What's outside the selection is left out of the search
+                Ok(Action::FindPredecessor(region)) => match sim.settings.rule {
+                    RuleKind::Life(rule) => {
+                        let target: Vec<(isize, isize)> = sim.life_obj.live_cells_in(&region).collect();
+                        match predecessor::find_predecessor(rule, &target, &region, 1) {
+                            Ok(Some(cells)) => {
+                                let edits = {
+                                    let mut editing = Recording::new(&mut sim.life_obj);
+                                    let around = predecessor::grown(&region, 1);
+                                    for y in around.y_min..around.y_max + 1 {
+                                        for x in around.x_min..around.x_max + 1 {
+                                            editing.set((x, y), false);
+                                        }
+                                    }
+                                    for &cell in &cells {
+                                        editing.set(cell, true);
+                                    }
+                                    editing.clean_up();
+                                    editing.events()
+                                };
+                                history.edited(&sim.life_obj);
+                                record(&mut recorder, &mut error_message, |r| r.edits(&sim.life_obj, &edits, true));
+                                notice = Some(format!("Found a predecessor with {} cells", cells.len()));
+                            },
+                            Ok(None) => error_message = Some("The selection is a Garden of Eden: nothing evolves into it".to_string()),
+                            Err(e) => error_message = Some(e.to_string()),
+                        }
+                    },
+                    RuleKind::Table(_) => error_message = Some("Predecessors can only be found under B/S rules".to_string()),
+                },
+                // A script works on the grid as it is, and what it leaves starts the timeline over
+                // like a loaded pattern. The last line it prints is shown in the overlay
+                Ok(Action::RunScript) => match script {
+                    Some(ref path) => {
+                        let printed = Rc::new(RefCell::new(None));
+                        let last_line = printed.clone();
+                        let result = scripting::run_script(path, &mut sim.life_obj, gui_obj.library().patterns(), find_seed, move |line| {
+                            println!("{}", line);
+                            *last_line.borrow_mut() = Some(line.to_string());
+                        });
+                        history = History::new(&sim.life_obj, &opts.checkpoint_interval);
+                        gui_obj.clear_populations();
+                        gui_obj.record_population(&sim.life_obj);
+                        record(&mut recorder, &mut error_message, |r| r.load(&sim.life_obj));
+                        match result {
+                            Ok(()) => notice = Some(printed.borrow_mut().take().unwrap_or(format!("Ran {}", path.display()))),
+                            Err(e) => error_message = Some(e.to_string()),
+                        }
+                    },
+                    None => error_message = Some("There's no script to run, start with --script SCRIPT".to_string()),
+                },
+                Ok(Action::Failed(message)) => error_message = Some(message),
+                Ok(Action::Edited) | Ok(Action::Nothing) => (),
+                Err(e) => error_message = Some(e.to_string()),
+            }
         };
 
-        if let Some(Button::Mouse(mouse_btn)) = e.release_args() {
-            gui_obj.mouse_release(mouse_btn);
-        };
+        // Whatever pressing, letting go of or moving the mouse asks for, and the cells it changed
+        let mut mouse_action = Action::Nothing;
+        let edits = {
+            let mut editing = Recording::new(&mut sim.life_obj);
 
-        if let Some(Button::Mouse(mouse_btn)) = e.press_args() {
-           gui_obj.mouse_press(mouse_btn,&mut life_obj,window);
-        };
-       
-        if let Some(mot) = e.mouse_cursor_args(){
-            gui_obj.mouse_move(mot);
+            if let Some(Button::Mouse(mouse_btn)) = e.release_args() {
+                mouse_action = gui_obj.mouse_release(mouse_btn,&mut editing);
+            };
+
+            if let Some(Button::Mouse(mouse_btn)) = e.press_args() {
+                mouse_action = gui_obj.mouse_press(mouse_btn,&mut editing,window);
+            };
+           
+            if let Some(mot) = e.mouse_cursor_args(){
+                mouse_action = gui_obj.mouse_move(mot,&mut editing,window);
+            };
+
+            editing.events()
         };
 
+        // Cells drawn while the button is held are recorded as they change, and checked once the
+        // edit is finished
+        let finished = match mouse_action { Action::Edited => true, _ => false };
+        if finished || !edits.is_empty() {
+            record(&mut recorder, &mut error_message, |r| r.edits(&sim.life_obj, &edits, finished));
+        }
+
+        match mouse_action {
+            // An edit replaces whatever came after the current generation
+            Action::Edited => history.edited(&sim.life_obj),
+            Action::Seek(target) => {
+                history.seek(&mut sim.life_obj, &target);
+                gui_obj.record_population(&sim.life_obj);
+                record(&mut recorder, &mut error_message, |r| record_seek(r, &generation, target, &sim.life_obj));
+            },
+            Action::Failed(message) => error_message = Some(message),
+            _ => (),
+        }
+
+        if gui_obj.is_paused() != was_paused {
+            let paused = gui_obj.is_paused();
+            record(&mut recorder, &mut error_message, |r| r.record(&generation, &Event::Pause(paused)));
+        }
+
         if let Some(scroll) = e.mouse_scroll_args(){
             gui_obj.mouse_scroll(scroll);
         };
@@ -68,13 +362,26 @@ fn run_life_with_gui<I: Iterator<Item=(isize, isize)>, L: LifeAlgorithm<I>>(mut
             do_update = true;
 
             //Draw the grid 
-            gui_obj.draw(&life_obj,window,&e);
+            gui_obj.draw(&sim.life_obj,window,&e);
             
-            let generation = life_obj.get_generation();
+            let generation = sim.life_obj.get_big_generation();
             
             let average_time_text:String = format!("Average time per generation: {0:.4} seconds.", running_average_time / running_average_count);  
             let last_time_text:String = format!("Time taken for last generation: {0:.4} seconds.", time_taken);  
-            let current_generation_text:String = format!("Generation: {}", generation);  
+            let (cursor_x, cursor_y) = gui_obj.cursor_cell(window);
+            // While a B0 background is alive, the population counts the dead cells
+            let population = if sim.life_obj.background() { format!("all but {}", sim.life_obj.population()) } else { sim.life_obj.population().to_string() };
+            let current_generation_text:String = format!("Generation: {}    Population: {}    Cursor: {}, {}", generation, population, cursor_x, cursor_y);  
+            let algorithm_text:String = format!("Algorithm: {} (A to switch)    Colors: {} (C to switch)", sim.algorithm.name, gui_obj.color_scheme().name());
+            let browser_lines = gui_obj.browser_lines().or_else(|| gui_obj.library_lines());
+            let text_color = gui_obj.theme().text;
+            let tool_text = match gui_obj.stamp_status() {
+                Some(status) => status,
+                None => {
+                    let script_text = script.as_ref().map_or(String::new(), |p| format!("    X: run {}", p.display()));
+                    format!("{}    R: random fill at {}%    P: pattern library{}", gui_obj.tool_palette(), opts.density, script_text)
+                },
+            };
 
             // Render text
              window.draw_2d(&e, |c, g| {
@@ -83,7 +390,7 @@ fn run_life_with_gui<I: Iterator<Item=(isize, isize)>, L: LifeAlgorithm<I>>(mut
                 let line_spacing = 15.0;
 
                 let mut transform = c.transform.trans(x, y);
-                text::Text::new_color([0.0, 0.0, 0.0, 1.0], 11).draw(
+                text::Text::new_color(text_color, 11).draw(
                     &average_time_text,
                     &mut glyphs,
                     &c.draw_state,
@@ -91,7 +398,7 @@ fn run_life_with_gui<I: Iterator<Item=(isize, isize)>, L: LifeAlgorithm<I>>(mut
                 );
 
                 transform = c.transform.trans(x, y+line_spacing);
-                text::Text::new_color([0.0, 0.0, 0.0, 1.0], 11).draw(
+                text::Text::new_color(text_color, 11).draw(
                     &last_time_text,
                     &mut glyphs,
                     &c.draw_state,
@@ -99,29 +406,85 @@ fn run_life_with_gui<I: Iterator<Item=(isize, isize)>, L: LifeAlgorithm<I>>(mut
                 );
 
                 transform = c.transform.trans(x, y+line_spacing*2.0);
-                text::Text::new_color([0.0, 0.0, 0.0, 1.0], 11).draw(
+                text::Text::new_color(text_color, 11).draw(
                     &current_generation_text,
                     &mut glyphs,
                     &c.draw_state,
                     transform, g
                 );
 
+                transform = c.transform.trans(x, y+line_spacing*3.0);
+                text::Text::new_color(text_color, 11).draw(
+                    &algorithm_text,
+                    &mut glyphs,
+                    &c.draw_state,
+                    transform, g
+                );
+
+                if let Some(ref message) = error_message {
+                    transform = c.transform.trans(x, y+line_spacing*4.0);
+                    text::Text::new_color([0.8, 0.0, 0.0, 1.0], 11).draw(
+                        message,
+                        &mut glyphs,
+                        &c.draw_state,
+                        transform, g
+                    );
+                } else if let Some(ref message) = notice {
+                    transform = c.transform.trans(x, y+line_spacing*4.0);
+                    text::Text::new_color(text_color, 11).draw(
+                        message,
+                        &mut glyphs,
+                        &c.draw_state,
+                        transform, g
+                    );
+                }
+
+                transform = c.transform.trans(x, y+line_spacing*5.0);
+                text::Text::new_color(text_color, 11).draw(
+                    &tool_text,
+                    &mut glyphs,
+                    &c.draw_state,
+                    transform, g
+                );
+
+                // The file browser or the library goes below the rest of the overlay
+                if let Some(ref lines) = browser_lines {
+                    for (i, line) in lines.iter().enumerate() {
+                        transform = c.transform.trans(x, y+line_spacing*(7.0 + i as f64));
+                        text::Text::new_color(text_color, 11).draw(
+                            line,
+                            &mut glyphs,
+                            &c.draw_state,
+                            transform, g
+                        );
+                    }
+                }
 
             });
+
+            gui_obj.draw_population_chart(window, &e);
+            gui_obj.set_timeline(history.first().clone(), history.last().clone());
+            gui_obj.draw_timeline(window, &e, &generation);
+            gui_obj.draw_minimap(&sim.life_obj, sim.algorithm.capabilities.large_steps, window, &e);
+            gui_obj.draw_library_preview(window, &e);
         }
 
         if let Some(_) = e.update_args(){
             //Update
 
-            if !gui_obj.is_paused() && do_update {
+            if !gui_obj.is_paused() && !gui_obj.is_drawing() && do_update {
                 // Record time it takes to calculate this generation step
                 let start_time = time::precise_time_ns() as f64;
                 // Advance the simulation 
-                life_obj.advance_by(1);
+                let generation = sim.life_obj.get_big_generation();
+                sim.life_obj.advance_by_big(&opts.step);
                 // Now calculate the time and average 
                 time_taken =  ((time::precise_time_ns() as f64 - start_time) as f64) / 1000000000.0;
                 running_average_count += 1.0;
                 running_average_time += time_taken;
+                history.record(&sim.life_obj);
+                gui_obj.record_population(&sim.life_obj);
+                record(&mut recorder, &mut error_message, |r| record_step(r, &generation, &opts.step, &sim.life_obj));
                 do_update = false;
             }
 
@@ -129,85 +492,333 @@ fn run_life_with_gui<I: Iterator<Item=(isize, isize)>, L: LifeAlgorithm<I>>(mut
     }
 }
 
+// Records an event with the recorder, if there is one. Recording stops at the first error, which
+// is shown in the overlay.
+fn record<F>(recorder: &mut Option<Recorder>, error_message: &mut Option<String>, f: F)
+    where F: FnOnce(&mut Recorder) -> Result<(), Error>
+{
+    let failed = match *recorder {
+        Some(ref mut r) => f(r).err(),
+        None => None,
+    };
+    if let Some(e) = failed {
+        *error_message = Some(format!("Stopped recording: {}", e));
+        *recorder = None;
+    }
+}
+
+fn record_step<L: LifeAlgorithm + ?Sized>(recorder: &mut Recorder, generation: &BigUint, step: &BigUint, life_obj: &L) -> Result<(), Error> {
+    try!(recorder.record(generation, &Event::Step(step.clone())));
+    recorder.check(life_obj)
+}
+
+fn record_seek<L: LifeAlgorithm + ?Sized>(recorder: &mut Recorder, generation: &BigUint, target: BigUint, life_obj: &L) -> Result<(), Error> {
+    try!(recorder.record(generation, &Event::Seek(target)));
+    recorder.check(life_obj)
+}
+
+// Turns cell tracking on or off to suit the color scheme, returning why not if it can't be
+fn track_cells(sim: &mut Simulation, scheme: ColorScheme) -> Option<String> {
+    if sim.life_obj.set_tracking(scheme.tracks_cells()) {
+        None
+    } else if sim.algorithm.capabilities.cell_tracking {
+        Some(format!("Cells can't be colored by {} under a B0 rule", scheme.name()))
+    } else {
+        Some(format!("The {} algorithm can't color cells by {}, press A to switch", sim.algorithm.name, scheme.name()))
+    }
+}
+
 fn main() {
-    // Collect any command line arguments 
-    // All args are optional. 
-    // First is which built in pattern to start with. Defaults to r_pentomino
-    // Second is which algorithm to use. Defaults to sequential. 
-    let args: Vec<String> = env::args().collect();
-    
-    let mut seed_pattern = "r_pentomino".to_string();
-    let mut mode = "sequential".to_string();
-
-    if args.len() > 1 { seed_pattern = args[1].parse().unwrap(); }
-    if args.len() > 2 { mode = args[2].parse().unwrap(); }
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = cli::parse(&args).and_then(|opts| run(&opts));
+    let code = match result {
+        Ok(()) => return,
+        Err(Error::Argument(_)) => 2,
+        Err(Error::Mismatch(_)) => 3,
+        Err(_) => 1,
+    };
+    // Errors go to stderr, so they don't end up in patterns or reports piped from stdout
+    let _ = writeln!(io::stderr(), "Error: {}", result.unwrap_err());
+    process::exit(code);
+}
 
-    // Set up Piston window 
-    let mut window:PistonWindow = WindowSettings::new("Rusty Game of Life - ".to_string() + &mode, [600,400]).build().unwrap();
+// The assets folder holds the built in seeds, rule tables and the font
+fn find_assets() -> Result<PathBuf, Error> {
+    Search::Parents(3).for_folder("assets").map_err(|_| Error::MissingAssets)
+}
 
-    // Get asset path 
-    let asset_path = Search::Parents(3).for_folder("assets").unwrap();
+// A path to an existing file is used as is, otherwise the name is looked up in the given folder of
+// assets with each of the given extensions in turn
+fn find_file(name: &str, folder: &str, extensions: &[&str]) -> Result<PathBuf, Error> {
+    let path = PathBuf::from(name);
+    if path.is_file() {
+        return Ok(path)
+    }
+    let mut file = try!(find_assets());
+        file.push(folder);
+        file.push(name);
+    for extension in extensions {
+        file.set_extension(extension);
+        if file.exists() { return Ok(file) }
+    }
+    // Report the first extension if none of them exist
+    file.set_extension(extensions[0]);
+    Ok(file)
+}
 
-    // Get the seed file 
-    let mut init_file:PathBuf = asset_path.clone(); 
-        init_file.push("game_seeds");
-        init_file.push(seed_pattern + &".cells".to_string());
+fn run(opts: &Options) -> Result<(), Error> {
+    if opts.command == Command::Help {
+        print!("{}", cli::usage());
+        return Ok(())
+    }
 
-    // Get the font file 
-    let mut font_path = asset_path.clone();
-        font_path.push("fonts");
-        font_path.push("Quicksand-Regular.ttf");
+    // The log says which algorithms and rules to make, unless --algorithm picks one for all of it
+    if opts.command == Command::Replay {
+        let create = |algorithm: &str, rule: &str| -> Result<BoxedLife, Error> {
+            let settings = Settings { rule: try!(load_rule(rule)), threads: opts.threads };
+            try!(life_algorithms::find(algorithm)).create(&settings)
+        };
+        return commands::replay(opts, opts.event_log.as_ref().unwrap(), create)
+    }
 
-    // Instantiate the right algorithm based on the given mode 
-    match mode.as_ref() {
-        "sequential" => {let life_logic = life_algorithms::sequential::Life::new();
-                         run_life_with_gui(life_logic, &mut window, init_file, font_path);
-                        },
-        "parallel" =>   {let life_logic = life_algorithms::parallel::Life::new();
-                         run_life_with_gui(life_logic, &mut window, init_file, font_path);
-                        },
-        "hashlife" =>   {let life_logic = life_algorithms::hashlife::Life::new();
-                         run_life_with_gui(life_logic, &mut window, init_file, font_path);
-                        },
-        _ => panic!("{:?} is not a recognized algorithm. See src/life_algorithms for a list of implemented algorithms.", mode),
+    // Get the seed file, which may be in any of the formats in patterns.rs
+    let init_file = try!(find_seed(opts.pattern()));
+    if opts.command == Command::Convert {
+        return commands::convert(opts, init_file, opts.output.as_ref().unwrap())
     }
 
+    // A resumed session brings its own algorithm and rule, and carries on from where it was saved
+    if let Some(ref path) = opts.resume {
+        let (session, sim) = try!(resume_session(path, opts.threads));
+        return match opts.command {
+            Command::Run    => commands::run(sim.life_obj, opts),
+            Command::Census => {
+                let conway = is_conway(&sim.settings.rule);
+                commands::census(sim.life_obj, opts, conway)
+            },
+            _ => run_gui(opts, sim, init_file, session.view, None),
+        }
+    }
 
+    let settings = Settings { rule: try!(load_rule(&opts.rule)), threads: opts.threads };
+    let conway = is_conway(&settings.rule);
+
+    match opts.command {
+        Command::Run    => commands::run(try!(load_life(opts.algorithm(), &settings, init_file)), opts),
+        Command::Bench  => run_bench(opts, &settings),
+        Command::Script => {
+            // Scripts start from an empty grid unless they're given a pattern
+            let mut life_obj = try!(try!(life_algorithms::find(opts.algorithm())).create(&settings));
+            if opts.pattern.is_some() {
+                try!(read_seed_from_file(&mut life_obj, init_file));
+            }
+            let library = try!(Library::load(&try!(find_assets()).join("library")));
+            let script = try!(find_script(opts.script.as_ref().unwrap()));
+            commands::script(life_obj, &script, library.patterns(), find_seed, opts)
+        },
+        Command::Census => commands::census(try!(load_life(opts.algorithm(), &settings, init_file)), opts, conway),
+        Command::Collide => run_collide(opts, &settings, conway),
+        Command::Search => run_search(opts, &settings, conway),
+        Command::Predecessor => run_predecessor(opts, &settings, init_file),
+        _ => {
+            let algorithm = try!(life_algorithms::find(opts.algorithm()));
+            let mut life_obj = try!(algorithm.create(&settings));
+            // A file that can't be loaded leaves the grid empty, and the reason is shown in the overlay
+            let error_message = read_seed_from_file(&mut life_obj, init_file.clone()).err().map(|e| e.to_string());
+            let sim = Simulation { life_obj: life_obj, algorithm: algorithm, settings: settings, rule: opts.rule.clone() };
+            run_gui(opts, sim, init_file, None, error_message)
+        },
+    }
+}
+
+// Rule tables are loaded from assets/rules or a path ending in .rule, anything else is a B/S rule
+fn load_rule(name: &str) -> Result<RuleKind, Error> {
+    let table_file = if Path::new(name).extension().map_or(false, |e| e == "rule") {
+        Some(PathBuf::from(name))
+    } else {
+        find_assets().ok().map(|mut path| {
+            path.push("rules");
+            path.push(name.to_string() + ".rule");
+            path
+        }).and_then(|path| if path.exists() { Some(path) } else { None })
+    };
+    match table_file {
+        Some(path) => Ok(RuleKind::Table(Arc::new(try!(TableRule::from_file(&path))))),
+        None       => Ok(RuleKind::Life(try!(Rule::parse(name)))),
+    }
+}
+
+// Objects are only named by census under Conway's rule
+fn is_conway(rule: &RuleKind) -> bool {
+    match *rule {
+        RuleKind::Life(r)  => r == Rule::conway(),
+        RuleKind::Table(_) => false,
+    }
 }
 
-fn read_seed_from_file<I: Iterator<Item=(isize, isize)>, L: LifeAlgorithm<I>>(life_obj: &mut L, path: PathBuf){
-    // Takes a ref to a game-of-life object and an absolute filepath, and reads the pattern
-    // Expects to find a .cells file 
-    assert_eq!(path.extension().unwrap(), "cells");
-    // Attempt to open the file
-    if let Ok(f) = File::open(&path) {
-        let f = BufReader::new(f);
-        let mut j = 0;
+// Creates a life object of the named algorithm holding the seed
+fn load_life(algorithm: &str, settings: &Settings, seed: PathBuf) -> Result<BoxedLife, Error> {
+    let mut life_obj = try!(try!(life_algorithms::find(algorithm)).create(settings));
+    try!(read_seed_from_file(&mut life_obj, seed));
+    Ok(life_obj)
+}
 
-        // Clear life object first 
-        (*life_obj).clear();
+// Reads a session file and sets up a life object to carry on from it, with the session's algorithm
+// and rule
+fn resume_session(path: &Path, threads: Option<usize>) -> Result<(Session, Simulation), Error> {
+    let session = try!(Session::read(path));
+    let algorithm = try!(life_algorithms::find(&session.algorithm));
+    let settings = Settings { rule: try!(load_rule(&session.rule)), threads: threads };
+    let mut life_obj = try!(algorithm.create(&settings));
+    try!(session.restore(&mut life_obj, path));
+    let sim = Simulation { life_obj: life_obj, algorithm: algorithm, settings: settings, rule: session.rule.clone() };
+    Ok((session, sim))
+}
 
-        // Iterate over all the lines 
-        for (lno, line) in f.lines().enumerate() {
-            let line = line.unwrap();
-            if line != "" && &line[0..1] != "!" { // Ignore commented out lines 
-                for (i,c) in line.chars().enumerate() {
-                    match c {
-                        '.' => (),
-                        'O' => (*life_obj).set((i as isize,j), true), 
-                        _   => panic!("Invalid character {} at position {}, {} in file {}",
-                                      c, lno, i, path.display()),
-                    };
-                }
-                j += 1;
+// Looks up a seed, which may be in any of the formats in patterns.rs
+fn find_seed(name: &str) -> Result<PathBuf, Error> {
+    find_file(name, "game_seeds", &["cells", "rle", "lif", "life", "mc"])
+}
+
+fn find_script(name: &str) -> Result<PathBuf, Error> {
+    find_file(name, "scripts", &["rhai"])
+}
+
+fn run_bench(opts: &Options, settings: &Settings) -> Result<(), Error> {
+    // A pattern or algorithm on the command line replaces the corpus or the default algorithms
+    let requested = match opts.generations {
+        Some(ref generations) => Some(try!(generations.to_u64().ok_or(Error::Argument("bench can run at most 2^64-1 generations".to_string())))),
+        None => None,
+    };
+    let mut cases = vec![];
+    if let Some(ref pattern) = opts.pattern {
+        cases.push(bench::Case { name: pattern.clone(), path: try!(find_seed(pattern)), generations: requested.unwrap_or(100) });
+    } else {
+        for &(name, generations) in bench::CORPUS.iter() {
+            let generations = requested.unwrap_or(generations);
+            cases.push(bench::Case { name: name.to_string(), path: try!(find_seed(name)), generations: generations });
+        }
+    }
+    let algorithms = match opts.algorithm {
+        Some(ref algorithm) => vec![try!(life_algorithms::find(algorithm))],
+        None => life_algorithms::ALGORITHMS.iter().cloned().filter(|a| a.supports(&settings.rule)).collect(),
+    };
+
+    let mut reports = vec![];
+    for algorithm in algorithms {
+        for case in &cases {
+            let mut samples = vec![];
+            for _ in 0..opts.repeat {
+                let life_logic = try!(algorithm.create(settings));
+                samples.push(try!(bench::time_run(life_logic, case, &opts.step)));
             }
-            // Empty lines represent empty rows 
-            if line  == "" { j+= 1; }
+            reports.push(bench::Report::new(algorithm.name, case, &samples));
         }
-    } else {
-        panic!("Could not open {}", path.display());
     }
 
-    // Run any necessary clean up (such as shrinking the hashmap)
-    (*life_obj).clean_up();
+    bench::print_reports(&reports);
+    match (opts.output.as_ref(), opts.format()) {
+        (Some(output), "csv") => bench::write_csv(&reports, &opts.rule, output),
+        (Some(output), _)     => bench::write_json(&reports, &opts.rule, output),
+        (None, _)             => Ok(()),
+    }
 }
+
+fn run_collide(opts: &Options, settings: &Settings, conway: bool) -> Result<(), Error> {
+    // Objects are placed by their live cells, which under B0 are dead on every other generation
+    match settings.rule {
+        RuleKind::Life(ref rule) if !rule.is_b0() => (),
+        _ => return Err(Error::Rule("collide needs a B/S rule without B0".to_string())),
+    }
+    let algorithm = match opts.algorithm {
+        Some(ref algorithm) => try!(life_algorithms::find(algorithm)),
+        None => life_algorithms::fastest(&settings.rule).unwrap(),
+    };
+    let generations = match opts.generations {
+        Some(ref generations) => try!(generations.to_u64().ok_or(Error::Argument("collide can run at most 2^64-1 generations".to_string()))),
+        None => 500,
+    };
+
+    let library = try!(Library::load(&try!(find_assets()).join("library")));
+    let mut objects = vec![];
+    for spec in &opts.objects {
+        objects.push(try!(collide::Object::new(spec, library.patterns(), find_seed, algorithm, settings)));
+    }
+    let range = opts.range as isize;
+    let collisions = try!(collide::collide(&objects, range, generations, algorithm, settings, conway));
+    let report = collide::report(&objects, &collisions, range, algorithm);
+    print!("{}", report);
+    match opts.output {
+        Some(ref output) => collide::write_report(&report, output),
+        None => Ok(()),
+    }
+}
+
+fn run_search(opts: &Options, settings: &Settings, conway: bool) -> Result<(), Error> {
+    let rule = match settings.rule {
+        RuleKind::Life(rule) => rule,
+        RuleKind::Table(_)   => return Err(Error::Rule("search needs a B/S rule".to_string())),
+    };
+    // Still lifes are looked for in every box their cells could fill, oscillators in a small one
+    let (cells, size, description) = if opts.period == 1 {
+        let cells = opts.cells.unwrap_or(8);
+        let size = opts.search_box.unwrap_or((cells as usize, cells as usize));
+        (Some(cells), size, format!("still lifes with up to {} cells within {}x{}", cells, size.0, size.1))
+    } else {
+        let size = opts.search_box.unwrap_or((6, 6));
+        (opts.cells, size, format!("period {} oscillators within {}x{}", opts.period, size.0, size.1))
+    };
+    let algorithm = try!(life_algorithms::find(opts.algorithm()));
+    let found = try!(search::search(rule, opts.period, size, cells.map(|c| c as usize), algorithm, settings));
+    commands::search(&found, &description, opts, conway)
+}
+
+fn run_predecessor(opts: &Options, settings: &Settings, init_file: PathBuf) -> Result<(), Error> {
+    let rule = match settings.rule {
+        RuleKind::Life(rule) => rule,
+        RuleKind::Table(_)   => return Err(Error::Rule("predecessor needs a B/S rule".to_string())),
+    };
+    let generations = match opts.generations {
+        Some(ref generations) => try!(generations.to_u64().ok_or(Error::Argument("predecessor can go back at most 2^64-1 generations".to_string()))),
+        None => 1,
+    };
+    let life_obj = try!(load_life(opts.algorithm(), settings, init_file));
+    let target: Vec<(isize, isize)> = life_obj.live_cells().collect();
+    if target.is_empty() {
+        return Err(Error::Argument(format!("{} has no live cells to find a predecessor of", opts.pattern())))
+    }
+    // The pattern has to be reached with nothing around it, so the cells next to it must end up dead
+    let mut bounds = Bounds { x_min: target[0].0, x_max: target[0].0, y_min: target[0].1, y_max: target[0].1 };
+    for &(x, y) in &target {
+        bounds.update_bounds(x, y);
+    }
+    let region = predecessor::grown(&bounds, 1);
+    let found = try!(predecessor::find_predecessor(rule, &target, &region, generations));
+    commands::predecessor(found.as_ref().map(|cells| &cells[..]), generations, opts)
+}
+
+fn run_gui(opts: &Options, sim: Simulation, init_file: PathBuf, view: Option<View>, error_message: Option<String>) -> Result<(), Error> {
+    // The theme is loaded before the window opens, so a mistake in it is reported like any other
+    let theme = match opts.theme {
+        Some(ref name) => try!(Theme::from_file(&try!(find_file(name, "themes", &["theme"])))),
+        None           => Theme::new(),
+    };
+
+    // So is the pattern library, which is read once and searched in memory
+    let library = try!(Library::load(&try!(find_assets()).join("library")));
+    let script = match opts.script {
+        Some(ref name) => Some(try!(find_script(name))),
+        None           => None,
+    };
+
+    // Get the font file 
+    let mut font_path = try!(find_assets());
+        font_path.push("fonts");
+        font_path.push("Quicksand-Regular.ttf");
+
+    // Set up Piston window 
+    let mut window:PistonWindow = try!(WindowSettings::new(window_title(sim.algorithm, &sim.rule), [opts.width, opts.height]).build().map_err(Error::Window));
+
+    run_life_with_gui(sim, &mut window, init_file, font_path, theme, library, script, view, error_message, opts);
+    Ok(())
+}
\ No newline at end of file
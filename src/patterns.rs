@@ -0,0 +1,723 @@
+/*
+
+This file reads seed patterns from files into any object that
+implements LifeAlgorithm, and writes them back out.
+
+The format of a file is worked out from its contents rather than its
+extension:
+
+* Plaintext (.cells): rows of '.' and 'O', with '!' comments.
+* RLE (.rle): run-length encoded rows after an "x = ..." header. RLE
+  can also describe multi-state patterns, with states 1, 2, 3...
+  written as A, B, C... the way Golly writes them.
+* Life 1.05 (.lif): starts with "#Life 1.05". Blocks of '.' and '*'
+  rows, each placed by a "#P x y" line giving its top left corner.
+* Life 1.06 (.lif): starts with "#Life 1.06". One "x y" coordinate
+  pair per live cell.
+* Macrocell (.mc): starts with "[M2]". Golly's format for HashLife
+  trees, one node per line. Two-state patterns have 8x8 squares of
+  '.' and '*' rows ending in '$' as their smallest nodes, and
+  multi-state ones have level 1 nodes of four states. Every other
+  line gives a node's level and its nw, ne, sw and se quadrants as
+  the numbers of earlier lines, counted from 1, with 0 for an empty
+  quadrant. The last line is the root, which is centered on (0,0).
+
+Life 1.05 and 1.06 files keep their absolute coordinates, so patterns
+written out can be read back into the same place. So do Macrocell
+files, and RLE files with a Golly "#CXRLE Pos=x,y" line giving the
+position of their top left corner. Plaintext and other RLE files
+always start at (0,0).
+
+Small objects can also be given by their apgcode, the name Catagolue
+gives them, such as xs4_33 for a block or xq4_153 for a glider. After
+the prefix, each character from 0-9 and a-v is a column of five
+cells, lowest bit at the top; w and x are two and three empty
+columns, y followed by a character from 0-9 and a-z is four or more,
+and z starts the next five rows down.
+
+*/
+
+use std::collections::BTreeMap;
+use std::io;
+use std::io::prelude::*;
+use std::fs::File;
+use std::path::{Path,PathBuf};
+
+use common::LifeAlgorithm;
+use error::Error;
+use rules::Rule;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    Cells,
+    Rle,
+    Life105,
+    Life106,
+    Macrocell,
+}
+
+impl Format {
+    /// Guesses the format of a pattern file from its contents.
+    pub fn sniff(text: &str) -> Option<Format> {
+        let first = text.lines().next().unwrap_or("").trim();
+        if first.starts_with("#Life 1.05") {
+            return Some(Format::Life105)
+        }
+        if first.starts_with("#Life 1.06") {
+            return Some(Format::Life106)
+        }
+        if first.starts_with("[M2]") {
+            return Some(Format::Macrocell)
+        }
+        for line in text.lines().map(|l| l.trim()) {
+            if line.starts_with('!') {
+                return Some(Format::Cells)
+            }
+            if line == "" || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('x') && line.contains('=') {
+                return Some(Format::Rle)
+            }
+            if line.chars().all(|c| c == '.' || c == 'O') {
+                return Some(Format::Cells)
+            }
+            // RLE files may leave out the header
+            if line.chars().all(|c| c.is_alphanumeric() || c == '$' || c == '!' || c.is_whitespace()) {
+                return Some(Format::Rle)
+            }
+            return None
+        }
+        None
+    }
+}
+
+/// Reads the pattern in the given file into a life object, replacing its contents. If the file
+/// cannot be read the life object is left empty.
+pub fn read_seed_from_file<L: LifeAlgorithm + ?Sized>(life_obj: &mut L, path: PathBuf) -> Result<(), Error> {
+    // Takes a ref to a game-of-life object and an absolute filepath, and reads the pattern
+    // Clear life object first
+    (*life_obj).clear();
+
+    // Attempt to open the file
+    let mut text = String::new();
+    if let Err(e) = File::open(&path).and_then(|mut f| f.read_to_string(&mut text)) {
+        return Err(Error::Io(path, e))
+    }
+    read_pattern(life_obj, &text, &path)
+}
+
+/// Reads a pattern in any of the formats above into a life object, replacing its contents. The
+/// path is only used to report errors.
+pub fn read_pattern<L: LifeAlgorithm + ?Sized>(life_obj: &mut L, text: &str, path: &Path) -> Result<(), Error> {
+    (*life_obj).clear();
+    let result = match Format::sniff(text) {
+        Some(Format::Cells)     => read_cells(life_obj, text),
+        Some(Format::Rle)       => read_rle(life_obj, text),
+        Some(Format::Life105)   => read_life_105(life_obj, text),
+        Some(Format::Life106)   => read_life_106(life_obj, text),
+        // Engines that keep a quadtree can take the nodes as they are
+        Some(Format::Macrocell) => match (*life_obj).from_macrocell(text) {
+            Some(result) => result,
+            None => read_macrocell(life_obj, text),
+        },
+        None => Err(Error::UnknownFormat(path.to_path_buf())),
+    };
+
+    // Run any necessary clean up (such as shrinking the hashmap)
+    (*life_obj).clean_up();
+    result.map_err(|e| e.in_file(&path.to_path_buf()))
+}
+
+/// Reads an object given by its apgcode into a life object, replacing its contents, with the top
+/// left corner at (0,0). Only the codes of still lifes (xs), oscillators (xp) and spaceships (xq)
+/// are understood.
+pub fn read_apgcode<L: LifeAlgorithm + ?Sized>(life_obj: &mut L, code: &str) -> Result<(), Error> {
+    let error = || Error::Argument(format!("{} is not an apgcode such as xs4_33 or xq4_153", code));
+    let underscore = try!(code.find('_').ok_or_else(&error));
+    let (prefix, body) = (&code[..underscore], &code[underscore+1..]);
+    let known = prefix.starts_with("xs") || prefix.starts_with("xp") || prefix.starts_with("xq");
+    if !known || prefix[2..].parse::<u64>().is_err() || body.is_empty() {
+        return Err(error())
+    }
+
+    (*life_obj).clear();
+    let (mut x, mut y) = (0, 0);
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            'w' => x += 2,
+            'x' => x += 3,
+            'y' => match chars.next().and_then(|n| n.to_digit(36)) {
+                Some(n) => x += 4 + n as isize,
+                None => return Err(error()),
+            },
+            'z' => {
+                x = 0;
+                y += 5;
+            },
+            _ => match c.to_digit(32) {
+                Some(column) => {
+                    for row in 0..5 {
+                        if column & (1 << row) != 0 {
+                            (*life_obj).set((x, y + row), true);
+                        }
+                    }
+                    x += 1;
+                },
+                None => return Err(error()),
+            },
+        }
+    }
+    (*life_obj).clean_up();
+    Ok(())
+}
+
+fn read_cells<L: LifeAlgorithm + ?Sized>(life_obj: &mut L, text: &str) -> Result<(), Error> {
+    let mut j = 0;
+
+    // Iterate over all the lines
+    for (lno, line) in text.lines().enumerate() {
+        if line != "" && &line[0..1] != "!" { // Ignore commented out lines
+            for (i,c) in line.chars().enumerate() {
+                match c {
+                    '.' => (),
+                    'O' => (*life_obj).set((i as isize,j), true),
+                    _   => return Err(Error::syntax(lno+1, i+1, format!("invalid character {}", c))),
+                };
+            }
+            j += 1;
+        }
+        // Empty lines represent empty rows
+        if line  == "" { j+= 1; }
+    }
+    Ok(())
+}
+
+fn read_rle<L: LifeAlgorithm + ?Sized>(life_obj: &mut L, text: &str) -> Result<(), Error> {
+    // The top left corner of the pattern, which Golly can give in a #CXRLE line
+    let mut left = 0;
+    let mut x = 0;
+    let mut y = 0;
+    let mut count = 0;
+    let mut prefix = 0u16; // states above 24 are written as pA, pB, ... qA, ...
+
+    for (lno, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with("#CXRLE") {
+            if let Some(i) = line.find("Pos=") {
+                let column = i + 5;
+                let pos = line[column-1..].split_whitespace().next().unwrap_or("");
+                let corner = try!(parse_coordinates(&pos.replace(",", " "), lno+1, column));
+                left = corner.0;
+                x = corner.0;
+                y = corner.1;
+            }
+            continue;
+        }
+        // Skip comments and the "x = ..., y = ..., rule = ..." header
+        if line.starts_with('#') || line.starts_with('x') {
+            continue;
+        }
+        for (i,c) in line.chars().enumerate() {
+            let run = if count == 0 { 1 } else { count };
+            match c {
+                '0'...'9' => { count = count*10 + c.to_digit(10).unwrap() as isize; continue; },
+                'p'...'y' => { prefix = (c as u16 - 'p' as u16 + 1) * 24; continue; },
+                'b' | '.' => x += run,
+                'o' | 'A'...'X' => {
+                    let state = if c == 'o' { 1 } else { prefix + (c as u16 - 'A' as u16 + 1) };
+                    if state >= (*life_obj).num_states() as u16 {
+                        return Err(Error::syntax(lno+1, i+1, format!("state {} is past the {} states of the rule", state, (*life_obj).num_states())))
+                    }
+                    let state = state as u8;
+                    for k in 0..run {
+                        (*life_obj).set_state((x+k, y), state);
+                    }
+                    x += run;
+                },
+                '$' => { y += run; x = left; },
+                '!' => return Ok(()),
+                _ if c.is_whitespace() => continue,
+                _   => return Err(Error::syntax(lno+1, i+1, format!("invalid character {}", c))),
+            }
+            count = 0;
+            prefix = 0;
+        }
+    }
+    Ok(())
+}
+
+/// Parses a whitespace separated pair of coordinates, where the pair starts at the given column.
+fn parse_coordinates(text: &str, lno: usize, column: usize) -> Result<(isize, isize), Error> {
+    let mut coords = vec![];
+    let mut offset = 0;
+    for word in text.split_whitespace() {
+        let start = offset + text[offset..].find(word).unwrap();
+        offset = start + word.len();
+        match word.parse::<isize>() {
+            Ok(n) => coords.push(n),
+            Err(_) => return Err(Error::syntax(lno, column + start, format!("invalid coordinate {}", word))),
+        }
+    }
+    if coords.len() != 2 {
+        return Err(Error::syntax(lno, column, format!("expected 2 coordinates, found {}", coords.len())))
+    }
+    Ok((coords[0], coords[1]))
+}
+
+fn read_life_105<L: LifeAlgorithm + ?Sized>(life_obj: &mut L, text: &str) -> Result<(), Error> {
+    // The top left corner of the current block and the row we are on within it
+    let mut corner = (0, 0);
+    let mut j = 0;
+
+    for (lno, line) in text.lines().enumerate() {
+        if line.starts_with("#P") {
+            corner = try!(parse_coordinates(&line[2..], lno+1, 3));
+            j = 0;
+            continue;
+        }
+        // Descriptions (#D), rules (#N, #R) and anything else starting with # are skipped
+        if line.starts_with('#') || line.trim() == "" {
+            continue;
+        }
+        for (i,c) in line.trim_right().chars().enumerate() {
+            match c {
+                '.' => (),
+                '*' => (*life_obj).set((corner.0 + i as isize, corner.1 + j), true),
+                _   => return Err(Error::syntax(lno+1, i+1, format!("invalid character {}", c))),
+            };
+        }
+        j += 1;
+    }
+    Ok(())
+}
+
+fn read_life_106<L: LifeAlgorithm + ?Sized>(life_obj: &mut L, text: &str) -> Result<(), Error> {
+    for (lno, line) in text.lines().enumerate() {
+        if line.starts_with('#') || line.trim() == "" {
+            continue;
+        }
+        let cell = try!(parse_coordinates(line, lno+1, 1));
+        (*life_obj).set(cell, true);
+    }
+    Ok(())
+}
+
+/// A line of a Macrocell file. Quadrants are in the order nw, ne, sw, se, with y increasing down
+/// the screen the way rows are written.
+#[derive(Clone, Debug)]
+pub enum MacrocellNode {
+    /// A level 3 node of two-state cells: 8 rows of 8 bits, each row with its leftmost cell in the
+    /// lowest bit
+    Square([u8; 8]),
+    /// A level 1 node of four cell states
+    States([u8; 4]),
+    /// A node of the given level, with the line numbers of its quadrants. 0 is an empty quadrant
+    Node(u64, [usize; 4]),
+}
+
+impl MacrocellNode {
+    pub fn level(&self) -> u64 {
+        match *self {
+            MacrocellNode::Square(_)      => 3,
+            MacrocellNode::States(_)      => 1,
+            MacrocellNode::Node(level, _) => level,
+        }
+    }
+}
+
+/// Parses the nodes of a Macrocell file, checking that every quadrant is an earlier node of the
+/// level below and every state is one of the rule's num_states. The root is the last node.
+pub fn parse_macrocell(text: &str, num_states: u8) -> Result<Vec<MacrocellNode>, Error> {
+    let mut nodes: Vec<MacrocellNode> = vec![];
+    for (lno, line) in text.lines().enumerate() {
+        let line = line.trim();
+        // Skip the [M2] line, comments, the rule (#R) and the generation (#G)
+        if line == "" || line.starts_with('[') || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('.') || line.starts_with('*') || line.starts_with('$') {
+            let mut rows = [0u8; 8];
+            let (mut x, mut y) = (0, 0);
+            for (i,c) in line.chars().enumerate() {
+                if y == 8 || (x == 8 && c != '$') {
+                    return Err(Error::syntax(lno+1, i+1, "more than 8x8 cells in a square".to_string()))
+                }
+                match c {
+                    '.' => x += 1,
+                    '*' => { rows[y] |= 1 << x; x += 1; },
+                    '$' => { y += 1; x = 0; },
+                    _   => return Err(Error::syntax(lno+1, i+1, format!("invalid character {}", c))),
+                }
+            }
+            nodes.push(MacrocellNode::Square(rows));
+            continue;
+        }
+
+        let mut numbers = vec![];
+        let mut offset = 0;
+        for word in line.split_whitespace() {
+            let start = offset + line[offset..].find(word).unwrap();
+            offset = start + word.len();
+            match word.parse::<usize>() {
+                Ok(n) => numbers.push((n, start+1)),
+                Err(_) => return Err(Error::syntax(lno+1, start+1, format!("invalid number {}", word))),
+            }
+        }
+        if numbers.len() != 5 {
+            return Err(Error::syntax(lno+1, 1, format!("expected a level and 4 quadrants, found {} numbers", numbers.len())))
+        }
+        let level = numbers[0].0 as u64;
+        let quadrants = [numbers[1].0, numbers[2].0, numbers[3].0, numbers[4].0];
+        if level == 1 {
+            if let Some(&(state, column)) = numbers[1..].iter().find(|&&(n, _)| n >= num_states as usize) {
+                return Err(Error::syntax(lno+1, column, format!("state {} is past the {} states of the rule", state, num_states)))
+            }
+            nodes.push(MacrocellNode::States([quadrants[0] as u8, quadrants[1] as u8, quadrants[2] as u8, quadrants[3] as u8]));
+            continue;
+        }
+        if level < 2 {
+            return Err(Error::syntax(lno+1, numbers[0].1, format!("invalid level {}", level)))
+        }
+        for &(n, column) in &numbers[1..] {
+            if n > nodes.len() {
+                return Err(Error::syntax(lno+1, column, format!("node {} is not defined yet", n)))
+            }
+            if n > 0 && nodes[n-1].level() != level-1 {
+                return Err(Error::syntax(lno+1, column, format!("node {} is not of level {}", n, level-1)))
+            }
+        }
+        nodes.push(MacrocellNode::Node(level, quadrants));
+    }
+    if nodes.is_empty() {
+        return Err(Error::syntax(1, 1, "no nodes".to_string()))
+    }
+    Ok(nodes)
+}
+
+// Reads a Macrocell file one cell at a time, for engines that don't keep a quadtree
+fn read_macrocell<L: LifeAlgorithm + ?Sized>(life_obj: &mut L, text: &str) -> Result<(), Error> {
+    let nodes = try!(parse_macrocell(text, (*life_obj).num_states()));
+    let root = nodes.len();
+    let level = nodes[root-1].level();
+    if level > (::std::mem::size_of::<isize>() * 8 - 2) as u64 {
+        return Err(Error::syntax(1, 1, "the pattern is too large to open with any algorithm but hashlife".to_string()))
+    }
+    let half = 1 << (level-1);
+    set_macrocell_node(life_obj, &nodes, root, -half, -half);
+    Ok(())
+}
+
+// Sets the cells of a node of a Macrocell file, given its line number and top left corner
+fn set_macrocell_node<L: LifeAlgorithm + ?Sized>(life_obj: &mut L, nodes: &[MacrocellNode], n: usize, left: isize, top: isize) {
+    if n == 0 {
+        return
+    }
+    match nodes[n-1] {
+        MacrocellNode::Square(ref rows) => {
+            for (j, row) in rows.iter().enumerate() {
+                for i in 0..8 {
+                    if row & (1 << i) != 0 {
+                        (*life_obj).set((left + i as isize, top + j as isize), true);
+                    }
+                }
+            }
+        },
+        MacrocellNode::States(ref states) => {
+            for (k, &state) in states.iter().enumerate() {
+                if state != 0 {
+                    (*life_obj).set_state((left + (k % 2) as isize, top + (k / 2) as isize), state);
+                }
+            }
+        },
+        MacrocellNode::Node(level, ref quadrants) => {
+            let half = 1 << (level-1);
+            for (k, &q) in quadrants.iter().enumerate() {
+                set_macrocell_node(life_obj, nodes, q, left + half * (k % 2) as isize, top + half * (k / 2) as isize);
+            }
+        },
+    }
+}
+
+// RLE lines are kept below this length, as Golly does
+const RLE_LINE_LENGTH: usize = 70;
+
+/// Returns the live cells of a pattern as RLE, with a #CXRLE line so that it's read back into the
+/// same place. States above 1 are written with letters, as in multi-state rules. Fails while the
+/// background of a B0 rule is alive.
+pub fn rle<L: LifeAlgorithm + ?Sized>(life_obj: &L) -> io::Result<String> {
+    try!(check_background(life_obj));
+    Ok(stored_rle(life_obj))
+}
+
+/// Returns the cells a life object stores as RLE, in the same way as rle. While the background of
+/// a B0 rule is alive these are its dead cells, written as o with nothing to say so, which is only
+/// right for sessions and recordings: they put back the generation, and with it the background.
+pub fn stored_rle<L: LifeAlgorithm + ?Sized>(life_obj: &L) -> String {
+    // Under a B0 rule the background may be alive, and the cells returned by live_cells are dead
+    let background = life_obj.background();
+    let mut cells: Vec<((isize, isize), u8)> = life_obj.live_cells()
+        .map(|cell| (cell, if background { 1 } else { life_obj.get_state(cell) }))
+        .collect();
+    cells.sort_by_key(|&((x,y), _)| (y,x));
+    if cells.is_empty() {
+        return "x = 0, y = 0\n!\n".to_string()
+    }
+    let multi_state = cells.iter().any(|&(_, state)| state > 1);
+    let x_min = cells.iter().map(|&((x,_), _)| x).min().unwrap();
+    let x_max = cells.iter().map(|&((x,_), _)| x).max().unwrap();
+    let (y_min, y_max) = ((cells[0].0).1, (cells[cells.len()-1].0).1);
+
+    // Runs of the same symbol, with dead cells at the end of a row left out
+    let mut runs: Vec<(usize, String)> = vec![];
+    let (mut x, mut y) = (x_min, y_min);
+    for ((cx, cy), state) in cells {
+        if cy > y {
+            runs.push(((cy - y) as usize, "$".to_string()));
+            x = x_min;
+            y = cy;
+        }
+        if cx > x {
+            runs.push(((cx - x) as usize, if multi_state { "." } else { "b" }.to_string()));
+        }
+        let symbol = if !multi_state {
+            "o".to_string()
+        } else if state <= 24 {
+            ((b'A' + state - 1) as char).to_string()
+        } else {
+            format!("{}{}", (b'p' + (state - 1) / 24 - 1) as char, (b'A' + (state - 1) % 24) as char)
+        };
+        if runs.last().map_or(false, |&(_, ref last)| *last == symbol) {
+            runs.last_mut().unwrap().0 += 1;
+        } else {
+            runs.push((1, symbol));
+        }
+        x = cx + 1;
+    }
+
+    let mut out = format!("#CXRLE Pos={},{}\nx = {}, y = {}\n", x_min, y_min, x_max - x_min + 1, y_max - y_min + 1);
+    let mut line = String::new();
+    for (count, symbol) in runs {
+        let run = if count == 1 { symbol } else { format!("{}{}", count, symbol) };
+        if line.len() + run.len() > RLE_LINE_LENGTH {
+            out.push_str(&line);
+            out.push('\n');
+            line.clear();
+        }
+        line.push_str(&run);
+    }
+    out.push_str(&line);
+    out.push_str("!\n");
+    out
+}
+
+/// Writes the live cells of a pattern to a file as RLE. Fails while the background of a B0 rule is
+/// alive.
+pub fn write_rle<L: LifeAlgorithm + ?Sized>(life_obj: &L, path: &Path) -> io::Result<()> {
+    let text = try!(rle(life_obj));
+    let mut f = try!(File::create(path));
+    f.write_all(text.as_bytes())
+}
+
+// The side length of the squares that Life 1.05 output is split into, so that sparse patterns
+// don't turn into one huge block
+const LIFE_105_BLOCK: isize = 32;
+
+// Life 1.05, Life 1.06 and RLE can only list live cells, which there are infinitely many of while
+// the background of a B0 rule is alive
+fn check_background<L: LifeAlgorithm + ?Sized>(life_obj: &L) -> io::Result<()> {
+    if life_obj.background() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "the background is alive on this generation, so there are infinitely many live cells to list"))
+    }
+    Ok(())
+}
+
+/// Writes the live cells of a pattern to a file as Life 1.05, one #P block per occupied square of
+/// the plane, under a #N line for Conway's rule or a #R line for any other Life-like rule. Rule
+/// tables have no Life 1.05 form, so they get neither. Fails while the background of a B0 rule is
+/// alive.
+pub fn write_life_105<L: LifeAlgorithm + ?Sized>(life_obj: &L, rule: Option<Rule>, path: &Path) -> io::Result<()> {
+    try!(check_background(life_obj));
+    let mut blocks: BTreeMap<(isize, isize), Vec<(isize, isize)>> = BTreeMap::new();
+    for (x,y) in life_obj.live_cells() {
+        let key = (div_floor(y, LIFE_105_BLOCK), div_floor(x, LIFE_105_BLOCK));
+        blocks.entry(key).or_insert(vec![]).push((x,y));
+    }
+
+    let mut f = try!(File::create(path));
+    try!(writeln!(f, "#Life 1.05"));
+    match rule {
+        Some(rule) if rule == Rule::conway() => try!(writeln!(f, "#N")),
+        Some(rule) => try!(writeln!(f, "#R {}", rule.to_survival_birth())),
+        None => (),
+    }
+    for (_, cells) in blocks {
+        let x_min = cells.iter().map(|c| c.0).min().unwrap();
+        let y_min = cells.iter().map(|c| c.1).min().unwrap();
+        let y_max = cells.iter().map(|c| c.1).max().unwrap();
+        try!(writeln!(f, "#P {} {}", x_min, y_min));
+        for y in y_min..y_max+1 {
+            let mut row: Vec<isize> = cells.iter().filter(|c| c.1 == y).map(|c| c.0 - x_min).collect();
+            row.sort();
+            let mut line = String::new();
+            for x in row {
+                while (line.len() as isize) < x {
+                    line.push('.');
+                }
+                line.push('*');
+            }
+            if line == "" {
+                line.push('.');
+            }
+            try!(writeln!(f, "{}", line));
+        }
+    }
+    Ok(())
+}
+
+/// Writes the live cells of a pattern to a file as Life 1.06. Fails while the background of a B0
+/// rule is alive.
+pub fn write_life_106<L: LifeAlgorithm + ?Sized>(life_obj: &L, path: &Path) -> io::Result<()> {
+    try!(check_background(life_obj));
+    let mut cells: Vec<(isize, isize)> = life_obj.live_cells().collect();
+    cells.sort_by_key(|&(x,y)| (y,x));
+
+    let mut f = try!(File::create(path));
+    try!(writeln!(f, "#Life 1.06"));
+    for (x,y) in cells {
+        try!(writeln!(f, "{} {}", x, y));
+    }
+    Ok(())
+}
+
+/// Division rounding towards negative infinity.
+fn div_floor(a: isize, b: isize) -> isize {
+    if a >= 0 { a / b } else { -((-a + b - 1) / b) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use num::BigInt;
+    use life_algorithms::{hashlife,sequential,table};
+    use rule_table::TableRule;
+
+    fn wireworld() -> Arc<TableRule> {
+        Arc::new(TableRule::parse(include_str!("../assets/rules/Wireworld.rule")).unwrap())
+    }
+
+    fn cells<L: LifeAlgorithm + ?Sized>(life_obj: &L) -> Vec<((isize, isize), u8)> {
+        let mut out: Vec<((isize, isize), u8)> = life_obj.live_cells().map(|cell| (cell, life_obj.get_state(cell))).collect();
+        out.sort();
+        out
+    }
+
+    #[test]
+    fn macrocell_round_trip() {
+        let path = Path::new("test.mc");
+        let mut life_obj = hashlife::Life::new();
+        read_pattern(&mut life_obj, "x = 3, y = 3\nbo$2bo$3o!\n", path).unwrap();
+        for &cell in &[(-40, -7), (100, 3), (5, 1000)] {
+            life_obj.set(cell, true);
+        }
+        life_obj.advance_by(3);
+        let text = life_obj.to_macrocell().unwrap();
+
+        let mut hashlife = hashlife::Life::new();
+        read_pattern(&mut hashlife, &text, path).unwrap();
+        assert_eq!(cells(&hashlife), cells(&life_obj));
+        // Reading a pattern leaves the generation alone
+        hashlife.set_generation(3);
+        assert_eq!(hashlife.to_macrocell().unwrap(), text);
+        let mut sequential = sequential::Life::new();
+        read_pattern(&mut sequential, &text, path).unwrap();
+        assert_eq!(cells(&sequential), cells(&life_obj));
+    }
+
+    #[test]
+    fn macrocell_round_trip_with_states_and_big_coordinates() {
+        let path = Path::new("test.mc");
+        let mut life_obj = hashlife::Life::with_table(wireworld());
+        read_pattern(&mut life_obj, "x = 4, y = 2\nABCC$2.C!\n", path).unwrap();
+        let far = BigInt::from(1) << 100;
+        life_obj.set_state_at(&far, &-far.clone(), 2);
+        let text = life_obj.to_macrocell().unwrap();
+
+        let mut read = hashlife::Life::with_table(wireworld());
+        read_pattern(&mut read, &text, path).unwrap();
+        assert_eq!(read.get_state_at(&far, &-far.clone()), 2);
+        assert_eq!(read.get_state_at(&far, &far), 0);
+        for (x, state) in (0..4).zip([1, 2, 3, 3].iter()) {
+            assert_eq!(read.get_state_at(&BigInt::from(x), &BigInt::from(0)), *state);
+        }
+        assert_eq!(read.get_state_at(&BigInt::from(2), &BigInt::from(1)), 3);
+        assert_eq!(read.to_macrocell().unwrap(), text);
+    }
+
+    fn syntax_column<T>(result: Result<T, Error>) -> (usize, usize) {
+        match result {
+            Err(Error::Syntax { line, column, .. }) => (line, column),
+            Err(e) => panic!("expected a syntax error, got {}", e),
+            Ok(_) => panic!("expected a syntax error"),
+        }
+    }
+
+    #[test]
+    fn rle_states_past_the_rule_are_rejected() {
+        let path = Path::new("test.rle");
+        let mut life_obj = table::Life::new(wireworld());
+        read_pattern(&mut life_obj, "x = 3, y = 1\nABC!\n", path).unwrap();
+        assert_eq!(life_obj.get_state((2, 0)), 3);
+        assert_eq!(syntax_column(read_pattern(&mut life_obj, "x = 3, y = 1\nA2D!\n", path)), (2, 3));
+        // The prefix y would take the state past 255
+        assert_eq!(syntax_column(read_pattern(&mut life_obj, "x = 1, y = 1\nyX!\n", path)), (2, 2));
+        assert_eq!(syntax_column(read_pattern(&mut sequential::Life::new(), "x = 2, y = 1\noB!\n", path)), (2, 2));
+    }
+
+    #[test]
+    fn life_105_names_the_rule() {
+        let path = ::std::env::temp_dir().join("game_of_life_rule.lif");
+        let mut life_obj = sequential::Life::new();
+        life_obj.set((0, 0), true);
+        life_obj.clean_up();
+        let header = |rule: Option<Rule>| {
+            write_life_105(&life_obj, rule, &path).unwrap();
+            let mut text = String::new();
+            File::open(&path).unwrap().read_to_string(&mut text).unwrap();
+            text.lines().nth(1).unwrap().to_string()
+        };
+        assert_eq!(header(Some(Rule::conway())), "#N");
+        assert_eq!(header(Some(Rule::parse("B36/S23").unwrap())), "#R 23/36");
+        // Without a Life-like rule the cells come straight after the first line
+        assert_eq!(header(None), "#P 0 0");
+        let _ = ::std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn patterns_are_not_written_with_a_live_background() {
+        let path = ::std::env::temp_dir().join("game_of_life_background.lif");
+        let mut life_obj = sequential::Life::with_rule(Rule::parse("B01/S").unwrap()).unwrap();
+        life_obj.set((0, 0), true);
+        life_obj.clean_up();
+        write_life_106(&life_obj, &path).unwrap();
+        life_obj.advance_by(1);
+        assert!(life_obj.background());
+        assert_eq!(write_life_105(&life_obj, Some(Rule::parse("B01/S").unwrap()), &path).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(write_life_106(&life_obj, &path).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(write_rle(&life_obj, &path).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        // Sessions still get the stored cells, which are the dead ones
+        assert_eq!(stored_rle(&life_obj), "#CXRLE Pos=0,0\nx = 1, y = 1\no!\n");
+        let _ = ::std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn macrocell_states_past_the_rule_are_rejected() {
+        let path = Path::new("test.mc");
+        let text = "[M2] (test)\n1 1 2 3 7\n2 1 0 0 0\n";
+        assert_eq!(syntax_column(read_pattern(&mut table::Life::new(wireworld()), text, path)), (2, 9));
+        assert_eq!(syntax_column(read_pattern(&mut hashlife::Life::with_table(wireworld()), text, path)), (2, 9));
+        assert_eq!(syntax_column(read_pattern(&mut hashlife::Life::new(), "[M2] (test)\n1 1 2 0 0\n2 1 0 0 0\n", path)), (2, 5));
+    }
+}
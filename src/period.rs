@@ -0,0 +1,92 @@
+/*
+
+This file finds the period of an oscillator or spaceship by running it
+a generation at a time until its cells come back, in the same shape
+and states, possibly somewhere else. A still life is just a pattern
+with period 1 that doesn't move.
+
+The pattern is compared by its shape relative to the top left corner
+of its bounds, so a spaceship is found the same way as an oscillator,
+along with how far it moved. Under a B0 rule the background is
+compared too, since the cells stored on odd generations are the dead
+ones (see rules.rs).
+
+The life object is put back as it was when the search is done, using
+a checkpoint (see common.rs).
+
+*/
+
+use common::LifeAlgorithm;
+
+/// How a pattern repeats: after period generations it's back, moved by (dx, dy) cells.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Period {
+    pub period: u64,
+    pub dx: isize,
+    pub dy: isize,
+}
+
+impl Period {
+    pub fn is_still_life(&self) -> bool {
+        self.period == 1 && !self.is_spaceship()
+    }
+
+    pub fn is_spaceship(&self) -> bool {
+        self.dx != 0 || self.dy != 0
+    }
+
+    /// Describes the pattern, such as "still life", "period 2 oscillator" or "period 4 spaceship
+    /// moving by (1, 1)".
+    pub fn name(&self) -> String {
+        if self.is_still_life() {
+            "still life".to_string()
+        } else if self.is_spaceship() {
+            format!("period {} spaceship moving by ({}, {})", self.period, self.dx, self.dy)
+        } else {
+            format!("period {} oscillator", self.period)
+        }
+    }
+}
+
+// The cells of a pattern relative to the top left corner of its bounds, and where that corner is
+struct Shape {
+    corner: (isize, isize),
+    cells: Vec<((isize, isize), u8)>,
+    background: bool,
+}
+
+impl Shape {
+    fn of<L: LifeAlgorithm + ?Sized>(life_obj: &L) -> Shape {
+        let background = life_obj.background();
+        let cells: Vec<((isize, isize), u8)> = life_obj.live_cells()
+            .map(|cell| (cell, if background { 1 } else { life_obj.get_state(cell) }))
+            .collect();
+        let x_min = cells.iter().map(|c| (c.0).0).min().unwrap_or(0);
+        let y_min = cells.iter().map(|c| (c.0).1).min().unwrap_or(0);
+        let mut cells: Vec<((isize, isize), u8)> = cells.into_iter().map(|((x, y), s)| ((x - x_min, y - y_min), s)).collect();
+        cells.sort();
+        Shape { corner: (x_min, y_min), cells: cells, background: background }
+    }
+}
+
+/// Runs the pattern for up to max generations, and returns how it repeats if it comes back within
+/// them. A pattern that's empty, dies out or doesn't come back in time has no period. The life
+/// object is left as it was.
+pub fn find_period<L: LifeAlgorithm + ?Sized>(life_obj: &mut L, max: u64) -> Option<Period> {
+    let first = Shape::of(life_obj);
+    if first.cells.is_empty() {
+        return None
+    }
+    let start = life_obj.checkpoint();
+    let mut found = None;
+    for period in 1..max + 1 {
+        life_obj.advance_by(1);
+        let shape = Shape::of(life_obj);
+        if shape.cells == first.cells && shape.background == first.background {
+            found = Some(Period { period: period, dx: shape.corner.0 - first.corner.0, dy: shape.corner.1 - first.corner.1 });
+            break;
+        }
+    }
+    life_obj.restore(&start);
+    found
+}
@@ -0,0 +1,176 @@
+/*
+
+This file looks for a predecessor of a pattern: cells that evolve
+into it in one generation, or a given number of them. If there are
+none the pattern can only ever be drawn, never reached, which makes
+it a Garden of Eden.
+
+The pattern is a region of cells, each of which has to end up alive
+or dead as it is in the pattern. Whatever ends up outside the region
+doesn't matter. Going back a generation, the cells that matter grow
+by one on every side, as those are all that can affect the region.
+
+The question is put to a SAT solver (see https://docs.rs/varisat).
+Each cell that matters in each generation before the last becomes a
+boolean variable, and the rule becomes clauses: for a cell and the
+eight around it, every combination of their states that would give
+the cell the wrong state in the next generation is ruled out. The
+cells in the last generation are known, so the clauses about them
+only mention the generation before. If the solver can satisfy all of
+them, the first generation is a predecessor. If it proves they can't
+be, there isn't one.
+
+*/
+
+use std::collections::HashSet;
+
+use varisat::{ExtendFormula,Lit,Solver};
+
+use common::Bounds;
+use error::Error;
+use rules::Rule;
+
+/// The most cells that can matter in the first generation, as the search gets slow past that
+pub const MAX_AREA: isize = 2500;
+
+// The variables for the cells that matter in one generation
+struct Layer {
+    bounds: Bounds,
+    lits: Vec<Lit>,
+}
+
+impl Layer {
+    fn new(solver: &mut Solver, bounds: Bounds) -> Layer {
+        let area = (bounds.x_max - bounds.x_min + 1) * (bounds.y_max - bounds.y_min + 1);
+        let lits = (0..area).map(|_| solver.new_lit()).collect();
+        Layer { bounds: bounds, lits: lits }
+    }
+
+    fn lit(&self, x: isize, y: isize) -> Lit {
+        let width = self.bounds.x_max - self.bounds.x_min + 1;
+        self.lits[((y - self.bounds.y_min) * width + x - self.bounds.x_min) as usize]
+    }
+}
+
+/// Returns the bounds with by more cells on every side
+pub fn grown(bounds: &Bounds, by: isize) -> Bounds {
+    Bounds { x_min: bounds.x_min - by, x_max: bounds.x_max + by, y_min: bounds.y_min - by, y_max: bounds.y_max + by }
+}
+
+/// Finds cells that evolve in the given number of generations into the live cells of target
+/// within the region, with every other cell in the region dead. Returns None if there aren't any,
+/// which for one generation makes the region a Garden of Eden. B0 rules aren't supported.
+pub fn find_predecessor(rule: Rule, target: &[(isize, isize)], region: &Bounds, generations: u64) -> Result<Option<Vec<(isize, isize)>>, Error> {
+    if rule.is_b0() {
+        return Err(Error::Rule("Predecessors can't be found under B0 rules".to_string()))
+    }
+    if generations < 1 {
+        return Err(Error::Argument("Predecessors are at least 1 generation back".to_string()))
+    }
+    let first = grown(region, generations as isize);
+    let area = (first.x_max - first.x_min + 1) * (first.y_max - first.y_min + 1);
+    if area > MAX_AREA {
+        return Err(Error::Argument(format!("The search would cover {} cells, and can cover at most {}. Try a smaller region or fewer generations", area, MAX_AREA)))
+    }
+
+    let alive: HashSet<(isize, isize)> = target.iter().cloned().filter(|&(x, y)| region.contains(x, y)).collect();
+    let mut solver = Solver::new();
+    let layers: Vec<Layer> = (0..generations).map(|t| Layer::new(&mut solver, grown(region, (generations - t) as isize))).collect();
+
+    for t in 0..generations as usize {
+        let before = &layers[t];
+        let after = if t + 1 < layers.len() { Some(&layers[t + 1]) } else { None };
+        let bounds = after.map_or(region.clone(), |layer| layer.bounds.clone());
+        for y in bounds.y_min..bounds.y_max + 1 {
+            for x in bounds.x_min..bounds.x_max + 1 {
+                // The cell itself comes first, then its neighbours
+                let mut inputs = vec![before.lit(x, y)];
+                for dy in -1..2 {
+                    for dx in -1..2 {
+                        if dx != 0 || dy != 0 {
+                            inputs.push(before.lit(x + dx, y + dy));
+                        }
+                    }
+                }
+                for states in 0..1u32 << 9 {
+                    let neighbours = (states >> 1).count_ones() as u8;
+                    let next = rule.next_value(states & 1 != 0, neighbours);
+                    // The clause holds unless the inputs are in exactly these states
+                    let mut clause: Vec<Lit> = inputs.iter().enumerate()
+                        .map(|(i, &lit)| if states & (1 << i) != 0 { !lit } else { lit })
+                        .collect();
+                    match after {
+                        Some(layer) => {
+                            let lit = layer.lit(x, y);
+                            clause.push(if next { lit } else { !lit });
+                        },
+                        None => if next == alive.contains(&(x, y)) {
+                            continue;
+                        },
+                    }
+                    solver.add_clause(&clause);
+                }
+            }
+        }
+    }
+
+    let satisfiable = try!(solver.solve().map_err(|e| Error::Argument(format!("The SAT solver failed: {}", e))));
+    if !satisfiable {
+        return Ok(None)
+    }
+    let model: HashSet<Lit> = solver.model().unwrap_or(vec![]).into_iter().collect();
+    let layer = &layers[0];
+    let mut cells = vec![];
+    for y in first.y_min..first.y_max + 1 {
+        for x in first.x_min..first.x_max + 1 {
+            if model.contains(&layer.lit(x, y)) {
+                cells.push((x, y));
+            }
+        }
+    }
+    Ok(Some(cells))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::LifeAlgorithm;
+    use life_algorithms::sequential;
+
+    // Runs cells on and returns the live ones in the region
+    fn run(cells: &[(isize, isize)], generations: u64, region: &Bounds) -> Vec<(isize, isize)> {
+        let mut life_obj = sequential::Life::new();
+        for &cell in cells {
+            life_obj.set(cell, true);
+        }
+        life_obj.clean_up();
+        life_obj.advance_by(generations);
+        let mut out: Vec<(isize, isize)> = life_obj.live_cells().filter(|&(x, y)| region.contains(x, y)).collect();
+        out.sort();
+        out
+    }
+
+    #[test]
+    fn block_has_a_predecessor() {
+        let block = vec![(0, 0), (0, 1), (1, 0), (1, 1)];
+        let region = Bounds { x_min: -1, x_max: 2, y_min: -1, y_max: 2 };
+        let found = find_predecessor(Rule::conway(), &block, &region, 1).unwrap().unwrap();
+        assert_eq!(run(&found, 1, &region), block);
+    }
+
+    #[test]
+    fn glider_two_generations_back() {
+        let glider = vec![(0, 2), (1, 0), (1, 2), (2, 1), (2, 2)];
+        let region = Bounds { x_min: -1, x_max: 3, y_min: -1, y_max: 3 };
+        let found = find_predecessor(Rule::conway(), &glider, &region, 2).unwrap().unwrap();
+        assert_eq!(run(&found, 2, &region), glider);
+    }
+
+    #[test]
+    fn nothing_comes_before_a_cell_where_nothing_lives() {
+        let rule = Rule::parse("B/S").unwrap();
+        let region = Bounds::new();
+        assert_eq!(find_predecessor(rule, &[(0, 0)], &region, 1).unwrap(), None);
+        assert!(find_predecessor(rule, &[], &region, 1).unwrap().is_some());
+    }
+}
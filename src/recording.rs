@@ -0,0 +1,534 @@
+/*
+
+This file records what the GUI does to the grid in an event log, and
+plays the log back without a window against any algorithm, so that a
+bug seen while editing a running pattern can be reproduced, and then
+kept as a regression test.
+
+An event log starts with "key: value" lines, ended by a blank line,
+like a session file (see session.rs):
+
+    #Rusty Game of Life event log
+    algorithm: parallel
+    rule: B3/S23
+    checkpoint-interval: 100
+
+followed by one event per line, each starting with the generation the
+grid was at when it happened:
+
+    0 load 2          the next 2 lines are a pattern that replaced the grid
+    0 check 7 4511... the population, and a digest of the cells
+    0 key D1          a key was pressed
+    0 pause           the simulation was paused (or resumed)
+    0 set 10 -4 1     a cell was set dead (0) or alive (1)
+    0 state 3 2 5     a cell was set to a state of a multi-state rule
+    0 clean           the algorithm cleaned up after cells were set
+    0 clear           the grid was emptied
+    0 edited          an edit finished, replacing the history after it
+    0 step 1          the grid advanced some generations
+    12 seek 4         the grid went back, or forward, to a generation
+    4 switch hashlife the algorithm was switched, keeping the grid
+    4 rule table Wireworld
+                      the algorithm and rule were replaced, emptying the grid
+
+Edits are caught by Recording, which wraps the life object the GUI
+edits and notes every cell set on it, so random fills and stamps are
+recorded as the cells they set. Keys and pauses are only there to
+make the log easier to follow, and playback skips them.
+
+Playback runs the events on a fresh life object, with its own history
+to seek in (see history.rs), and stops at the first check that
+doesn't match, or the first event that happens at a different
+generation than it was recorded at. Checks follow every step and
+edit, which slows recording down for very large patterns.
+
+*/
+
+use std::fs::File;
+use std::io::{self,BufWriter};
+use std::io::prelude::*;
+use std::path::{Path,PathBuf};
+
+use num::{BigUint,Zero};
+
+use common::{Bounds,CellIter,Checkpoint,LifeAlgorithm,transfer};
+use error::Error;
+use history::History;
+use life_algorithms::BoxedLife;
+use patterns::{read_pattern,stored_rle};
+use tracking::CellTracker;
+
+const HEADER: &'static str = "#Rusty Game of Life event log";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The grid was replaced by a pattern, in any format patterns.rs can read
+    Load(String),
+    /// The population and digest (see digest) of the grid
+    Check(usize, u64),
+    Key(String),
+    /// The simulation was paused (true) or resumed (false)
+    Pause(bool),
+    Set((isize, isize), bool),
+    State((isize, isize), u8),
+    CleanUp,
+    Clear,
+    Edited,
+    Step(BigUint),
+    Seek(BigUint),
+    Switch(String),
+    /// The algorithm and the rule were replaced, and the grid emptied
+    Rule(String, String),
+}
+
+impl Event {
+    // Writes the event as a line of the log, or more than one for a pattern
+    fn write<W: Write>(&self, generation: &BigUint, out: &mut W) -> io::Result<()> {
+        match *self {
+            Event::Load(ref pattern) => {
+                let pattern = pattern.trim_right();
+                try!(writeln!(out, "{} load {}", generation, pattern.lines().count()));
+                writeln!(out, "{}", pattern)
+            },
+            Event::Check(population, digest) => writeln!(out, "{} check {} {}", generation, population, digest),
+            Event::Key(ref key)              => writeln!(out, "{} key {}", generation, key),
+            Event::Pause(true)               => writeln!(out, "{} pause", generation),
+            Event::Pause(false)              => writeln!(out, "{} resume", generation),
+            Event::Set((x, y), value)        => writeln!(out, "{} set {} {} {}", generation, x, y, value as u8),
+            Event::State((x, y), state)      => writeln!(out, "{} state {} {} {}", generation, x, y, state),
+            Event::CleanUp                   => writeln!(out, "{} clean", generation),
+            Event::Clear                     => writeln!(out, "{} clear", generation),
+            Event::Edited                    => writeln!(out, "{} edited", generation),
+            Event::Step(ref count)           => writeln!(out, "{} step {}", generation, count),
+            Event::Seek(ref target)          => writeln!(out, "{} seek {}", generation, target),
+            Event::Switch(ref algorithm)     => writeln!(out, "{} switch {}", generation, algorithm),
+            Event::Rule(ref algorithm, ref rule) => writeln!(out, "{} rule {} {}", generation, algorithm, rule),
+        }
+    }
+}
+
+/// Returns a digest of the cells of a life object that doesn't depend on the order live_cells
+/// returns them in, so that different algorithms can be compared.
+pub fn digest<L: LifeAlgorithm + ?Sized>(life_obj: &L) -> u64 {
+    let background = life_obj.background();
+    let mut sum = background as u64;
+    for cell in life_obj.live_cells() {
+        let state = if background { 1 } else { life_obj.get_state(cell) as u64 };
+        sum = sum.wrapping_add(mix(mix(cell.0 as u64) ^ cell.1 as u64 ^ (state << 56)));
+    }
+    sum
+}
+
+// Scrambles the bits of a number (the finalizer of SplitMix64)
+fn mix(x: u64) -> u64 {
+    let x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// Writes an event log as the GUI runs.
+pub struct Recorder {
+    path: PathBuf,
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    /// Starts an event log for a life object run with the given algorithm, rule and checkpoint
+    /// interval, beginning with its pattern.
+    pub fn create<L: LifeAlgorithm + ?Sized>(path: &Path, life_obj: &L, algorithm: &str, rule: &str, interval: &BigUint) -> Result<Recorder, Error> {
+        let file = try!(File::create(path).map_err(|e| Error::Io(path.to_path_buf(), e)));
+        let mut recorder = Recorder { path: path.to_path_buf(), out: BufWriter::new(file) };
+        let header = format!("{}\nalgorithm: {}\nrule: {}\ncheckpoint-interval: {}\n\n", HEADER, algorithm, rule, interval);
+        try!(recorder.out.write_all(header.as_bytes()).map_err(|e| Error::Io(path.to_path_buf(), e)));
+        try!(recorder.load(life_obj));
+        Ok(recorder)
+    }
+
+    /// Notes an event that happened at a generation.
+    pub fn record(&mut self, generation: &BigUint, event: &Event) -> Result<(), Error> {
+        event.write(generation, &mut self.out).map_err(|e| Error::Io(self.path.clone(), e))
+    }
+
+    /// Notes that the grid of a life object was replaced, along with its new pattern.
+    pub fn load<L: LifeAlgorithm + ?Sized>(&mut self, life_obj: &L) -> Result<(), Error> {
+        let pattern = life_obj.to_macrocell().unwrap_or_else(|| stored_rle(life_obj));
+        try!(self.record(&life_obj.get_big_generation(), &Event::Load(pattern)));
+        self.check(life_obj)
+    }
+
+    /// Notes the events of edits made through a Recording. Once an edit is finished, that's noted
+    /// too, followed by a check.
+    pub fn edits<L: LifeAlgorithm + ?Sized>(&mut self, life_obj: &L, events: &[Event], finished: bool) -> Result<(), Error> {
+        let generation = life_obj.get_big_generation();
+        for event in events {
+            try!(self.record(&generation, event));
+        }
+        if finished {
+            try!(self.record(&generation, &Event::Edited));
+            try!(self.check(life_obj));
+        }
+        Ok(())
+    }
+
+    /// Notes the population and digest of a life object for playback to compare against, and
+    /// writes out everything noted so far, so the log survives a crash.
+    pub fn check<L: LifeAlgorithm + ?Sized>(&mut self, life_obj: &L) -> Result<(), Error> {
+        try!(self.record(&life_obj.get_big_generation(), &Event::Check(life_obj.population(), digest(life_obj))));
+        self.out.flush().map_err(|e| Error::Io(self.path.clone(), e))
+    }
+}
+
+/// A life object that notes every cell set on it, and everything else that changes its grid, so
+/// the edits made through it can be recorded.
+pub struct Recording<'a, L: LifeAlgorithm + ?Sized + 'a> {
+    life_obj: &'a mut L,
+    events: Vec<Event>,
+}
+
+impl<'a, L: LifeAlgorithm + ?Sized> Recording<'a, L> {
+    pub fn new(life_obj: &'a mut L) -> Recording<'a, L> {
+        Recording { life_obj: life_obj, events: vec![] }
+    }
+
+    /// Returns what was done to the life object, in order.
+    pub fn events(self) -> Vec<Event> {
+        self.events
+    }
+}
+
+impl<'a, L: LifeAlgorithm + ?Sized> LifeAlgorithm for Recording<'a, L> {
+    fn advance_by(&mut self, count: u64) {
+        self.events.push(Event::Step(BigUint::from(count)));
+        self.life_obj.advance_by(count)
+    }
+    fn set(&mut self, cell: (isize, isize), value: bool) {
+        self.events.push(Event::Set(cell, value));
+        self.life_obj.set(cell, value)
+    }
+    fn clean_up(&mut self) {
+        self.events.push(Event::CleanUp);
+        self.life_obj.clean_up()
+    }
+    fn clear(&mut self) {
+        self.events.push(Event::Clear);
+        self.life_obj.clear()
+    }
+    fn advance_by_big(&mut self, count: &BigUint) {
+        self.events.push(Event::Step(count.clone()));
+        self.life_obj.advance_by_big(count)
+    }
+    fn set_state(&mut self, cell: (isize, isize), state: u8) {
+        self.events.push(Event::State(cell, state));
+        self.life_obj.set_state(cell, state)
+    }
+    fn get_generation(&self) -> u64 { self.life_obj.get_generation() }
+    fn set_generation(&mut self, generation: u64) { self.life_obj.set_generation(generation) }
+    fn get_big_generation(&self) -> BigUint { self.life_obj.get_big_generation() }
+    fn set_big_generation(&mut self, generation: &BigUint) { self.life_obj.set_big_generation(generation) }
+    fn get_bounds(&self) -> Bounds { self.life_obj.get_bounds() }
+    fn get_value(&self, cell: (isize, isize)) -> bool { self.life_obj.get_value(cell) }
+    fn live_cells<'b>(&'b self) -> CellIter<'b> { self.life_obj.live_cells() }
+    fn population(&self) -> usize { self.life_obj.population() }
+    fn live_cells_in<'b>(&'b self, region: &Bounds) -> CellIter<'b> { self.life_obj.live_cells_in(region) }
+    fn population_in(&self, region: &Bounds) -> usize { self.life_obj.population_in(region) }
+    fn get_state(&self, cell: (isize, isize)) -> u8 { self.life_obj.get_state(cell) }
+    fn num_states(&self) -> u8 { self.life_obj.num_states() }
+    fn background(&self) -> bool { self.life_obj.background() }
+    fn to_macrocell(&self) -> Option<String> { self.life_obj.to_macrocell() }
+    fn from_macrocell(&mut self, text: &str) -> Option<Result<(), Error>> { self.life_obj.from_macrocell(text) }
+    fn checkpoint(&self) -> Checkpoint { self.life_obj.checkpoint() }
+    fn restore(&mut self, checkpoint: &Checkpoint) { self.life_obj.restore(checkpoint) }
+    fn set_tracking(&mut self, on: bool) -> bool { self.life_obj.set_tracking(on) }
+    fn tracker(&self) -> Option<&CellTracker> { self.life_obj.tracker() }
+}
+
+/// An event log read back from a file.
+pub struct EventLog {
+    pub algorithm: String,
+    pub rule: String,
+    pub checkpoint_interval: BigUint,
+    /// The events, with the line each starts on and the generation it happened at
+    events: Vec<(usize, BigUint, Event)>,
+}
+
+impl EventLog {
+    /// Reads an event log.
+    pub fn read(path: &Path) -> Result<EventLog, Error> {
+        let mut text = String::new();
+        if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut text)) {
+            return Err(Error::Io(path.to_path_buf(), e))
+        }
+        EventLog::parse(&text).map_err(|e| e.in_file(&path.to_path_buf()))
+    }
+
+    fn parse(text: &str) -> Result<EventLog, Error> {
+        let (mut algorithm, mut rule, mut interval) = (None, None, None);
+        let mut lines = text.lines().enumerate();
+        while let Some((lno, line)) = lines.next() {
+            let line = line.trim();
+            if line == "" {
+                break;
+            }
+            if line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.find(':') {
+                Some(i) => (line[..i].trim(), line[i+1..].trim()),
+                None => return Err(Error::syntax(lno+1, 1, "expected a key and a value".to_string())),
+            };
+            match key {
+                "algorithm"           => algorithm = Some(value.to_string()),
+                "rule"                => rule = Some(value.to_string()),
+                "checkpoint-interval" => match value.parse::<BigUint>() {
+                    Ok(ref n) if !n.is_zero() => interval = Some(n.clone()),
+                    _ => return Err(Error::syntax(lno+1, 1, format!("invalid checkpoint-interval {}", value))),
+                },
+                _ => return Err(Error::syntax(lno+1, 1, format!("unknown key {}", key))),
+            }
+        }
+
+        let mut events = vec![];
+        while let Some((lno, line)) = lines.next() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            let invalid = || Error::syntax(lno+1, 1, format!("invalid event {}", line.trim()));
+            if words.len() < 2 {
+                return Err(invalid())
+            }
+            let generation = try!(words[0].parse::<BigUint>().map_err(|_| invalid()));
+            let args = &words[2..];
+            let number = |i: usize| args.get(i).and_then(|a| a.parse::<isize>().ok()).ok_or_else(&invalid);
+            let event = match (words[1], args.len()) {
+                ("load", 1) => {
+                    let count = try!(args[0].parse::<usize>().map_err(|_| invalid()));
+                    let pattern: Vec<&str> = lines.by_ref().take(count).map(|(_, l)| l).collect();
+                    if pattern.len() < count {
+                        return Err(Error::syntax(lno+1, 1, format!("the log ends inside a pattern of {} lines", count)))
+                    }
+                    Event::Load(pattern.join("\n"))
+                },
+                ("check", 2) => Event::Check(try!(args[0].parse().map_err(|_| invalid())), try!(args[1].parse().map_err(|_| invalid()))),
+                ("key", 1)    => Event::Key(args[0].to_string()),
+                ("pause", 0)  => Event::Pause(true),
+                ("resume", 0) => Event::Pause(false),
+                ("set", 3)    => Event::Set((try!(number(0)), try!(number(1))), try!(number(2)) != 0),
+                ("state", 3)  => Event::State((try!(number(0)), try!(number(1))), try!(args[2].parse::<u8>().map_err(|_| invalid()))),
+                ("clean", 0)  => Event::CleanUp,
+                ("clear", 0)  => Event::Clear,
+                ("edited", 0) => Event::Edited,
+                ("step", 1)   => Event::Step(try!(args[0].parse().map_err(|_| invalid()))),
+                ("seek", 1)   => Event::Seek(try!(args[0].parse().map_err(|_| invalid()))),
+                ("switch", 1) => Event::Switch(args[0].to_string()),
+                // Rules can be paths, which may have spaces in them
+                ("rule", n) if n > 1 => Event::Rule(args[0].to_string(), args[1..].join(" ")),
+                _ => return Err(invalid()),
+            };
+            events.push((lno+1, generation, event));
+        }
+
+        let missing = |key: &str| Error::syntax(1, 1, format!("missing {}", key));
+        Ok(EventLog {
+            algorithm: try!(algorithm.ok_or(missing("algorithm"))),
+            rule: try!(rule.ok_or(missing("rule"))),
+            checkpoint_interval: try!(interval.ok_or(missing("checkpoint-interval"))),
+            events: events,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Plays the events back on life objects made by create from an algorithm name and a rule. The
+    /// log's own algorithm is used, switching wherever it did, unless one is given, which then runs
+    /// every event. Returns the life object at the end and how many checks matched. The path is
+    /// only used to report errors.
+    pub fn play<F>(&self, algorithm: Option<&str>, create: F, path: &Path) -> Result<(BoxedLife, usize), Error>
+        where F: Fn(&str, &str) -> Result<BoxedLife, Error>
+    {
+        let mut current = algorithm.unwrap_or(&self.algorithm).to_string();
+        let mut rule = self.rule.clone();
+        let mut life_obj = try!(create(&current, &rule));
+        let mut history = History::new(&life_obj, &self.checkpoint_interval);
+        let mut checks = 0;
+
+        for &(line, ref generation, ref event) in &self.events {
+            let mismatch = |message: String| Error::Mismatch(format!("{}:{}: {}", path.display(), line, message));
+            // Loading a pattern sets the generation, so it's the one event that can happen anywhere
+            let load = match *event { Event::Load(_) => true, _ => false };
+            if !load && life_obj.get_big_generation() != *generation {
+                return Err(mismatch(format!("the grid is at generation {}, but this was recorded at generation {}",
+                                            life_obj.get_big_generation(), generation)))
+            }
+            match *event {
+                Event::Load(ref pattern) => {
+                    // Under a B0 rule the pattern holds the cells as they're stored, like a session's,
+                    // so it's read in at generation 0 and the generation is set afterwards
+                    life_obj.set_big_generation(&BigUint::zero());
+                    try!(read_pattern(&mut life_obj, pattern, path).map_err(|e| match e {
+                        Error::Syntax { path, line: l, column, message } => Error::Syntax { path: path, line: line + l, column: column, message: message },
+                        other => other,
+                    }));
+                    life_obj.set_big_generation(generation);
+                    history = History::new(&life_obj, &self.checkpoint_interval);
+                },
+                Event::Check(population, digest_recorded) => {
+                    let found = (life_obj.population(), digest(&life_obj));
+                    if found != (population, digest_recorded) {
+                        let message = if found.0 != population {
+                            format!("the population is {}, but it was {} when recorded", found.0, population)
+                        } else {
+                            format!("the population is {} as recorded, but the cells are different", population)
+                        };
+                        return Err(mismatch(format!("{} at generation {}", message, generation)))
+                    }
+                    checks += 1;
+                },
+                Event::Key(_) | Event::Pause(_) => (),
+                Event::Set(cell, value)   => life_obj.set(cell, value),
+                Event::State(cell, state) => life_obj.set_state(cell, state),
+                Event::CleanUp            => life_obj.clean_up(),
+                Event::Clear              => life_obj.clear(),
+                Event::Edited             => history.edited(&life_obj),
+                Event::Step(ref count)    => {
+                    life_obj.advance_by_big(count);
+                    history.record(&life_obj);
+                },
+                Event::Seek(ref target)   => history.seek(&mut life_obj, target),
+                // A forced algorithm runs everything, so switches are skipped
+                Event::Switch(ref next) => if algorithm.is_none() {
+                    let mut new_life = try!(create(next, &rule));
+                    transfer(&life_obj, &mut new_life);
+                    life_obj = new_life;
+                    current = next.clone();
+                    history = History::new(&life_obj, &self.checkpoint_interval);
+                },
+                Event::Rule(ref next, ref next_rule) => {
+                    if algorithm.is_none() {
+                        current = next.clone();
+                    }
+                    rule = next_rule.clone();
+                    life_obj = try!(create(&current, &rule));
+                    history = History::new(&life_obj, &self.checkpoint_interval);
+                },
+            }
+        }
+        Ok((life_obj, checks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use num::One;
+    use life_algorithms::{self,RuleKind,Settings};
+    use rules::Rule;
+
+    fn create(name: &str, rule: &str) -> Result<BoxedLife, Error> {
+        let rule = try!(Rule::parse(rule));
+        try!(life_algorithms::find(name)).create(&Settings::new(RuleKind::Life(rule)))
+    }
+
+    // Records drawing a glider and running it for 8 generations, as the GUI would, and returns
+    // the log
+    fn record_glider(algorithm: &str) -> String {
+        let path = env::temp_dir().join(format!("game_of_life_glider_{}.log", algorithm));
+        let mut life_obj = create(algorithm, "B3/S23").unwrap();
+        {
+            let mut recorder = Recorder::create(&path, &life_obj, algorithm, "B3/S23", &BigUint::from(4u32)).unwrap();
+            let edits = {
+                let mut editing = Recording::new(&mut life_obj);
+                for &cell in &[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+                    editing.set(cell, true);
+                }
+                editing.clean_up();
+                editing.events()
+            };
+            recorder.edits(&life_obj, &edits, true).unwrap();
+            for _ in 0..8 {
+                let generation = life_obj.get_big_generation();
+                life_obj.advance_by(1);
+                recorder.record(&generation, &Event::Step(BigUint::one())).unwrap();
+                recorder.check(&life_obj).unwrap();
+            }
+        }
+        let mut text = String::new();
+        File::open(&path).unwrap().read_to_string(&mut text).unwrap();
+        let _ = ::std::fs::remove_file(&path);
+        text
+    }
+
+    fn play(text: &str, algorithm: Option<&str>) -> Result<(BoxedLife, usize), Error> {
+        EventLog::parse(text).unwrap().play(algorithm, create, Path::new("test.log"))
+    }
+
+    #[test]
+    fn events_are_read_back_as_written() {
+        let events = vec![
+            (0, Event::Load("#CXRLE Pos=0,-1\nx = 3, y = 2\nobo$3o!".to_string())),
+            (0, Event::Check(5, 1234567890123456789)),
+            (0, Event::Key("D1".to_string())),
+            (0, Event::Pause(true)),
+            (0, Event::Pause(false)),
+            (0, Event::Set((10, -4), true)),
+            (0, Event::Set((-3, 7), false)),
+            (0, Event::State((3, 2), 5)),
+            (0, Event::CleanUp),
+            (0, Event::Clear),
+            (0, Event::Edited),
+            (0, Event::Step(BigUint::one() << 80)),
+            (12, Event::Seek(BigUint::from(4u32))),
+            (4, Event::Switch("hashlife".to_string())),
+            (4, Event::Rule("table".to_string(), "my rules/Wireworld.rule".to_string())),
+        ];
+        let mut text = b"#Rusty Game of Life event log\nalgorithm: parallel\nrule: B3/S23\ncheckpoint-interval: 100\n\n".to_vec();
+        for &(generation, ref event) in &events {
+            event.write(&BigUint::from(generation as u32), &mut text).unwrap();
+        }
+        let log = EventLog::parse(&String::from_utf8(text).unwrap()).unwrap();
+        assert_eq!((&log.algorithm[..], &log.rule[..], log.checkpoint_interval.clone()), ("parallel", "B3/S23", BigUint::from(100u32)));
+        let read: Vec<(BigUint, Event)> = log.events.into_iter().map(|(_, generation, event)| (generation, event)).collect();
+        let written: Vec<(BigUint, Event)> = events.into_iter().map(|(generation, event)| (BigUint::from(generation as u32), event)).collect();
+        assert_eq!(read, written);
+    }
+
+    #[test]
+    fn recorded_edits_and_steps_play_back_on_every_engine() {
+        for &recorded_on in &["sequential", "hashlife"] {
+            let text = record_glider(recorded_on);
+            for &algorithm in &[None, Some("sequential"), Some("hashlife")] {
+                // A check after the load, the edit and each of the 8 steps
+                let (life_obj, checks) = play(&text, algorithm).unwrap();
+                assert_eq!(checks, 10, "recorded on {}, played on {:?}", recorded_on, algorithm);
+                assert_eq!(life_obj.get_generation(), 8);
+                assert!(life_obj.get_value((3, 2)) && life_obj.get_value((3, 4)));
+            }
+        }
+    }
+
+    #[test]
+    fn the_sample_log_plays_back_on_every_engine() {
+        let text = include_str!("../assets/logs/glider_and_block.log");
+        for &algorithm in &[None, Some("sequential"), Some("parallel"), Some("hashlife")] {
+            let (life_obj, checks) = play(text, algorithm).unwrap();
+            assert_eq!((checks, life_obj.population()), (11, 9), "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn a_changed_cell_is_a_mismatch() {
+        let text = record_glider("sequential");
+        assert!(text.contains("0 set 2 1 1\n"));
+        let changed = text.replace("0 set 2 1 1\n", "0 set 3 1 1\n");
+        for &algorithm in &[Some("sequential"), Some("hashlife")] {
+            match play(&changed, algorithm) {
+                Err(Error::Mismatch(_)) => (),
+                Err(e) => panic!("expected a mismatch, got {}", e),
+                Ok(_) => panic!("expected a mismatch on {:?}", algorithm),
+            }
+        }
+    }
+}
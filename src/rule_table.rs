@@ -0,0 +1,542 @@
+/*
+
+This file loads multi-state cellular automata from Golly .rule files,
+such as Wireworld. Only the @TABLE and @COLORS sections are read.
+
+A rule table lists transitions of the form C,N,NE,...,C' (the current
+state of a cell, the states of its neighbours and its next state),
+where any entry may be a variable standing for a set of states. As in
+Golly, a variable that appears more than once in a transition takes
+the same value everywhere, and a cell that matches no transition keeps
+its state. Each transition is expanded under the rule's symmetries
+when the table is loaded.
+
+Rules where empty space can come alive are rejected, since every
+engine assumes an infinite dead background.
+
+*/
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use error::Error;
+
+/// The offsets of the eight neighbours of a cell in the order N, NE, E, SE, S, SW, W, NW. Rows
+/// grow downwards, as in pattern files, so north is y-1.
+pub const MOORE_NEIGHBOURS: [(isize, isize); 8] = [(0, -1), (1, -1), (1, 0), (1, 1),
+                                                   (0, 1), (-1, 1), (-1, 0), (-1, -1)];
+
+// The largest lookup table precomputed for a rule, in entries
+const MAX_LOOKUP_SIZE: usize = 1 << 20;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Neighborhood {
+    VonNeumann, // N, E, S, W
+    Moore,      // N, NE, E, SE, S, SW, W, NW
+    Hexagonal,  // N, E, SE, S, W, NW on a square grid skewed so that NE and SW are not adjacent
+}
+
+impl Neighborhood {
+    /// Returns the positions in MOORE_NEIGHBOURS of the neighbours in this neighborhood, in the
+    /// order they are listed in a rule table.
+    fn moore_indices(&self) -> &'static [usize] {
+        match *self {
+            Neighborhood::VonNeumann => &[0, 2, 4, 6],
+            Neighborhood::Moore      => &[0, 1, 2, 3, 4, 5, 6, 7],
+            Neighborhood::Hexagonal  => &[0, 2, 3, 4, 6, 7],
+        }
+    }
+
+    /// Returns the permutations of the neighbour positions generated by the given symmetry, or
+    /// None if every permutation is allowed.
+    fn symmetries(&self, name: &str) -> Result<Option<Vec<Vec<usize>>>, Error> {
+        let n = self.moore_indices().len();
+        let rotate = |by: usize| -> Vec<Vec<usize>> {
+            (0..n).filter(|s| s % (n/by) == 0)
+                  .map(|s| (0..n).map(|i| (i + s) % n).collect())
+                  .collect()
+        };
+        let reflect = |perms: Vec<Vec<usize>>| -> Vec<Vec<usize>> {
+            let mut out = perms.clone();
+            for p in perms {
+                out.push((0..n).map(|i| p[(n - i) % n]).collect());
+            }
+            out
+        };
+        let out = match (*self, name) {
+            (_, "none")                                   => rotate(1),
+            (_, "permute")                                => return Ok(None),
+            (Neighborhood::Moore, "rotate8")              => rotate(8),
+            (Neighborhood::Moore, "rotate8reflect")       => reflect(rotate(8)),
+            (Neighborhood::Moore, "rotate4")              |
+            (Neighborhood::VonNeumann, "rotate4")         => rotate(4),
+            (Neighborhood::Moore, "rotate4reflect")       |
+            (Neighborhood::VonNeumann, "rotate4reflect")  => reflect(rotate(4)),
+            (Neighborhood::Moore, "reflect_horizontal")   |
+            (Neighborhood::VonNeumann, "reflect_horizontal") => reflect(rotate(1)),
+            (Neighborhood::Hexagonal, "rotate2")          => rotate(2),
+            (Neighborhood::Hexagonal, "rotate3")          => rotate(3),
+            (Neighborhood::Hexagonal, "rotate6")          => rotate(6),
+            (Neighborhood::Hexagonal, "rotate6reflect")   => reflect(rotate(6)),
+            _ => return Err(Error::Rule(format!("Symmetry {} is not supported for the {:?} neighborhood", name, self))),
+        };
+        Ok(Some(out))
+    }
+}
+
+/// One entry of a transition: a fixed state or a variable, given as an index into the rule's
+/// variables.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Term {
+    State(u8),
+    Var(usize),
+}
+
+#[derive(Clone, Debug)]
+struct Transition {
+    center: Term,
+    neighbours: Vec<Term>, // in the order of the neighborhood
+    output: Term,
+    permute: bool,         // the neighbours may match in any order
+}
+
+pub struct TableRule {
+    name: String,
+    num_states: u8,
+    neighborhood: Neighborhood,
+    variables: Vec<Vec<u8>>,
+    transitions: Vec<Transition>,
+    lookup: Option<Vec<u8>>,
+    colors: Vec<[f32; 4]>,
+}
+
+impl TableRule {
+    /// Reads a rule table from a Golly .rule file.
+    pub fn from_file(path: &Path) -> Result<TableRule, Error> {
+        let mut text = String::new();
+        if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut text)) {
+            return Err(Error::Io(path.to_path_buf(), e))
+        }
+        TableRule::parse(&text).map_err(|e| e.in_file(&path.to_path_buf()))
+    }
+
+    /// Parses the contents of a Golly .rule file.
+    pub fn parse(text: &str) -> Result<TableRule, Error> {
+        let mut name = String::new();
+        let mut section = "";
+        let mut num_states: Option<u8> = None;
+        let mut neighborhood = Neighborhood::Moore;
+        let mut symmetry = "none".to_string();
+        let mut var_names: HashMap<String, usize> = HashMap::new();
+        let mut variables: Vec<Vec<u8>> = vec![];
+        let mut raw_transitions: Vec<(usize, Vec<String>)> = vec![];
+        let mut color_lines: Vec<(usize, Vec<u32>)> = vec![];
+
+        for (lno, line) in text.lines().enumerate() {
+            let lno = lno + 1;
+            let line = match line.find('#') {
+                Some(i) => &line[..i],
+                None    => line,
+            }.trim();
+            if line.starts_with('@') {
+                let mut words = line.split_whitespace();
+                section = match words.next().unwrap() {
+                    "@RULE"   => { name = words.next().unwrap_or("").to_string(); "" },
+                    "@TABLE"  => "table",
+                    "@COLORS" => "colors",
+                    _         => "",
+                };
+                continue;
+            }
+            if line == "" {
+                continue;
+            }
+            match section {
+                "table" => {
+                    if let Some(i) = line.find(':') {
+                        let value = line[i+1..].trim();
+                        match line[..i].trim() {
+                            "n_states" => match value.parse::<u8>() {
+                                Ok(n) if n >= 2 => num_states = Some(n),
+                                _ => return Err(Error::syntax(lno, 1, format!("invalid number of states {}", value))),
+                            },
+                            "neighborhood" => neighborhood = match value {
+                                "vonNeumann" => Neighborhood::VonNeumann,
+                                "Moore"      => Neighborhood::Moore,
+                                "hexagonal"  => Neighborhood::Hexagonal,
+                                _ => return Err(Error::syntax(lno, 1, format!("unsupported neighborhood {}", value))),
+                            },
+                            "symmetries" => symmetry = value.to_string(),
+                            key => return Err(Error::syntax(lno, 1, format!("unknown setting {}", key))),
+                        }
+                    } else if line.starts_with("var ") {
+                        let states = match num_states {
+                            Some(n) => n,
+                            None    => return Err(Error::syntax(lno, 1, "n_states must come before variables".to_string())),
+                        };
+                        let def = &line[4..];
+                        let eq = match def.find('=') {
+                            Some(i) => i,
+                            None    => return Err(Error::syntax(lno, 1, "expected var name={...}".to_string())),
+                        };
+                        let var_name = def[..eq].trim().to_string();
+                        let body = def[eq+1..].trim().trim_left_matches('{').trim_right_matches('}');
+                        let mut values = vec![];
+                        for item in body.split(|c: char| c == ',' || c.is_whitespace()).filter(|s| *s != "") {
+                            match TableRule::parse_term(item, &var_names, states) {
+                                Some(Term::State(s)) => values.push(s),
+                                Some(Term::Var(v))   => values.extend(variables[v].iter().cloned()),
+                                None => return Err(Error::syntax(lno, 1, format!("unknown state or variable {}", item))),
+                            }
+                        }
+                        var_names.insert(var_name, variables.len());
+                        variables.push(values);
+                    } else {
+                        let mut items: Vec<String> = line.split(|c: char| c == ',' || c.is_whitespace())
+                                                         .filter(|s| *s != "")
+                                                         .map(|s| s.to_string())
+                                                         .collect();
+                        // With fewer than 11 states transitions may be written without separators
+                        if items.len() == 1 {
+                            items = items[0].chars().map(|c| c.to_string()).collect();
+                        }
+                        raw_transitions.push((lno, items));
+                    }
+                },
+                "colors" => {
+                    let mut values = vec![];
+                    for item in line.split_whitespace() {
+                        match item.parse::<u32>() {
+                            Ok(v) => values.push(v),
+                            Err(_) => return Err(Error::syntax(lno, 1, format!("invalid color value {}", item))),
+                        }
+                    }
+                    color_lines.push((lno, values));
+                },
+                _ => (),
+            }
+        }
+
+        let num_states = match num_states {
+            Some(n) => n,
+            None    => return Err(Error::Rule("The rule has no @TABLE section with n_states".to_string())),
+        };
+        let num_neighbours = neighborhood.moore_indices().len();
+        let perms = try!(neighborhood.symmetries(&symmetry));
+        let mut transitions = vec![];
+        for (lno, items) in raw_transitions {
+            if items.len() != num_neighbours + 2 {
+                return Err(Error::syntax(lno, 1, format!("expected {} entries in a transition, found {}",
+                                                         num_neighbours + 2, items.len())))
+            }
+            let mut terms = vec![];
+            for item in &items {
+                match TableRule::parse_term(item, &var_names, num_states) {
+                    Some(t) => terms.push(t),
+                    None    => return Err(Error::syntax(lno, 1, format!("unknown state or variable {}", item))),
+                }
+            }
+            let neighbours: Vec<Term> = terms[1..num_neighbours+1].to_vec();
+            match perms {
+                Some(ref perms) => for p in perms {
+                    transitions.push(Transition { center: terms[0],
+                                                  neighbours: p.iter().map(|&i| neighbours[i]).collect(),
+                                                  output: terms[num_neighbours+1],
+                                                  permute: false });
+                },
+                None => transitions.push(Transition { center: terms[0],
+                                                      neighbours: neighbours,
+                                                      output: terms[num_neighbours+1],
+                                                      permute: true }),
+            }
+        }
+
+        let mut colors = TableRule::default_colors(num_states);
+        for (lno, values) in color_lines {
+            let rgb = |v: &[u32]| [v[0] as f32/255.0, v[1] as f32/255.0, v[2] as f32/255.0, 1.0];
+            match values.len() {
+                4 if values[0] < num_states as u32 => colors[values[0] as usize] = rgb(&values[1..]),
+                6 => {
+                    // A gradient from the first color to the second over the live states
+                    let (from, to) = (rgb(&values[..3]), rgb(&values[3..]));
+                    for s in 1..num_states as usize {
+                        let t = if num_states > 2 { (s-1) as f32 / (num_states-2) as f32 } else { 0.0 };
+                        for c in 0..3 {
+                            colors[s][c] = from[c] + (to[c] - from[c])*t;
+                        }
+                    }
+                },
+                _ => return Err(Error::syntax(lno, 1, "expected 'state r g b' or 'r g b r g b'".to_string())),
+            }
+        }
+
+        let mut out = TableRule { name: name,
+                                  num_states: num_states,
+                                  neighborhood: neighborhood,
+                                  variables: variables,
+                                  transitions: transitions,
+                                  lookup: None,
+                                  colors: colors };
+        if out.next_state(0, &[0; 8]) != 0 {
+            return Err(Error::Rule(format!("Rule {} turns empty space on, which is not supported", out.name)))
+        }
+        out.build_lookup();
+        Ok(out)
+    }
+
+    /// Parses a single state or variable name.
+    fn parse_term(item: &str, var_names: &HashMap<String, usize>, num_states: u8) -> Option<Term> {
+        if let Some(&v) = var_names.get(item) {
+            Some(Term::Var(v))
+        } else {
+            match item.parse::<u8>() {
+                Ok(s) if s < num_states => Some(Term::State(s)),
+                _ => None,
+            }
+        }
+    }
+
+    /// Returns Golly's default colors: a gradient from red to yellow over the live states.
+    fn default_colors(num_states: u8) -> Vec<[f32; 4]> {
+        let mut out = vec![[1.0, 1.0, 1.0, 1.0]];
+        for s in 1..num_states {
+            let t = if num_states > 2 { (s-1) as f32 / (num_states-2) as f32 } else { 0.0 };
+            out.push([1.0, t, 0.0, 1.0]);
+        }
+        out
+    }
+
+    /// Precomputes the next state of every neighbourhood if there are few enough of them.
+    fn build_lookup(&mut self) {
+        let n = self.moore_len() + 1;
+        let size = (self.num_states as usize).checked_pow(n as u32);
+        if let Some(size) = size {
+            if size <= MAX_LOOKUP_SIZE {
+                let mut lookup = vec![0; size];
+                let mut cells = vec![0u8; n];
+                for index in 0..size {
+                    let mut rest = index;
+                    for c in cells.iter_mut() {
+                        *c = (rest % self.num_states as usize) as u8;
+                        rest /= self.num_states as usize;
+                    }
+                    lookup[index] = self.search(cells[0], &cells[1..]);
+                }
+                self.lookup = Some(lookup);
+            }
+        }
+    }
+
+    fn moore_len(&self) -> usize {
+        self.neighborhood.moore_indices().len()
+    }
+
+    /// Returns the name given in the @RULE line.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of states, including the dead state 0.
+    pub fn num_states(&self) -> u8 {
+        self.num_states
+    }
+
+    /// Returns the color of each state as RGBA.
+    pub fn colors(&self) -> &Vec<[f32; 4]> {
+        &self.colors
+    }
+
+    /// Returns the next state of a cell, given its state and the states of its neighbours in the
+    /// order of MOORE_NEIGHBOURS.
+    pub fn next_state(&self, center: u8, moore: &[u8; 8]) -> u8 {
+        let indices = self.neighborhood.moore_indices();
+        if let Some(ref lookup) = self.lookup {
+            let mut index = 0;
+            for &i in indices.iter().rev() {
+                index = index * self.num_states as usize + moore[i] as usize;
+            }
+            lookup[index * self.num_states as usize + center as usize]
+        } else {
+            let neighbours: Vec<u8> = indices.iter().map(|&i| moore[i]).collect();
+            self.search(center, &neighbours)
+        }
+    }
+
+    /// Finds the first transition matching the given cell and returns its output, or the current
+    /// state if none match.
+    fn search(&self, center: u8, neighbours: &[u8]) -> u8 {
+        let mut bound = vec![None; self.variables.len()];
+        for t in &self.transitions {
+            for b in bound.iter_mut() {
+                *b = None;
+            }
+            if !self.matches(t.center, center, &mut bound) {
+                continue;
+            }
+            let matched = if t.permute {
+                let mut used = vec![false; neighbours.len()];
+                self.match_permuted(&t.neighbours, neighbours, &mut used, &mut bound)
+            } else {
+                t.neighbours.iter().zip(neighbours.iter()).all(|(&term, &s)| self.matches(term, s, &mut bound))
+            };
+            if matched {
+                return match t.output {
+                    Term::State(s) => s,
+                    Term::Var(v)   => bound[v].unwrap_or(self.variables[v][0]),
+                }
+            }
+        }
+        center
+    }
+
+    /// Returns true if the state matches the term, binding the term's variable if it has one.
+    fn matches(&self, term: Term, state: u8, bound: &mut Vec<Option<u8>>) -> bool {
+        match term {
+            Term::State(s) => s == state,
+            Term::Var(v)   => match bound[v] {
+                Some(b) => b == state,
+                None if self.variables[v].contains(&state) => { bound[v] = Some(state); true },
+                None => false,
+            },
+        }
+    }
+
+    /// Returns true if the neighbours can be assigned to the terms in some order.
+    fn match_permuted(&self, terms: &[Term], neighbours: &[u8], used: &mut Vec<bool>,
+                      bound: &mut Vec<Option<u8>>) -> bool {
+        if terms.is_empty() {
+            return true
+        }
+        for i in 0..neighbours.len() {
+            if used[i] {
+                continue;
+            }
+            let saved = bound.clone();
+            if self.matches(terms[0], neighbours[i], bound) {
+                used[i] = true;
+                if self.match_permuted(&terms[1..], neighbours, used, bound) {
+                    return true
+                }
+                used[i] = false;
+            }
+            *bound = saved;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use common::LifeAlgorithm;
+    use life_algorithms::table;
+
+    fn wireworld() -> TableRule {
+        TableRule::parse(include_str!("../assets/rules/Wireworld.rule")).unwrap()
+    }
+
+    // Wireworld as Brian Silverman defined it
+    fn wireworld_next(center: u8, moore: &[u8; 8]) -> u8 {
+        let heads = moore.iter().filter(|&&s| s == 1).count();
+        match center {
+            1 => 2,
+            2 => 3,
+            3 if heads == 1 || heads == 2 => 1,
+            s => s,
+        }
+    }
+
+    // Runs cells on a generation by looking at every cell next to a non-empty one
+    fn wireworld_step(cells: &HashMap<(isize, isize), u8>) -> HashMap<(isize, isize), u8> {
+        let mut out = HashMap::new();
+        for (&(x, y), &state) in cells {
+            let mut moore = [0u8; 8];
+            for (n, &(dx, dy)) in moore.iter_mut().zip(MOORE_NEIGHBOURS.iter()) {
+                *n = cells.get(&(x + dx, y + dy)).cloned().unwrap_or(0);
+            }
+            out.insert((x, y), wireworld_next(state, &moore));
+        }
+        out
+    }
+
+    fn state_at(rule: &TableRule, center: u8, moore: [u8; 8]) -> u8 {
+        rule.next_state(center, &moore)
+    }
+
+    #[test]
+    fn wireworld_matches_its_definition_in_every_neighbourhood() {
+        let rule = wireworld();
+        let mut moore = [0u8; 8];
+        for index in 0..1 << 16 {
+            for (i, n) in moore.iter_mut().enumerate() {
+                *n = ((index >> (2 * i)) & 3) as u8;
+            }
+            for center in 0..4 {
+                assert_eq!(rule.next_state(center, &moore), wireworld_next(center, &moore), "{} {:?}", center, moore);
+            }
+        }
+    }
+
+    #[test]
+    fn wireworld_clock_steps_like_the_definition() {
+        let mut cells: HashMap<(isize, isize), u8> = HashMap::new();
+        // A loop of wire with an electron going round it, and a wire leading off it
+        for x in 0..6 {
+            cells.insert((x, 0), 3);
+            cells.insert((x, 2), 3);
+        }
+        cells.insert((-1, 1), 3);
+        cells.insert((6, 1), 3);
+        for x in 7..12 {
+            cells.insert((x, 1), 3);
+        }
+        cells.insert((1, 0), 1);
+        cells.insert((0, 0), 2);
+
+        let mut life_obj = table::Life::new(Arc::new(wireworld()));
+        for (&cell, &state) in &cells {
+            life_obj.set_state(cell, state);
+        }
+        life_obj.clean_up();
+        for generation in 1..30 {
+            cells = wireworld_step(&cells);
+            life_obj.advance_by(1);
+            for (&cell, &state) in &cells {
+                assert_eq!(life_obj.get_state(cell), state, "{:?} at generation {}", cell, generation);
+            }
+        }
+    }
+
+    #[test]
+    fn symmetries_expand_transitions() {
+        let table = |symmetries: &str, transition: &str| {
+            TableRule::parse(&format!("@RULE Test\n@TABLE\nn_states:2\nneighborhood:Moore\nsymmetries:{}\n{}\n", symmetries, transition)).unwrap()
+        };
+        // A birth with N and NE alive, listed in the order N, NE, E, SE, S, SW, W, NW
+        let birth = "0,1,1,0,0,0,0,0,0,1";
+        let n_ne = [1, 1, 0, 0, 0, 0, 0, 0];
+        let e_se = [0, 0, 1, 1, 0, 0, 0, 0];
+        let ne_e = [0, 1, 1, 0, 0, 0, 0, 0];
+        let nw_n = [1, 0, 0, 0, 0, 0, 0, 1];
+        let n_e = [1, 0, 1, 0, 0, 0, 0, 0];
+
+        let none = table("none", birth);
+        assert_eq!((state_at(&none, 0, n_ne), state_at(&none, 0, e_se), state_at(&none, 0, nw_n)), (1, 0, 0));
+        let rotate4 = table("rotate4", birth);
+        assert_eq!((state_at(&rotate4, 0, e_se), state_at(&rotate4, 0, ne_e), state_at(&rotate4, 0, nw_n)), (1, 0, 0));
+        let rotate8 = table("rotate8", birth);
+        assert_eq!((state_at(&rotate8, 0, ne_e), state_at(&rotate8, 0, nw_n), state_at(&rotate8, 0, n_e)), (1, 1, 0));
+        let reflect = table("reflect_horizontal", birth);
+        assert_eq!((state_at(&reflect, 0, n_ne), state_at(&reflect, 0, nw_n), state_at(&reflect, 0, e_se)), (1, 1, 0));
+        let rotate4reflect = table("rotate4reflect", birth);
+        assert_eq!((state_at(&rotate4reflect, 0, nw_n), state_at(&rotate4reflect, 0, ne_e), state_at(&rotate4reflect, 0, n_e)), (1, 1, 0));
+        let permute = table("permute", birth);
+        assert_eq!((state_at(&permute, 0, n_e), state_at(&permute, 0, [0, 0, 0, 0, 0, 1, 0, 1]), state_at(&permute, 0, [1, 1, 1, 0, 0, 0, 0, 0])), (1, 1, 0));
+        // A cell that matches nothing keeps its state
+        assert_eq!(state_at(&permute, 1, n_e), 1);
+    }
+}
@@ -0,0 +1,306 @@
+/*
+
+This file defines the outer-totalistic "Life-like" rules that the
+engines can simulate, written in the usual B/S notation
+(Conway's Game of Life is B3/S23).
+
+Rules with B0 (an empty cell with no live neighbours is born)
+turn the whole infinite background on after one generation, which
+none of the engines can represent directly. These are emulated
+with the standard strobing trick: even generations are stepped with
+one rule and odd generations with another, and on odd generations
+the engines store the complement of the real pattern so that the
+stored background always stays dead. B0 rules that also have S8
+never turn the background off again and are not supported.
+
+*/
+
+use std::fmt;
+
+use error::Error;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Rule {
+    birth: u16,    // bit n is set if a dead cell with n live neighbours is born
+    survival: u16, // bit n is set if a live cell with n live neighbours survives
+}
+
+impl Rule {
+    /// Returns the rule for Conway's Game of Life, B3/S23.
+    pub fn conway() -> Rule {
+        Rule { birth: 1 << 3, survival: (1 << 2) | (1 << 3) }
+    }
+
+    /// Parses a rule in B/S notation ("B3/S23", "b3s23") or the older S/B notation ("23/3").
+    pub fn parse(s: &str) -> Result<Rule, Error> {
+        let s = s.trim();
+        let lower = s.to_lowercase();
+        let (birth, survival) = if lower.starts_with('b') {
+            let rest = &lower[1..];
+            match rest.find('s') {
+                Some(i) => (rest[..i].trim_right_matches('/'), &rest[i+1..]),
+                None    => return Err(Error::Rule(format!("Rule {} is missing its S part", s))),
+            }
+        } else if let Some(i) = lower.find('/') {
+            (&lower[i+1..], &lower[..i])
+        } else {
+            return Err(Error::Rule(format!("Rule {} is not in B/S or S/B notation", s)));
+        };
+        Ok(Rule { birth: try!(Rule::parse_counts(birth, s)),
+                  survival: try!(Rule::parse_counts(survival, s)) })
+    }
+
+    fn parse_counts(counts: &str, rule: &str) -> Result<u16, Error> {
+        let mut out = 0;
+        for c in counts.chars() {
+            match c.to_digit(10) {
+                Some(n) if n <= 8 => out |= 1 << n,
+                _ => return Err(Error::Rule(format!("Invalid neighbour count {} in rule {}", c, rule))),
+            }
+        }
+        Ok(out)
+    }
+
+    /// Returns the next state of a cell given its current state and its number of live neighbours.
+    pub fn next_value(&self, alive: bool, neighbours: u8) -> bool {
+        let counts = if alive { self.survival } else { self.birth };
+        counts & (1 << neighbours) != 0
+    }
+
+    /// Returns the rule in the older S/B notation ("23/3"), as Life 1.05 files give it.
+    pub fn to_survival_birth(&self) -> String {
+        let counts = |bits: u16| (0..9).filter(|n| bits & (1 << n) != 0).map(|n| n.to_string()).collect::<String>();
+        format!("{}/{}", counts(self.survival), counts(self.birth))
+    }
+
+    /// Returns true if the rule turns empty space on, i.e. it contains B0.
+    pub fn is_b0(&self) -> bool {
+        self.birth & 1 != 0
+    }
+
+    /// Returns the rule taking a pattern to the complement of its successor under this rule.
+    fn inverting(&self) -> Rule {
+        Rule { birth: !self.birth & 0x1ff, survival: !self.survival & 0x1ff }
+    }
+
+    /// Returns the rule taking the complement of a pattern to its successor under this rule. A
+    /// stored cell with n live neighbours really has 8-n, and its stored state is inverted.
+    fn uninverting(&self) -> Rule {
+        let mut out = Rule { birth: 0, survival: 0 };
+        for n in 0..9 {
+            if self.survival & (1 << (8 - n)) != 0 { out.birth |= 1 << n; }
+            if self.birth & (1 << (8 - n)) != 0 { out.survival |= 1 << n; }
+        }
+        out
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "B"));
+        for n in 0..9 {
+            if self.birth & (1 << n) != 0 { try!(write!(f, "{}", n)); }
+        }
+        try!(write!(f, "/S"));
+        for n in 0..9 {
+            if self.survival & (1 << n) != 0 { try!(write!(f, "{}", n)); }
+        }
+        Ok(())
+    }
+}
+
+/// The rules an engine actually applies on even and odd generations. For rules without B0 these
+/// are both the rule itself. For B0 rules the even rule takes the real pattern to the complement
+/// of its successor and the odd rule takes that complement back to the real pattern, so neither
+/// of them contains B0.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct RulePhases {
+    rule: Rule,
+    phases: [Rule; 2],
+}
+
+impl RulePhases {
+    /// Returns the phases for the given rule, or an error if the rule cannot be emulated.
+    pub fn new(rule: Rule) -> Result<RulePhases, Error> {
+        if !rule.is_b0() {
+            Ok(RulePhases { rule: rule, phases: [rule, rule] })
+        } else if rule.survival & (1 << 8) != 0 {
+            Err(Error::Rule(format!("Rule {} has both B0 and S8, which is not supported", rule)))
+        } else {
+            Ok(RulePhases { rule: rule, phases: [rule.inverting(), rule.uninverting()] })
+        }
+    }
+
+    /// Returns the rule that was requested.
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    /// Returns true if the pattern is stored inverted on odd generations.
+    pub fn is_strobing(&self) -> bool {
+        self.rule.is_b0()
+    }
+
+    /// Returns which of the two phases is used to step from the given generation.
+    pub fn index(&self, generation: u64) -> usize {
+        if self.is_strobing() { (generation % 2) as usize } else { 0 }
+    }
+
+    /// Returns the rule used to step from the given generation.
+    pub fn at(&self, generation: u64) -> &Rule {
+        &self.phases[self.index(generation)]
+    }
+
+    /// Returns both phases, indexed as in index().
+    pub fn phases(&self) -> &[Rule; 2] {
+        &self.phases
+    }
+
+    /// Returns the state of the background at the given generation. The engines store each cell
+    /// XOR'ed with this value, so the stored background is always dead.
+    pub fn background(&self, generation: u64) -> bool {
+        self.is_strobing() && generation % 2 == 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use common::LifeAlgorithm;
+    use life_algorithms::{hashlife,parallel,sequential};
+
+    // Runs a rule on the infinite plane directly: every cell of a region that grows by one on each
+    // side every generation, and the background everywhere else
+    struct BruteForce {
+        rule: Rule,
+        min: (isize, isize),
+        max: (isize, isize),
+        alive: HashSet<(isize, isize)>,
+        background: bool,
+    }
+
+    impl BruteForce {
+        fn new(rule: Rule, cells: &[(isize, isize)]) -> BruteForce {
+            let min = (cells.iter().map(|c| c.0).min().unwrap(), cells.iter().map(|c| c.1).min().unwrap());
+            let max = (cells.iter().map(|c| c.0).max().unwrap(), cells.iter().map(|c| c.1).max().unwrap());
+            BruteForce { rule: rule, min: min, max: max, alive: cells.iter().cloned().collect(), background: false }
+        }
+
+        fn get(&self, (x, y): (isize, isize)) -> bool {
+            let inside = x >= self.min.0 && x <= self.max.0 && y >= self.min.1 && y <= self.max.1;
+            if inside { self.alive.contains(&(x, y)) } else { self.background }
+        }
+
+        // The cells of the region and two more on every side, where the background starts
+        fn cells(&self) -> Vec<(isize, isize)> {
+            let mut out = vec![];
+            for y in self.min.1 - 2..self.max.1 + 3 {
+                for x in self.min.0 - 2..self.max.0 + 3 {
+                    out.push((x, y));
+                }
+            }
+            out
+        }
+
+        fn step(&mut self) {
+            let (min, max) = ((self.min.0 - 1, self.min.1 - 1), (self.max.0 + 1, self.max.1 + 1));
+            let mut alive = HashSet::new();
+            for y in min.1..max.1 + 1 {
+                for x in min.0..max.0 + 1 {
+                    let mut count = 0;
+                    for dy in -1..2 {
+                        for dx in -1..2 {
+                            if (dx != 0 || dy != 0) && self.get((x + dx, y + dy)) {
+                                count += 1;
+                            }
+                        }
+                    }
+                    if self.rule.next_value(self.get((x, y)), count) {
+                        alive.insert((x, y));
+                    }
+                }
+            }
+            self.background = self.rule.next_value(self.background, if self.background { 8 } else { 0 });
+            self.min = min;
+            self.max = max;
+            self.alive = alive;
+        }
+    }
+
+    // A soup that's the same every time
+    fn soup() -> Vec<(isize, isize)> {
+        let mut seed: u32 = 12345;
+        let mut cells = vec![];
+        for y in 0..6 {
+            for x in 0..6 {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                if seed >> 16 & 1 != 0 {
+                    cells.push((x, y));
+                }
+            }
+        }
+        cells
+    }
+
+    // Runs the soup on an engine a generation at a time and then all at once, checking it against
+    // a brute force run. A macro, so it works with every engine's own type
+    macro_rules! check_engine {
+        ($create:expr, $rule:expr, $name:expr) => {{
+            let generations = 20;
+            let mut brute = BruteForce::new($rule, &soup());
+            let mut life_obj = $create;
+            for &cell in &soup() {
+                life_obj.set(cell, true);
+            }
+            life_obj.clean_up();
+            for generation in 1..generations + 1 {
+                brute.step();
+                life_obj.advance_by(1);
+                assert_eq!(life_obj.background(), brute.background, "{} under {} at generation {}", $name, $rule, generation);
+                for cell in brute.cells() {
+                    assert_eq!(life_obj.get_value(cell), brute.get(cell), "{:?} on {} under {} at generation {}", cell, $name, $rule, generation);
+                }
+            }
+
+            // Large steps go through the same strobing
+            let mut life_obj = $create;
+            for &cell in &soup() {
+                life_obj.set(cell, true);
+            }
+            life_obj.clean_up();
+            life_obj.advance_by(generations);
+            for cell in brute.cells() {
+                assert_eq!(life_obj.get_value(cell), brute.get(cell), "{:?} on {} under {} after {} generations at once", cell, $name, $rule, generations);
+            }
+        }};
+    }
+
+    #[test]
+    fn notations() {
+        assert_eq!(Rule::parse("B3/S23").unwrap(), Rule::conway());
+        assert_eq!(Rule::parse("b3s23").unwrap(), Rule::conway());
+        assert_eq!(Rule::parse("23/3").unwrap(), Rule::conway());
+        assert_eq!(Rule::parse("b36/s23").unwrap().to_string(), "B36/S23");
+        assert_eq!(Rule::parse("B36/S23").unwrap().to_survival_birth(), "23/36");
+        assert_eq!(Rule::parse("B0/S").unwrap().to_survival_birth(), "/0");
+        assert!(Rule::parse("B9/S23").is_err());
+        assert!(Rule::parse("B3").is_err());
+    }
+
+    #[test]
+    fn b0_with_s8_is_rejected() {
+        assert!(RulePhases::new(Rule::parse("B0/S8").unwrap()).is_err());
+        assert!(!RulePhases::new(Rule::parse("B0/S").unwrap()).unwrap().phases().iter().any(|r| r.is_b0()));
+    }
+
+    #[test]
+    fn b0_rules_match_a_brute_force_run_on_every_engine() {
+        for rule in &["B0/S", "B03/S23", "B01245/S0123", "B0123478/S0123467"] {
+            let rule = Rule::parse(rule).unwrap();
+            check_engine!(sequential::Life::with_rule(rule).unwrap(), rule, "sequential");
+            check_engine!(parallel::Life::with_rule(rule).unwrap(), rule, "parallel");
+            check_engine!(hashlife::Life::with_rule(rule).unwrap(), rule, "hashlife");
+        }
+    }
+}
@@ -0,0 +1,230 @@
+/*
+
+This file runs scripts written in Rhai (see https://rhai.rs) against a
+life object, so that experiments such as colliding two gliders at
+every offset don't each need their own copy of main.rs. Scripts run
+without a window with the script command, or in the GUI with X on the
+pattern being shown, like Golly's scripts.
+
+A script works on one grid, with the algorithm and rule it was
+started with, through these functions:
+
+    set(x, y, alive)        set a cell alive (true) or dead (false)
+    set_state(x, y, state)  set a cell to a state of a multi-state rule
+    get_value(x, y)         whether a cell is alive
+    get_state(x, y)         the state of a cell
+    advance_by(n)           run n generations
+    generation()            the current generation
+    population()            the number of live cells
+    live_cells()            the live cells, as an array of [x, y] pairs
+    clear()                 empty the grid and go back to generation 0
+    load(name)              read a pattern file, or one in assets/game_seeds
+    pattern(name)           a pattern from the library (see library.rs)
+    rotate(cells, turns)    turn cells clockwise by quarter turns
+    flip(cells)             flip cells from left to right
+    put(cells, x, y)        set cells alive with their top left corner at x, y
+    find_period(max)        how the grid repeats within max generations, as a
+                            map of period, dx and dy, or () if it doesn't
+
+Patterns are arrays of [x, y] pairs with their top left corner at
+(0, 0), so they can be built and changed with Rhai's own array
+functions as well. print writes a line to the terminal, and in the
+GUI the last line printed is shown in the overlay.
+
+*/
+
+use std::cell::RefCell;
+use std::mem;
+use std::path::{Path,PathBuf};
+use std::rc::Rc;
+
+use rhai::{Array,Dynamic,Engine,EvalAltResult,Map,INT};
+
+use common::LifeAlgorithm;
+use error::Error;
+use library::{Pattern,Transform};
+use life_algorithms::{sequential,BoxedLife};
+use patterns::read_seed_from_file;
+use period::find_period;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+// The grid a script works on. Setting cells one at a time is common in scripts, so they're only
+// cleaned up once something needs the grid to be consistent
+struct Grid {
+    life_obj: BoxedLife,
+    dirty: bool,
+}
+
+impl Grid {
+    fn get(&mut self) -> &mut BoxedLife {
+        if self.dirty {
+            self.life_obj.clean_up();
+            self.dirty = false;
+        }
+        &mut self.life_obj
+    }
+}
+
+/// Runs a script file against a life object, which is left as the script leaves it. Every line
+/// the script prints is passed to print, and pattern names given to load are turned into files
+/// by find.
+pub fn run_script<F, P>(path: &Path, life_obj: &mut BoxedLife, library: &[Pattern], find: F, print: P) -> Result<(), Error>
+    where F: Fn(&str) -> Result<PathBuf, Error> + 'static,
+          P: Fn(&str) + 'static
+{
+    // The grid is shared by the functions the script calls, and handed back once it's done
+    let placeholder: BoxedLife = Box::new(sequential::Life::new());
+    let grid = Rc::new(RefCell::new(Grid { life_obj: mem::replace(life_obj, placeholder), dirty: false }));
+    let result = {
+        let engine = engine(&grid, library.to_vec(), find, print);
+        engine.run_file(path.to_path_buf())
+    };
+    let grid = match Rc::try_unwrap(grid) {
+        Ok(grid) => grid,
+        Err(_) => panic!("the script engine outlived the script"),
+    };
+    *life_obj = grid.into_inner().life_obj;
+    life_obj.clean_up();
+    result.map_err(|e| Error::Script(path.to_path_buf(), e.to_string()))
+}
+
+// Makes an engine with the functions listed at the top of the file, working on the grid
+fn engine<F, P>(grid: &Rc<RefCell<Grid>>, library: Vec<Pattern>, find: F, print: P) -> Engine
+    where F: Fn(&str) -> Result<PathBuf, Error> + 'static,
+          P: Fn(&str) + 'static
+{
+    let mut engine = Engine::new();
+    engine.on_print(print);
+
+    let g = grid.clone();
+    engine.register_fn("set", move |x: INT, y: INT, alive: bool| {
+        let mut grid = g.borrow_mut();
+        grid.life_obj.set((x as isize, y as isize), alive);
+        grid.dirty = true;
+    });
+    let g = grid.clone();
+    engine.register_fn("set_state", move |x: INT, y: INT, state: INT| -> ScriptResult<()> {
+        let mut grid = g.borrow_mut();
+        // The engines only know how to step states that the rule has
+        let num_states = grid.life_obj.num_states();
+        if state < 0 || state >= num_states as INT {
+            return Err(format!("{} is not a state of the rule, which go from 0 to {}", state, num_states - 1).into())
+        }
+        grid.life_obj.set_state((x as isize, y as isize), state as u8);
+        grid.dirty = true;
+        Ok(())
+    });
+    let g = grid.clone();
+    engine.register_fn("get_value", move |x: INT, y: INT| g.borrow_mut().get().get_value((x as isize, y as isize)));
+    let g = grid.clone();
+    engine.register_fn("get_state", move |x: INT, y: INT| g.borrow_mut().get().get_state((x as isize, y as isize)) as INT);
+    let g = grid.clone();
+    engine.register_fn("advance_by", move |n: INT| -> ScriptResult<()> {
+        if n < 0 {
+            return Err(format!("Can't advance by {} generations", n).into())
+        }
+        g.borrow_mut().get().advance_by(n as u64);
+        Ok(())
+    });
+    let g = grid.clone();
+    engine.register_fn("generation", move || g.borrow_mut().get().get_generation() as INT);
+    let g = grid.clone();
+    engine.register_fn("population", move || g.borrow_mut().get().population() as INT);
+    let g = grid.clone();
+    engine.register_fn("live_cells", move || -> Array {
+        let mut grid = g.borrow_mut();
+        let cells: Vec<(isize, isize)> = grid.get().live_cells().collect();
+        to_array(&cells)
+    });
+    let g = grid.clone();
+    engine.register_fn("clear", move || {
+        let mut grid = g.borrow_mut();
+        grid.life_obj.clear();
+        grid.life_obj.set_generation(0);
+        grid.dirty = false;
+    });
+    let g = grid.clone();
+    engine.register_fn("put", move |cells: Array, x: INT, y: INT| -> ScriptResult<()> {
+        let cells = try!(from_array(&cells));
+        let mut grid = g.borrow_mut();
+        for (cx, cy) in cells {
+            grid.life_obj.set((cx + x as isize, cy + y as isize), true);
+        }
+        grid.dirty = true;
+        Ok(())
+    });
+    let g = grid.clone();
+    engine.register_fn("find_period", move |max: INT| -> ScriptResult<Dynamic> {
+        if max < 1 {
+            return Err(format!("Can't look for a period in {} generations", max).into())
+        }
+        let mut grid = g.borrow_mut();
+        Ok(match find_period(grid.get(), max as u64) {
+            Some(period) => {
+                let mut map = Map::new();
+                map.insert("period".into(), Dynamic::from(period.period as INT));
+                map.insert("dx".into(), Dynamic::from(period.dx as INT));
+                map.insert("dy".into(), Dynamic::from(period.dy as INT));
+                Dynamic::from(map)
+            },
+            None => Dynamic::UNIT,
+        })
+    });
+
+    engine.register_fn("load", move |name: &str| -> ScriptResult<Array> {
+        let mut life_obj = sequential::Life::new();
+        let path = try!(find(name).map_err(|e| e.to_string()));
+        try!(read_seed_from_file(&mut life_obj, path).map_err(|e| e.to_string()));
+        let cells: Vec<(isize, isize)> = life_obj.live_cells().collect();
+        Ok(to_array(&to_corner(cells)))
+    });
+    engine.register_fn("pattern", move |name: &str| -> ScriptResult<Array> {
+        match library.iter().find(|p| p.name.to_lowercase() == name.to_lowercase()) {
+            Some(pattern) => Ok(to_array(&pattern.cells)),
+            None => Err(format!("There's no pattern called {} in the library", name).into()),
+        }
+    });
+    engine.register_fn("rotate", |cells: Array, turns: INT| -> ScriptResult<Array> {
+        let mut transform = Transform::new();
+        for _ in 0..((turns % 4) + 4) % 4 {
+            transform = transform.rotate(true);
+        }
+        transformed(&cells, transform)
+    });
+    engine.register_fn("flip", |cells: Array| transformed(&cells, Transform::new().flip()));
+
+    engine
+}
+
+fn transformed(cells: &Array, transform: Transform) -> ScriptResult<Array> {
+    let cells = try!(from_array(cells));
+    Ok(to_array(&to_corner(cells.into_iter().map(|c| transform.apply(c)).collect())))
+}
+
+// Moves cells so the top left corner of their bounds is at (0,0)
+fn to_corner(cells: Vec<(isize, isize)>) -> Vec<(isize, isize)> {
+    let x_min = cells.iter().map(|c| c.0).min().unwrap_or(0);
+    let y_min = cells.iter().map(|c| c.1).min().unwrap_or(0);
+    cells.into_iter().map(|(x, y)| (x - x_min, y - y_min)).collect()
+}
+
+fn to_array(cells: &[(isize, isize)]) -> Array {
+    cells.iter().map(|&(x, y)| {
+        let pair: Array = vec![Dynamic::from(x as INT), Dynamic::from(y as INT)];
+        Dynamic::from(pair)
+    }).collect()
+}
+
+fn from_array(cells: &Array) -> ScriptResult<Vec<(isize, isize)>> {
+    let mut out = vec![];
+    for cell in cells {
+        let pair = cell.clone().try_cast::<Array>().unwrap_or(vec![]);
+        let coordinates: Vec<INT> = pair.iter().filter_map(|c| c.as_int().ok()).collect();
+        if pair.len() != 2 || coordinates.len() != 2 {
+            return Err(format!("Expected cells as [x, y] pairs, not {}", cell).into())
+        }
+        out.push((coordinates[0] as isize, coordinates[1] as isize));
+    }
+    Ok(out)
+}
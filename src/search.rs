@@ -0,0 +1,259 @@
+/*
+
+This file searches for still lifes and oscillators directly, the way
+programs such as gfind and lls do, rather than waiting for them to
+turn up in soups.
+
+A search works on a box of cells. Each cell is given its state in
+every generation of the period at once, as a bitmask, and the rule
+ties each generation to the next, with the last wrapping round to the
+first. Cells are filled in row by row trying every mask, and as soon
+as the nine cells around a cell are all known the rule is checked
+there, backtracking from any mask that breaks it. The cells just
+outside the box must stay dead, so nothing spills out of it. Boxes of
+every size up to the one asked for are searched, keeping patterns
+that reach all four sides of theirs, so each is only found once.
+
+Everything found is then run with a LifeAlgorithm to check it has
+exactly the period asked for (see period.rs), and patterns made of
+parts that would carry on the same way apart, such as two blocks side
+by side, are dropped. What's left is deduplicated by its canonical
+form (see census.rs), taken over all of its phases.
+
+*/
+
+use std::collections::HashSet;
+
+use census::{canonical,split_objects};
+use common::LifeAlgorithm;
+use error::Error;
+use life_algorithms::{Algorithm,BoxedLife,Settings};
+use period::find_period;
+use rules::Rule;
+
+// The most separate parts a pattern is checked for, as every way of splitting them is tried
+const MAX_PARTS: usize = 12;
+
+// The margin of cells kept around a box, so the cells just outside it have all their neighbours
+const MARGIN: usize = 2;
+
+// One box being searched, with the masks given to its cells so far. Masks are stored with a margin
+// of dead cells around the box, so neighbours can be looked up without checking the bounds
+struct Grid {
+    width: usize,
+    height: usize,
+    period: u32,
+    /// The next state of a cell, dead or alive, by its number of live neighbours
+    next: [[bool; 9]; 2],
+    masks: Vec<u32>,
+    /// How far each neighbour of a cell is from it in masks
+    neighbours: [isize; 8],
+    /// The cells to check the rule at, as indexes into masks, once each cell of the box is filled
+    /// in, as the nine cells around them are known by then. Cells just outside the box are included
+    checks: Vec<Vec<usize>>,
+    max_cells: Option<usize>,
+}
+
+impl Grid {
+    fn new(rule: Rule, period: u32, width: usize, height: usize, max_cells: Option<usize>) -> Grid {
+        let mut next = [[false; 9]; 2];
+        for n in 0..9 {
+            next[0][n] = rule.next_value(false, n as u8);
+            next[1][n] = rule.next_value(true, n as u8);
+        }
+        let stride = (width + 2 * MARGIN) as isize;
+        let neighbours = [-stride - 1, -stride, -stride + 1, -1, 1, stride - 1, stride, stride + 1];
+        let mut grid = Grid {
+            width: width,
+            height: height,
+            period: period,
+            next: next,
+            masks: vec![0; (width + 2 * MARGIN) * (height + 2 * MARGIN)],
+            neighbours: neighbours,
+            checks: vec![vec![]; width * height],
+            max_cells: max_cells,
+        };
+        for y in -1..height as isize + 1 {
+            for x in -1..width as isize + 1 {
+                let last_x = (x + 1).min(width as isize - 1);
+                let last_y = (y + 1).min(height as isize - 1);
+                let index = grid.index(x, y);
+                grid.checks[last_y as usize * width + last_x as usize].push(index);
+            }
+        }
+        grid
+    }
+
+    fn index(&self, x: isize, y: isize) -> usize {
+        (y + MARGIN as isize) as usize * (self.width + 2 * MARGIN) + (x + MARGIN as isize) as usize
+    }
+
+    // Whether the rule takes the cell from each generation to the next
+    fn follows_rule(&self, index: usize) -> bool {
+        let mask = self.masks[index];
+        (0..self.period).all(|t| {
+            let count = self.neighbours.iter().filter(|&&n| self.masks[(index as isize + n) as usize] & (1 << t) != 0).count();
+            let alive = (mask >> t) & 1;
+            let next = (mask >> ((t + 1) % self.period)) & 1 != 0;
+            self.next[alive as usize][count] == next
+        })
+    }
+
+    fn occupied<I: Iterator<Item=(usize, usize)>>(&self, mut cells: I) -> bool {
+        cells.any(|(x, y)| self.masks[self.index(x as isize, y as isize)] != 0)
+    }
+
+    // Fills in the cells of the box from the ith on, in rows, passing the first generation of every
+    // pattern that fills the box to found
+    fn fill<F: FnMut(Vec<(isize, isize)>)>(&mut self, i: usize, population: usize, found: &mut F) {
+        let (width, height) = (self.width, self.height);
+        if i == width * height {
+            let sides = self.occupied((0..height).map(|y| (0, y))) &&
+                        self.occupied((0..height).map(|y| (width - 1, y))) &&
+                        self.occupied((0..width).map(|x| (x, height - 1)));
+            if sides {
+                let cells = (0..width * height)
+                    .map(|j| ((j % width) as isize, (j / width) as isize))
+                    .filter(|&(x, y)| self.masks[self.index(x, y)] & 1 != 0)
+                    .collect();
+                found(cells);
+            }
+            return
+        }
+        let index = self.index((i % width) as isize, (i / width) as isize);
+        for mask in 0..1u32 << self.period {
+            let population = population + (mask & 1) as usize;
+            if self.max_cells.map_or(false, |max| population > max) {
+                continue;
+            }
+            self.masks[index] = mask;
+            if !self.checks[i].iter().all(|&c| self.follows_rule(c)) {
+                continue;
+            }
+            if i == width - 1 && !self.occupied((0..width).map(|x| (x, 0))) {
+                continue;
+            }
+            self.fill(i + 1, population, found);
+        }
+        self.masks[index] = 0;
+    }
+}
+
+/// Finds every pattern with exactly the given period that fits in a box of the given size, with at
+/// most max_cells live cells in its first generation if that's given. Period 1 finds still lifes.
+/// Patterns are returned in their canonical form, smallest first. B0 rules aren't supported, as
+/// they'd fill the space around the box.
+pub fn search(rule: Rule, period: u64, size: (usize, usize), max_cells: Option<usize>, algorithm: &Algorithm, settings: &Settings) -> Result<Vec<Vec<(isize, isize)>>, Error> {
+    if rule.is_b0() {
+        return Err(Error::Rule("Searches can't be run under B0 rules".to_string()))
+    }
+    if period < 1 || period > 16 {
+        return Err(Error::Argument(format!("Searches can look for periods from 1 to 16, not {}", period)))
+    }
+
+    let mut life_obj = try!(algorithm.create(settings));
+    let mut seen = HashSet::new();
+    let mut out = vec![];
+    for height in 1..size.1 + 1 {
+        for width in 1..size.0 + 1 {
+            // A square area has every pattern turned both ways, so only one of them is needed
+            if size.0 == size.1 && width > height {
+                continue;
+            }
+            let mut grid = Grid::new(rule, period as u32, width, height, max_cells);
+            grid.fill(0, 0, &mut |cells| {
+                if let Some(shape) = verify(&mut life_obj, &cells, period) {
+                    if seen.insert(shape.clone()) {
+                        out.push(shape);
+                    }
+                }
+            });
+        }
+    }
+    out.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+    Ok(out)
+}
+
+// Runs a pattern to check that it has exactly the period, and can't be split into parts that
+// would carry on the same way apart. Returns its canonical form over all of its phases if so
+fn verify(life_obj: &mut BoxedLife, cells: &[(isize, isize)], period: u64) -> Option<Vec<(isize, isize)>> {
+    let phases = run(life_obj, cells, period);
+    match find_period(life_obj, period) {
+        Some(found) if found.period == period && !found.is_spaceship() => (),
+        _ => return None,
+    }
+
+    // Parts that never touch in any phase, any set of which may run on its own
+    let everywhere: HashSet<(isize, isize)> = phases.iter().flat_map(|p| p.iter().cloned()).collect();
+    let parts = split_objects(everywhere);
+    if parts.len() > 1 && parts.len() <= MAX_PARTS {
+        let part_of = |cell: &(isize, isize)| parts.iter().position(|p| p.contains(cell)).unwrap();
+        // The first part is always in the set and some other part never is, so each split is tried once
+        for set in (0..(1u32 << (parts.len() - 1)) - 1).map(|s| (s << 1) | 1) {
+            let (inside, outside): (Vec<(isize, isize)>, Vec<(isize, isize)>) = cells.iter().partition(|c| set & (1 << part_of(c)) != 0);
+            // Run on into the next period too, as a still life only changes then
+            let inside = run(life_obj, &inside, period + 1);
+            let outside = run(life_obj, &outside, period + 1);
+            let apart = (0..period as usize + 1).all(|t| {
+                let mut both: Vec<(isize, isize)> = inside[t].iter().chain(outside[t].iter()).cloned().collect();
+                both.sort();
+                both == phases[t % period as usize]
+            });
+            if apart {
+                return None
+            }
+        }
+    }
+    phases.iter().map(|p| canonical(p)).min()
+}
+
+// Puts cells on the grid and returns them in each of the given number of generations, leaving the
+// first on the grid
+fn run(life_obj: &mut BoxedLife, cells: &[(isize, isize)], generations: u64) -> Vec<Vec<(isize, isize)>> {
+    life_obj.clear();
+    life_obj.set_generation(0);
+    for &cell in cells {
+        life_obj.set(cell, true);
+    }
+    life_obj.clean_up();
+    let start = life_obj.checkpoint();
+    let mut phases = vec![];
+    for _ in 0..generations {
+        let mut phase: Vec<(isize, isize)> = life_obj.live_cells().collect();
+        phase.sort();
+        phases.push(phase);
+        life_obj.advance_by(1);
+    }
+    life_obj.restore(&start);
+    phases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use census::{Census,to_picture};
+    use life_algorithms::{sequential,RuleKind};
+
+    fn search_life(period: u64, size: (usize, usize), max_cells: Option<usize>) -> Vec<Vec<(isize, isize)>> {
+        let settings = Settings::new(RuleKind::Life(Rule::conway()));
+        search(Rule::conway(), period, size, max_cells, &sequential::ALGORITHM, &settings).unwrap()
+    }
+
+    #[test]
+    fn still_lifes_by_population() {
+        let mut counts = [0; 9];
+        for shape in search_life(1, (8, 8), Some(8)) {
+            counts[shape.len()] += 1;
+        }
+        assert_eq!(counts, [0, 0, 0, 0, 2, 1, 5, 4, 9]);
+    }
+
+    #[test]
+    fn period_2_oscillators_in_a_small_box() {
+        let found = search_life(2, (5, 5), None);
+        let census = Census::new(true);
+        let names: Vec<String> = found.iter().map(|shape| census.name(shape).map_or_else(|| to_picture(shape), |n| n.to_string())).collect();
+        // The clock and the bipole have no names in the census
+        assert_eq!(names, ["blinker", "toad", "..O./OO../..OO/.O..", "beacon", "OO.../O..../.O.O./....O/...OO"]);
+    }
+}
@@ -0,0 +1,176 @@
+/*
+
+This file saves and restores whole simulation sessions: the pattern
+along with its generation, the rule, the algorithm running it and
+where the window was looking, so that a run can be picked up again
+later.
+
+A session file starts with "key: value" lines, ended by a blank line:
+
+    #Rusty Game of Life session
+    algorithm: hashlife
+    rule: B3/S23
+    topology: plane
+    generation: 1234
+    zoom: 1.5
+    offset: -20 30.5
+
+followed by the pattern in any format patterns.rs can read. It's
+written as Macrocell by algorithms that keep a quadtree (hashlife),
+so huge patterns stay small, and as RLE otherwise. Both keep the
+pattern in place. The view lines are left out of sessions saved
+without a window. The plane is the only topology for now.
+
+Under a B0 rule the pattern holds the cells as they are stored (see
+rules.rs), so it's read in at generation 0 and the generation is set
+afterwards.
+
+*/
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use num::BigUint;
+
+use common::LifeAlgorithm;
+use error::Error;
+use patterns::{read_pattern,stored_rle};
+
+const HEADER: &'static str = "#Rusty Game of Life session";
+
+/// Where the window is looking, as kept by the GUI.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct View {
+    pub zoom: f64,
+    pub offset_x: f64,
+    pub offset_y: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub algorithm: String,
+    /// The rule as given on the command line, in B/S notation or as a rule table name or path
+    pub rule: String,
+    pub topology: String,
+    pub generation: BigUint,
+    pub view: Option<View>,
+    /// The text of the pattern, and the line of the file it starts on
+    pattern: String,
+    pattern_line: usize,
+}
+
+impl Session {
+    /// Captures the pattern and generation of a life object, along with everything needed to
+    /// carry on running it.
+    pub fn capture<L: LifeAlgorithm + ?Sized>(life_obj: &L, algorithm: &str, rule: &str, view: Option<View>) -> Session {
+        Session {
+            algorithm: algorithm.to_string(),
+            rule: rule.to_string(),
+            topology: "plane".to_string(),
+            generation: life_obj.get_big_generation(),
+            view: view,
+            pattern: life_obj.to_macrocell().unwrap_or_else(|| stored_rle(life_obj)),
+            pattern_line: 0,
+        }
+    }
+
+    /// Reads a session file.
+    pub fn read(path: &Path) -> Result<Session, Error> {
+        let mut text = String::new();
+        if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut text)) {
+            return Err(Error::Io(path.to_path_buf(), e))
+        }
+        Session::parse(&text).map_err(|e| e.in_file(&path.to_path_buf()))
+    }
+
+    fn parse(text: &str) -> Result<Session, Error> {
+        let (mut algorithm, mut rule, mut generation) = (None, None, None);
+        let mut topology = "plane".to_string();
+        let (mut zoom, mut offset) = (None, None);
+
+        let mut lines = text.lines().enumerate();
+        let mut pattern_line = 0;
+        while let Some((lno, line)) = lines.next() {
+            let line = line.trim();
+            if line == "" {
+                pattern_line = lno+1;
+                break;
+            }
+            if line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.find(':') {
+                Some(i) => (line[..i].trim(), line[i+1..].trim()),
+                None => return Err(Error::syntax(lno+1, 1, "expected a key and a value".to_string())),
+            };
+            let invalid = || Error::syntax(lno+1, 1, format!("invalid {} {}", key, value));
+            match key {
+                "algorithm"  => algorithm = Some(value.to_string()),
+                "rule"       => rule = Some(value.to_string()),
+                "topology"   => {
+                    if value != "plane" {
+                        return Err(Error::syntax(lno+1, 1, format!("unsupported topology {}, only the plane can be simulated", value)))
+                    }
+                    topology = value.to_string();
+                },
+                "generation" => generation = Some(try!(value.parse::<BigUint>().map_err(|_| invalid()))),
+                "zoom"       => zoom = Some(try!(value.parse::<f64>().map_err(|_| invalid()))),
+                "offset"     => {
+                    let xy = try!(value.split_whitespace().map(|n| n.parse::<f64>()).collect::<Result<Vec<f64>, _>>()
+                                       .map_err(|_| invalid()));
+                    if xy.len() != 2 {
+                        return Err(invalid())
+                    }
+                    offset = Some((xy[0], xy[1]));
+                },
+                _ => return Err(Error::syntax(lno+1, 1, format!("unknown key {}", key))),
+            }
+        }
+        if pattern_line == 0 {
+            return Err(Error::syntax(text.lines().count()+1, 1, "missing the pattern after a blank line".to_string()))
+        }
+
+        let missing = |key: &str| Error::syntax(1, 1, format!("missing {}", key));
+        let view = match (zoom, offset) {
+            (Some(zoom), Some((x, y))) => Some(View { zoom: zoom, offset_x: x, offset_y: y }),
+            _ => None,
+        };
+        Ok(Session {
+            algorithm: try!(algorithm.ok_or(missing("algorithm"))),
+            rule: try!(rule.ok_or(missing("rule"))),
+            topology: topology,
+            generation: try!(generation.ok_or(missing("generation"))),
+            view: view,
+            pattern: lines.map(|(_, line)| line).collect::<Vec<&str>>().join("\n"),
+            pattern_line: pattern_line,
+        })
+    }
+
+    /// Writes the session to a file.
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let mut out = format!("{}\nalgorithm: {}\nrule: {}\ntopology: {}\ngeneration: {}\n",
+                              HEADER, self.algorithm, self.rule, self.topology, self.generation);
+        if let Some(view) = self.view {
+            out.push_str(&format!("zoom: {}\noffset: {} {}\n", view.zoom, view.offset_x, view.offset_y));
+        }
+        out.push('\n');
+        out.push_str(&self.pattern);
+        File::create(path).and_then(|mut f| f.write_all(out.as_bytes())).map_err(|e| Error::Io(path.to_path_buf(), e))
+    }
+
+    /// Replaces the contents of a life object with the pattern and generation of the session. The
+    /// life object should already be running the session's rule. The path is only used to report
+    /// errors.
+    pub fn restore<L: LifeAlgorithm + ?Sized>(&self, life_obj: &mut L, path: &Path) -> Result<(), Error> {
+        // Syntax errors are counted from the start of the pattern, so move them to its place in the file
+        let result = read_pattern(life_obj, &self.pattern, path).map_err(|e| match e {
+            Error::Syntax { path, line, column, message } =>
+                Error::Syntax { path: path, line: line + self.pattern_line, column: column, message: message },
+            other => other,
+        });
+        try!(result);
+        life_obj.set_big_generation(&self.generation);
+        Ok(())
+    }
+}
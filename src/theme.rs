@@ -0,0 +1,102 @@
+/*
+
+This file loads the colors the window is drawn in from a theme file,
+given with --theme as a path or as the name of a file in
+assets/themes. A theme file has one "key: color" line per color:
+
+    # A dark theme
+    background: 20 20 30
+    live: 255 200 0
+    grid: 60 60 80
+    selection: 0 160 255 96
+    text: 230 230 230
+
+Colors are red, green and blue from 0 to 255, as in the @COLORS
+section of a rule table, with an optional alpha after them. Colors
+that aren't given keep the built in theme's, which is red cells on
+white. Rule tables with their own colors still use them for cells.
+
+*/
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use error::Error;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub background: [f32; 4],
+    /// The color of live cells under a two state rule
+    pub live: [f32; 4],
+    /// The color of the grid lines drawn when zoomed in
+    pub grid: [f32; 4],
+    /// The color cells under the cursor are highlighted in
+    pub selection: [f32; 4],
+    /// The color of the overlay text
+    pub text: [f32; 4],
+}
+
+impl Theme {
+    /// Returns the built in theme: red cells on white, with black text.
+    pub fn new() -> Theme {
+        Theme {
+            background: [1.0, 1.0, 1.0, 1.0],
+            live: [1.0, 0.0, 0.0, 1.0],
+            grid: [0.8, 0.8, 0.8, 1.0],
+            selection: [0.0, 0.5, 1.0, 0.4],
+            text: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    /// Reads a theme file.
+    pub fn from_file(path: &Path) -> Result<Theme, Error> {
+        let mut text = String::new();
+        if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut text)) {
+            return Err(Error::Io(path.to_path_buf(), e))
+        }
+        Theme::parse(&text).map_err(|e| e.in_file(&path.to_path_buf()))
+    }
+
+    /// Parses the contents of a theme file.
+    pub fn parse(text: &str) -> Result<Theme, Error> {
+        let mut theme = Theme::new();
+        for (lno, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line == "" || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.find(':') {
+                Some(i) => (line[..i].trim(), line[i+1..].trim()),
+                None => return Err(Error::syntax(lno+1, 1, "expected a key and a color".to_string())),
+            };
+            let color = try!(parse_color(value).ok_or_else(|| Error::syntax(lno+1, 1,
+                format!("invalid color {}, expected red, green, blue and an optional alpha from 0 to 255", value))));
+            match key {
+                "background" => theme.background = color,
+                "live"       => theme.live = color,
+                "grid"       => theme.grid = color,
+                "selection"  => theme.selection = color,
+                "text"       => theme.text = color,
+                _ => return Err(Error::syntax(lno+1, 1, format!("unknown key {}", key))),
+            }
+        }
+        Ok(theme)
+    }
+}
+
+// Parses three or four numbers from 0 to 255 into a color, opaque unless the alpha is given
+fn parse_color(value: &str) -> Option<[f32; 4]> {
+    let values: Vec<u8> = match value.split_whitespace().map(|v| v.parse::<u8>()).collect() {
+        Ok(values) => values,
+        Err(_) => return None,
+    };
+    if values.len() != 3 && values.len() != 4 {
+        return None
+    }
+    let mut color = [1.0; 4];
+    for (c, &v) in color.iter_mut().zip(&values) {
+        *c = v as f32 / 255.0;
+    }
+    Some(color)
+}
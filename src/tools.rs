@@ -0,0 +1,179 @@
+/*
+
+This file holds the drawing tools the GUI edits the grid with. Each
+tool works out which cells to change, and paint sets them all through
+LifeAlgorithm::set with a single clean_up at the end, so that drawing
+a big shape doesn't tidy up the grid once per cell.
+
+* Draw: freehand, following the mouse while a button is held.
+* Line: a straight line from where the mouse was pressed to where it
+  was released.
+* Box and filled box: the outline of a rectangle, or all of it.
+* Fill: every cell connected to the one clicked (not diagonally) that
+  has the same value, as long as the region is enclosed. A dead
+  region that reaches past the edge of the pattern isn't.
+* Select: a rectangle that can be filled at random to make a soup.
+
+*/
+
+extern crate rand;
+
+use std::collections::HashSet;
+
+use common::{LifeAlgorithm,Bounds};
+
+// The most cells a flood fill changes
+const MAX_FILL: usize = 1000000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Tool {
+    Draw,
+    Line,
+    Box,
+    FilledBox,
+    Fill,
+    Select,
+}
+
+/// Every tool, in the order of the keys that pick them
+pub const TOOLS: [Tool; 6] = [Tool::Draw, Tool::Line, Tool::Box, Tool::FilledBox, Tool::Fill, Tool::Select];
+
+impl Tool {
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::Draw      => "draw",
+            Tool::Line      => "line",
+            Tool::Box       => "box",
+            Tool::FilledBox => "filled box",
+            Tool::Fill      => "fill",
+            Tool::Select    => "select",
+        }
+    }
+}
+
+/// Returns the rectangle with two cells at opposite corners.
+pub fn between(a: (isize, isize), b: (isize, isize)) -> Bounds {
+    Bounds { x_min: a.0.min(b.0), x_max: a.0.max(b.0), y_min: a.1.min(b.1), y_max: a.1.max(b.1) }
+}
+
+/// Returns the cells on a straight line between two cells, both ends included, with no gaps
+/// between them (Bresenham's algorithm).
+pub fn line(from: (isize, isize), to: (isize, isize)) -> Vec<(isize, isize)> {
+    let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+    let (sx, sy) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+    let (mut x, mut y) = from;
+    let mut error = dx + dy;
+    let mut cells = vec![(x, y)];
+    while (x, y) != to {
+        let e2 = 2 * error;
+        if e2 >= dy {
+            error += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            error += dx;
+            y += sy;
+        }
+        cells.push((x, y));
+    }
+    cells
+}
+
+/// Returns the cells of a rectangle with two cells at opposite corners: all of them if it's filled,
+/// otherwise just its edges.
+pub fn rectangle(a: (isize, isize), b: (isize, isize), filled: bool) -> Vec<(isize, isize)> {
+    let r = between(a, b);
+    let mut cells = vec![];
+    for y in r.y_min..r.y_max+1 {
+        for x in r.x_min..r.x_max+1 {
+            if filled || x == r.x_min || x == r.x_max || y == r.y_min || y == r.y_max {
+                cells.push((x, y));
+            }
+        }
+    }
+    cells
+}
+
+/// Returns the region of cells connected to the start, not diagonally, that have the same value.
+/// Returns None if the region isn't enclosed, because it reaches past the edge of the pattern, or
+/// it has more than MAX_FILL cells.
+pub fn flood_fill<L: LifeAlgorithm + ?Sized>(life_obj: &L, start: (isize, isize)) -> Option<Vec<(isize, isize)>> {
+    if life_obj.population() == 0 {
+        return None
+    }
+    let bounds = life_obj.get_bounds();
+    let value = life_obj.get_value(start);
+    let mut seen = HashSet::new();
+    let mut todo = vec![start];
+    seen.insert(start);
+    while let Some((x, y)) = todo.pop() {
+        if !bounds.contains(x, y) || seen.len() > MAX_FILL {
+            return None
+        }
+        for &next in &[(x+1, y), (x-1, y), (x, y+1), (x, y-1)] {
+            if !seen.contains(&next) && life_obj.get_value(next) == value {
+                seen.insert(next);
+                todo.push(next);
+            }
+        }
+    }
+    Some(seen.into_iter().collect())
+}
+
+/// Sets every one of the cells to the value, and then cleans up.
+pub fn paint<L: LifeAlgorithm + ?Sized>(life_obj: &mut L, cells: &[(isize, isize)], value: bool) {
+    for &cell in cells {
+        life_obj.set(cell, value);
+    }
+    life_obj.clean_up();
+}
+
+/// Makes every cell in the region alive with the given chance, from 0 to 1, and dead otherwise.
+pub fn random_fill<L: LifeAlgorithm + ?Sized>(life_obj: &mut L, region: &Bounds, density: f64) {
+    for y in region.y_min..region.y_max+1 {
+        for x in region.x_min..region.x_max+1 {
+            life_obj.set((x, y), rand::random::<f64>() < density);
+        }
+    }
+    life_obj.clean_up();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use life_algorithms::sequential;
+
+    // The edges of a square with sides of the given length, with its top left corner at (0,0)
+    fn square(side: isize) -> sequential::Life {
+        let mut life_obj = sequential::Life::new();
+        paint(&mut life_obj, &rectangle((0, 0), (side - 1, side - 1), false), true);
+        life_obj
+    }
+
+    #[test]
+    fn enclosed_regions_are_filled() {
+        let mut filled = flood_fill(&square(5), (2, 2)).unwrap();
+        let mut inside = rectangle((1, 1), (3, 3), true);
+        filled.sort();
+        inside.sort();
+        assert_eq!(filled, inside);
+        // Live cells are filled the same way, up to the dead cells around them
+        assert_eq!(flood_fill(&square(5), (0, 0)).unwrap().len(), 16);
+    }
+
+    #[test]
+    fn regions_that_are_not_enclosed_are_not_filled() {
+        let mut life_obj = square(5);
+        paint(&mut life_obj, &[(2, 0)], false);
+        assert_eq!(flood_fill(&life_obj, (2, 2)), None);
+        assert_eq!(flood_fill(&life_obj, (10, 10)), None);
+        assert_eq!(flood_fill(&sequential::Life::new(), (0, 0)), None);
+    }
+
+    #[test]
+    fn fills_are_capped() {
+        let inside = (MAX_FILL as f64).sqrt() as isize;
+        assert_eq!(flood_fill(&square(inside + 2), (1, 1)).map(|cells| cells.len()), Some(MAX_FILL));
+        assert_eq!(flood_fill(&square(inside + 3), (1, 1)), None);
+    }
+}
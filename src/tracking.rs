@@ -0,0 +1,141 @@
+/*
+
+This file keeps track of what each cell has been doing, for the GUI
+to color cells by: how many generations a cell has been alive, how
+often it changed over the latest generations ("heat"), and whether
+it was ever alive at all, like Golly's LifeHistory.
+
+Tracking is an optional engine capability. Engines that advance one
+generation at a time keep a CellTracker, when asked to, and update it
+after every generation (see LifeAlgorithm::set_tracking in common.rs).
+Engines that skip generations, such as hashlife, can't.
+
+Every cell that has ever been alive stays in the tracker, so it grows
+with the area the pattern has covered, and is only emptied when the
+grid is cleared. Under a B0 rule the background flickers every
+generation (see rules.rs), so there's nothing useful to track and
+engines turn tracking down.
+
+*/
+
+use std::collections::HashMap;
+
+/// The most generations heat can look back over
+pub const MAX_HEAT_GENERATIONS: u32 = 64;
+
+/// How the GUI colors cells
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorScheme {
+    /// By state, with the rule's palette
+    States,
+    /// By how many generations each cell has been alive
+    Age,
+    /// By how often each cell changed over the latest generations
+    Heat,
+    /// Live cells by state, and cells that were ever alive tinted
+    History,
+}
+
+/// Every color scheme, in the order the GUI cycles through them
+pub const COLOR_SCHEMES: [ColorScheme; 4] = [ColorScheme::States, ColorScheme::Age, ColorScheme::Heat, ColorScheme::History];
+
+impl ColorScheme {
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorScheme::States  => "states",
+            ColorScheme::Age     => "age",
+            ColorScheme::Heat    => "heat",
+            ColorScheme::History => "history",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ColorScheme> {
+        COLOR_SCHEMES.iter().cloned().find(|s| s.name() == name)
+    }
+
+    /// Returns the scheme after this one, going back to the first after the last
+    pub fn next(self) -> ColorScheme {
+        let i = COLOR_SCHEMES.iter().position(|&s| s == self).unwrap();
+        COLOR_SCHEMES[(i + 1) % COLOR_SCHEMES.len()]
+    }
+
+    /// Returns whether the scheme needs the engine to track cells
+    pub fn tracks_cells(self) -> bool {
+        self != ColorScheme::States
+    }
+}
+
+/// What's known about one cell that has been alive.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CellRecord {
+    pub state: u8,
+    /// Generations the cell has been alive in a row, or 0 if it's dead
+    pub age: u64,
+    // Bit i is set if the cell changed state i generations ago
+    changes: u64,
+    // Whether the cell was alive in the latest update
+    seen: bool,
+}
+
+impl CellRecord {
+    /// Returns how many of the latest generations, up to MAX_HEAT_GENERATIONS, the cell changed
+    /// state in.
+    pub fn heat(&self, generations: u32) -> u32 {
+        let mask = if generations >= MAX_HEAT_GENERATIONS { !0 } else { (1u64 << generations) - 1 };
+        (self.changes & mask).count_ones()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CellTracker {
+    cells: HashMap<(isize, isize), CellRecord>,
+}
+
+impl CellTracker {
+    /// Starts tracking from the live cells of a grid, with their states. They count as having
+    /// been alive for one generation and not having changed.
+    pub fn start<I: Iterator<Item=((isize, isize), u8)>>(live: I) -> CellTracker {
+        let cells = live.map(|(cell, state)| (cell, CellRecord { state: state, age: 1, changes: 0, seen: true })).collect();
+        CellTracker { cells: cells }
+    }
+
+    /// Notes the live cells of a grid, with their states, after it advanced a generation.
+    pub fn update<I: Iterator<Item=((isize, isize), u8)>>(&mut self, live: I) {
+        for record in self.cells.values_mut() {
+            record.changes <<= 1;
+            record.seen = false;
+        }
+        for (cell, state) in live {
+            let record = self.cells.entry(cell).or_insert(CellRecord { state: 0, age: 0, changes: 0, seen: false });
+            if record.state != state {
+                record.changes |= 1;
+            }
+            record.age = if record.state != 0 { record.age + 1 } else { 1 };
+            record.state = state;
+            record.seen = true;
+        }
+        // Whatever is left unseen died this generation
+        for record in self.cells.values_mut() {
+            if !record.seen && record.state != 0 {
+                record.state = 0;
+                record.age = 0;
+                record.changes |= 1;
+            }
+        }
+    }
+
+    /// Gets what's known about a cell, if it has ever been alive
+    pub fn get(&self, cell: (isize, isize)) -> Option<&CellRecord> {
+        self.cells.get(&cell)
+    }
+
+    /// Gets every cell that has ever been alive
+    pub fn cells<'a>(&'a self) -> Box<Iterator<Item=((isize, isize), &'a CellRecord)> + 'a> {
+        Box::new(self.cells.iter().map(|(&cell, record)| (cell, record)))
+    }
+
+    /// Forgets every cell, for when the grid is cleared
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+}